@@ -1,19 +1,29 @@
 use crate::{
     engine::{
         codegen::{self, CodeGenResult},
+        compare_slots::CompareSlots,
+        controls::{Control, UpdateRequest},
         data_transfer::IOData,
         data_transfer::{
-            DynDataCollector, FeedbackData, FeedbackDisplayer, GlobalData, GlobalParameters,
+            DynDataCollector, EngineLoad, FeedbackData, FeedbackDisplayer, GlobalData,
+            GlobalParameters,
         },
+        edit_history::{EditHistory, GraphState},
         julia_thread,
-        parts::ModuleGraph,
+        midi_bindings::{MidiBinding, ResolvedMidiBinding},
+        morph::ResolvedMorph,
+        parts::{Module, ModuleGraph},
+        randomizer::Randomizer,
     },
-    registry::{save_data::Patch, Registry},
+    registry::{patch_metadata::PatchMetadata, recovery::RecoverySlot, save_data::Patch, Registry},
 };
 use crossbeam_channel::{Receiver, Sender, TrySendError};
 use crossbeam_utils::atomic::AtomicCell;
 use julia_helper::GeneratedCode;
-use shared_util::prelude::*;
+use shared_util::{
+    perf_counter::{sections, AtomicPerfCounter},
+    prelude::*,
+};
 use std::{
     path::PathBuf,
     str::FromStr,
@@ -25,8 +35,7 @@ const DEFAULT_CHANNELS: usize = 2;
 const DEFAULT_BUFFER_LENGTH: usize = 512;
 const DEFAULT_SAMPLE_RATE: usize = 44100;
 const FEEDBACK_UPDATE_INTERVAL: Duration = Duration::from_millis(50);
-
-type PreferredPerfCounter = shared_util::perf_counter::SimplePerfCounter;
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(10);
 
 struct UiThreadData {
     registry: Rcrc<Registry>,
@@ -34,6 +43,24 @@ struct UiThreadData {
     dyn_data_collector: DynDataCollector,
     feedback_displayer: FeedbackDisplayer,
     current_patch_save_data: Rcrc<Patch>,
+    midi_learn_target: Option<(Rcrc<Module>, usize)>,
+    /// True while the next MIDI controller that is moved should control the morph amount.
+    learning_morph_controller: bool,
+    edit_history: EditHistory,
+    compare_slots: CompareSlots,
+    /// Whether the audio side is blending between the first two compare slots, see
+    /// `engine::morph`.
+    morph_enabled: bool,
+    randomizer: Randomizer,
+    recovery: RecoverySlot,
+    /// A patch with unsaved changes left behind by a crash, see `registry::recovery`.
+    recovered_patch: Option<Patch>,
+    /// Whether the graph was edited since the current patch was loaded, saved or autosaved.
+    needs_autosave: bool,
+    last_autosave: Instant,
+    /// Total time spent executing each module since the current code was loaded, in the same order
+    /// as the modules in module_graph.
+    module_times: Vec<Duration>,
 }
 
 pub(super) struct Communication {
@@ -41,8 +68,29 @@ pub(super) struct Communication {
 
     pub new_global_params: AtomicCell<Option<()>>,
     pub new_note_graph_code: AtomicCell<Option<(GeneratedCode, Vec<IOData>)>>,
+    /// Code for all the libraries, sent after libraries are reloaded in development mode.
+    pub new_registry_code: AtomicCell<Option<GeneratedCode>>,
     pub new_dyn_data: AtomicCell<Option<Vec<IOData>>>,
     pub new_feedback: AtomicCell<Option<FeedbackData>>,
+    pub new_midi_bindings: AtomicCell<Option<Vec<ResolvedMidiBinding>>>,
+    pub new_morph: AtomicCell<Option<ResolvedMorph>>,
+    /// Between 0 and 1, written by the GUI, the host or the morph controller.
+    pub morph_amount: AtomicCell<f32>,
+    /// The MIDI controller which sets morph_amount when it is moved.
+    pub morph_controller: AtomicCell<Option<usize>>,
+    pub new_module_times: AtomicCell<Option<Vec<Duration>>>,
+    pub new_engine_load: AtomicCell<Option<EngineLoad>>,
+    /// Errors from Julia code which should be shown to the user instead of stopping the engine.
+    pub new_julia_error: AtomicCell<Option<String>>,
+    /// When true, the next MIDI controller that is moved will be stored in learned_controller.
+    pub midi_learn_armed: AtomicCell<bool>,
+    pub learned_controller: AtomicCell<Option<usize>>,
+    /// When true, the seed of each note only depends on patch_seed and how many notes were played
+    /// since the patch was loaded, so rendering the same notes always gives the same result.
+    pub deterministic_mode: AtomicCell<bool>,
+    pub patch_seed: AtomicCell<u32>,
+    /// Written to by both the UI and Julia threads.
+    pub perf_counter: AtomicPerfCounter,
 
     pub global_params: AtomicCell<GlobalParameters>,
     pub note_events: Mutex<Vec<julia_thread::NoteEvent>>,
@@ -85,13 +133,19 @@ pub fn new_engine(
     default_patch
         .borrow()
         .restore_note_graph(&mut module_graph, &*registry)
-        .map_err(|_| {
-            format!(concat!(
-                "Default patch failed to load!\n",
-                "This is a critical error, please submit a bug report containing this ",
-                "error:\n\nPatch data is corrupt.",
-            ))
+        .map_err(|err| {
+            format!(
+                concat!(
+                    "Default patch failed to load!\n",
+                    "This is a critical error, please submit a bug report containing this ",
+                    "error:\n\n{}",
+                ),
+                err
+            )
         })?;
+    // Starting from the default patch is how a new patch is made, so it gets its own seed instead
+    // of sharing the one saved in the default patch.
+    module_graph.set_seed(rand::random());
     let CodeGenResult {
         code,
         dyn_data_collector,
@@ -110,12 +164,25 @@ pub fn new_engine(
     let (polli, pollo) = crossbeam_channel::bounded(0xFF);
     let (audio_resi, audio_reso) = crossbeam_channel::bounded(0);
 
+    let edit_history = EditHistory::new(&module_graph);
+    let recovery = RecoverySlot::new(registry.borrow_library_path());
     let utd = UiThreadData {
         registry: Rc::clone(&registry_ptr),
         module_graph: rcrc(module_graph),
         dyn_data_collector,
         feedback_displayer,
         current_patch_save_data: default_patch,
+        midi_learn_target: None,
+        learning_morph_controller: false,
+        edit_history,
+        compare_slots: CompareSlots::new(),
+        morph_enabled: false,
+        randomizer: Randomizer::new(),
+        recovery,
+        recovered_patch: None,
+        needs_autosave: false,
+        last_autosave: Instant::now(),
+        module_times: Vec::new(),
     };
 
     let atd = AudioThreadData {
@@ -131,8 +198,21 @@ pub fn new_engine(
 
         new_global_params: Default::default(),
         new_note_graph_code: Default::default(),
+        new_registry_code: Default::default(),
         new_dyn_data: Default::default(),
         new_feedback: Default::default(),
+        new_midi_bindings: Default::default(),
+        new_morph: Default::default(),
+        morph_amount: AtomicCell::new(0.0),
+        morph_controller: Default::default(),
+        new_module_times: Default::default(),
+        new_engine_load: Default::default(),
+        new_julia_error: Default::default(),
+        midi_learn_armed: AtomicCell::new(false),
+        learned_controller: Default::default(),
+        deterministic_mode: AtomicCell::new(false),
+        patch_seed: AtomicCell::new(utd.module_graph.borrow().get_seed()),
+        perf_counter: AtomicPerfCounter::new(),
 
         global_params: AtomicCell::new(global_params),
         note_events: Default::default(),
@@ -184,6 +264,34 @@ impl UiThreadEngine {
         patch_ref.write().unwrap();
     }
 
+    /// Patches saved in an older format are converted to the current one so that the metadata
+    /// can be stored with them.
+    pub fn set_current_patch_metadata(&mut self, metadata: PatchMetadata) -> Result<(), String> {
+        assert!(self.data.current_patch_save_data.borrow().is_writable());
+        metadata.check_text_lengths()?;
+        let mut patch_ref = self.data.current_patch_save_data.borrow_mut();
+        if patch_ref.can_store_metadata() {
+            patch_ref.set_metadata(metadata);
+        } else {
+            // The module graph might have unsaved changes, so the saved contents of the patch are
+            // converted instead.
+            let registry = self.data.registry.borrow();
+            let mut graph = ModuleGraph::new();
+            patch_ref
+                .restore_note_graph(&mut graph, &registry)
+                .map_err(|err| {
+                    format!(
+                        "ERROR: Failed to convert the patch to the current format, caused by:\n{}",
+                        err
+                    )
+                })?;
+            patch_ref.set_metadata(metadata);
+            patch_ref.save_note_graph(&graph, &registry);
+        }
+        patch_ref.write().unwrap();
+        Ok(())
+    }
+
     pub fn borrow_registry(&self) -> &Rcrc<Registry> {
         &self.data.registry
     }
@@ -194,6 +302,9 @@ impl UiThreadEngine {
         let reg = self.data.registry.borrow();
         patch_ref.save_note_graph(&*self.data.module_graph.borrow(), &*reg);
         patch_ref.write().unwrap();
+        drop(patch_ref);
+        drop(reg);
+        self.after_save();
     }
 
     pub fn borrow_current_patch(&self) -> &Rcrc<Patch> {
@@ -207,6 +318,27 @@ impl UiThreadEngine {
         patch_ref.serialize()
     }
 
+    /// Returns the current patch along with the id of the recovery file, see `registry::recovery`.
+    pub fn serialize_plugin_state(&self) -> String {
+        let patch_data = self.serialize_current_patch();
+        self.data.recovery.write_state(&patch_data)
+    }
+
+    /// Called with the recovery id from a restored plugin state. If the instance the state
+    /// belonged to crashed with unsaved changes, they become available from
+    /// `borrow_recovered_patch`.
+    pub fn restore_recovery_id(&mut self, id: u64) {
+        let registry = self.data.registry.borrow();
+        match self.data.recovery.restore_id(id, &registry) {
+            Some(Ok(patch)) => self.data.recovered_patch = Some(patch),
+            Some(Err(err)) => {
+                eprintln!("WARNING: {}", err);
+                self.data.recovery.release_leftover();
+            }
+            None => (),
+        }
+    }
+
     pub fn save_current_patch_with_new_name(&mut self) -> &Rcrc<Patch> {
         let mut reg = self.data.registry.borrow_mut();
         let patch = self.borrow_current_patch().borrow();
@@ -229,6 +361,11 @@ impl UiThreadEngine {
         let new_patch = Rc::clone(reg.create_new_user_patch());
         let mut new_patch_ref = new_patch.borrow_mut();
         new_patch_ref.set_name(name);
+        // The copy keeps the author and description but gets its own creation date.
+        new_patch_ref.set_metadata(PatchMetadata {
+            created: 0,
+            ..patch.borrow_metadata().clone()
+        });
         new_patch_ref.save_note_graph(&*self.data.module_graph.borrow(), &*reg);
         new_patch_ref.write().unwrap();
         drop(new_patch_ref);
@@ -236,37 +373,159 @@ impl UiThreadEngine {
         drop(reg);
         // Don't reload anything because we are just copying the current patch data.
         self.data.current_patch_save_data = new_patch;
+        self.after_save();
         &self.data.current_patch_save_data
     }
 
+    /// The recovery file is no longer needed once the changes it holds are saved.
+    fn after_save(&mut self) {
+        self.data.needs_autosave = false;
+        self.data.recovery.clear();
+    }
+
+    /// Also returns a description of any changes made to upgrade the patch, see `load_patch`.
     pub fn new_patch_from_clipboard(
         &mut self,
         clipboard_data: &[u8],
-    ) -> Result<&Rcrc<Patch>, String> {
+    ) -> Result<(&Rcrc<Patch>, Vec<String>), String> {
+        self.new_user_patch_from_data(clipboard_data, "pasted")
+    }
+
+    /// Loads `data` as a new user patch which is not saved yet, with `note` added to its name.
+    fn new_user_patch_from_data(
+        &mut self,
+        data: &[u8],
+        note: &str,
+    ) -> Result<(&Rcrc<Patch>, Vec<String>), String> {
         let mut reg = self.data.registry.borrow_mut();
         let new_patch = Rc::clone(reg.create_new_user_patch());
         let mut new_patch_ref = new_patch.borrow_mut();
-        new_patch_ref.deserialize(clipboard_data)?;
-        let name = format!("{} (pasted)", new_patch_ref.borrow_name());
+        new_patch_ref.deserialize(data)?;
+        let name = format!("{} ({})", new_patch_ref.borrow_name(), note);
         new_patch_ref.set_name(name);
         drop(new_patch_ref);
         drop(reg);
-        self.load_patch(Rc::clone(&new_patch))
-            .map_err(|_| format!("ERROR: Patch data is corrupt."))?;
-        Ok(&self.data.current_patch_save_data)
+        let changes = self.load_patch(Rc::clone(&new_patch))?;
+        Ok((&self.data.current_patch_save_data, changes))
+    }
+
+    /// Returns the patch with unsaved changes which was left behind by a crash, if the user has
+    /// not restored or discarded it yet.
+    pub fn borrow_recovered_patch(&self) -> Option<&Patch> {
+        self.data.recovered_patch.as_ref()
+    }
+
+    /// Loads the recovered patch as a new user patch, see `borrow_recovered_patch`.
+    pub fn restore_recovered_patch(&mut self) -> Result<(&Rcrc<Patch>, Vec<String>), String> {
+        let data = if let Some(patch) = self.data.recovered_patch.take() {
+            patch.serialize()
+        } else {
+            return Err("ERROR: There is no recovered patch to restore.".to_owned());
+        };
+        self.data.recovery.release_leftover();
+        let (_, changes) = self.new_user_patch_from_data(data.as_bytes(), "recovered")?;
+        // The restored changes are not saved anywhere else yet.
+        self.data.needs_autosave = true;
+        Ok((&self.data.current_patch_save_data, changes))
     }
 
-    pub fn load_patch(&mut self, patch: Rcrc<Patch>) -> Result<(), ()> {
+    pub fn discard_recovered_patch(&mut self) {
+        self.data.recovered_patch = None;
+        self.data.recovery.release_leftover();
+    }
+
+    /// Writes the current patch to the recovery file if the graph was edited since the last time
+    /// and enough time has passed. This is called periodically by the GUI.
+    pub fn autosave(&mut self) {
+        if !self.data.needs_autosave || self.data.last_autosave.elapsed() < AUTOSAVE_INTERVAL {
+            return;
+        }
+        self.data.needs_autosave = false;
+        self.data.last_autosave = Instant::now();
+        let result = self.data.recovery.save(
+            &*self.data.module_graph.borrow(),
+            &*self.data.current_patch_save_data.borrow(),
+            &*self.data.registry.borrow(),
+        );
+        if let Err(err) = result {
+            eprintln!(
+                "WARNING: Failed to autosave the current patch, caused by:\n{}",
+                err
+            );
+        }
+    }
+
+    /// Returns a description of each change that was made to upgrade the patch if it was saved
+    /// with older versions of its libraries.
+    pub fn load_patch(&mut self, patch: Rcrc<Patch>) -> Result<Vec<String>, String> {
+        self.cancel_midi_learn();
         let reg = self.data.registry.borrow();
         self.data.current_patch_save_data = patch;
-        self.data
+        let changes = self
+            .data
             .current_patch_save_data
             .borrow()
             .restore_note_graph(&mut *self.data.module_graph.borrow_mut(), &*reg)?;
         drop(reg);
+        self.data
+            .edit_history
+            .clear(&*self.data.module_graph.borrow());
+        self.data.compare_slots.clear();
+        self.data.randomizer.clear_locks();
+        self.data.needs_autosave = false;
+        self.data.recovery.clear();
         self.data.module_graph.borrow().rebuild_widget();
         self.regenerate_code();
-        Ok(())
+        Ok(changes)
+    }
+
+    /// In development mode, reloads any directory libraries whose files have changed and updates
+    /// the current patch to use the new versions of their modules. Returns the names of the
+    /// libraries that were reloaded.
+    pub fn poll_library_changes(&mut self) -> Result<Vec<String>, String> {
+        let changed = self.data.registry.borrow_mut().poll_changed_libraries();
+        if changed.is_empty() {
+            return Ok(changed);
+        }
+        self.cancel_midi_learn();
+        let old_control_names = self.data.module_graph.borrow().control_names();
+        let mut reg = self.data.registry.borrow_mut();
+        let mut errors = Vec::new();
+        for name in &changed {
+            if let Err(err) = reg.reload_library(name) {
+                errors.push(err);
+            }
+        }
+        let registry_code = codegen::generate_registry_code(&*reg);
+        let templates = reg.borrow_templates();
+        self.data
+            .module_graph
+            .borrow_mut()
+            .adapt_to_reloaded_templates(old_control_names, |template| {
+                templates.iter().any(|other| Rc::ptr_eq(template, other))
+            });
+        drop(reg);
+        // Old snapshots may contain controls which no longer match their templates.
+        self.data
+            .edit_history
+            .clear(&*self.data.module_graph.borrow());
+        self.data.compare_slots.clear();
+        match registry_code {
+            Ok(code) => self.comms.new_registry_code.store(Some(code)),
+            Err(err) => errors.push(err),
+        }
+        self.data.module_graph.borrow().rebuild_widget();
+        self.regenerate_code();
+        if errors.is_empty() {
+            Ok(changed)
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    /// Returns the last error produced by Julia code, if there was one since the last call.
+    pub fn take_julia_error(&mut self) -> Option<String> {
+        self.comms.new_julia_error.take()
     }
 
     pub fn borrow_module_graph_ref(&self) -> &Rcrc<ModuleGraph> {
@@ -276,31 +535,450 @@ impl UiThreadEngine {
     pub fn regenerate_code(&mut self) {
         let module_graph_ref = self.data.module_graph.borrow();
         let params = self.comms.global_params.load();
+        let perf_section = self
+            .comms
+            .perf_counter
+            .begin_section(&sections::GENERATE_CODE);
         let new_gen = codegen::generate_code(&*module_graph_ref, &params)
             .map_err(|_| format!("The note graph cannot contain feedback loops"));
+        self.comms.perf_counter.end_section(perf_section);
         let new_gen = new_gen.expect("TODO: Nice error.");
+        self.comms.patch_seed.store(module_graph_ref.get_seed());
         drop(module_graph_ref);
         self.comms.new_dyn_data.store(None);
+        let perf_section = self
+            .comms
+            .perf_counter
+            .begin_section(&sections::COLLECT_DYN_DATA);
         let dyn_data = new_gen.dyn_data_collector.collect();
+        self.comms.perf_counter.end_section(perf_section);
+        // The modules may have been rearranged, so old timings no longer make sense.
+        self.data.module_times.clear();
         self.comms
             .new_note_graph_code
             .store(Some((new_gen.code, dyn_data)));
         self.comms.julia_poll_pipe.send(()).unwrap();
         self.data.dyn_data_collector = new_gen.dyn_data_collector;
         self.data.feedback_displayer = new_gen.feedback_displayer;
+        // The positions of controls in the dyn data may have changed.
+        self.reload_morph();
+        self.reload_midi_bindings();
+    }
+
+    /// Adds any changes made to the module graph since this was last called to the edit history.
+    /// The GUI calls this whenever the user may have finished an edit, like when the mouse is
+    /// released, so that e.g. dragging a knob can be undone in one step.
+    pub fn record_edit(&mut self) {
+        let changed = self
+            .data
+            .edit_history
+            .record(&*self.data.module_graph.borrow());
+        if changed {
+            self.data.needs_autosave = true;
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.data.edit_history.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.data.edit_history.can_redo()
+    }
+
+    pub fn undo(&mut self) {
+        self.record_edit();
+        let result = self
+            .data
+            .edit_history
+            .undo(&mut *self.data.module_graph.borrow_mut());
+        self.after_history_change(result);
+    }
+
+    pub fn redo(&mut self) {
+        self.record_edit();
+        let result = self
+            .data
+            .edit_history
+            .redo(&mut *self.data.module_graph.borrow_mut());
+        self.after_history_change(result);
+    }
+
+    /// Returns which of the `NUM_COMPARE_SLOTS` variations of the current patch is being edited.
+    pub fn get_compare_slot(&self) -> usize {
+        self.data.compare_slots.get_active()
+    }
+
+    /// Switches to another variation of the current patch, see `engine::compare_slots`.
+    pub fn switch_compare_slot(&mut self, index: usize) {
+        self.record_edit();
+        let result = self.data.compare_slots.switch_to(
+            index,
+            &mut *self.data.module_graph.borrow_mut(),
+            &mut self.data.edit_history,
+        );
+        self.after_history_change(result);
+    }
+
+    /// Overwrites the variation in slot `index` with the one being edited.
+    pub fn copy_to_compare_slot(&mut self, index: usize) {
+        self.record_edit();
+        self.data
+            .compare_slots
+            .copy_active_to(index, &*self.data.module_graph.borrow());
+    }
+
+    /// Takes the result of `EditHistory::undo` or `redo` or `CompareSlots::switch_to`.
+    fn after_history_change(&mut self, structure_changed: Option<bool>) {
+        if structure_changed.is_some() {
+            self.data.needs_autosave = true;
+        }
+        match structure_changed {
+            None => (),
+            Some(true) => {
+                self.cancel_midi_learn();
+                self.data.module_graph.borrow().rebuild_widget_in_place();
+                self.regenerate_code();
+            }
+            // Only values changed, so the current code can be kept.
+            Some(false) => {
+                self.reload_dyn_data();
+                self.reload_midi_bindings();
+            }
+        }
     }
 
     pub fn reload_dyn_data(&mut self) {
+        let perf_section = self
+            .comms
+            .perf_counter
+            .begin_section(&sections::COLLECT_DYN_DATA);
         let data = self.data.dyn_data_collector.collect();
+        self.comms.perf_counter.end_section(perf_section);
         self.comms.new_dyn_data.store(Some(data));
+        // The values the morph starts or ends at may have changed.
+        self.reload_morph();
         self.comms.julia_poll_pipe.send(()).unwrap();
     }
 
+    /// Resolves the difference between the first two compare slots, see `engine::morph`.
+    fn resolve_morph(&self) -> Result<ResolvedMorph, String> {
+        let live = GraphState::capture(&*self.data.module_graph.borrow());
+        let slots = &self.data.compare_slots;
+        let snapshot = |index| {
+            if index == slots.get_active() {
+                Some(&live)
+            } else {
+                slots.borrow_snapshot(index)
+            }
+        };
+        let (from, to) = if let (Some(from), Some(to)) = (snapshot(0), snapshot(1)) {
+            (from, to)
+        } else {
+            return Err(concat!(
+                "ERROR: Morphing blends between compare slots A and B, use the buttons at the ",
+                "top to create a second variation of the patch first."
+            )
+            .to_owned());
+        };
+        ResolvedMorph::resolve(from, to, &self.data.dyn_data_collector).ok_or_else(|| {
+            concat!(
+                "ERROR: Morphing only works when compare slots A and B contain the same modules ",
+                "and connections."
+            )
+            .to_owned()
+        })
+    }
+
+    /// Sends the morph to the audio side again after the graph or the compare slots changed. If
+    /// the slots can no longer be morphed between, morphing is turned off.
+    fn reload_morph(&mut self) {
+        if !self.data.morph_enabled {
+            return;
+        }
+        let morph = self.resolve_morph().unwrap_or_else(|_| {
+            self.data.morph_enabled = false;
+            ResolvedMorph::default()
+        });
+        self.comms.new_morph.store(Some(morph));
+    }
+
+    pub fn is_morph_enabled(&self) -> bool {
+        self.data.morph_enabled
+    }
+
+    /// Starts or stops blending between compare slots A and B on the audio side.
+    pub fn set_morph_enabled(&mut self, enabled: bool) -> Result<(), String> {
+        if enabled == self.data.morph_enabled {
+            return Ok(());
+        }
+        if enabled {
+            // Only checks that the slots can be morphed between, reload_dyn_data sends the morph.
+            self.resolve_morph()?;
+        } else {
+            self.comms.new_morph.store(Some(ResolvedMorph::default()));
+        }
+        self.data.morph_enabled = enabled;
+        // This also puts back the values the morph overwrote when it is turned off.
+        self.reload_dyn_data();
+        Ok(())
+    }
+
+    /// Returns how far between A (0) and B (1) the morph is. This can also be changed by the host
+    /// or by a MIDI controller.
+    pub fn get_morph_amount(&self) -> f32 {
+        self.comms.morph_amount.load()
+    }
+
+    pub fn set_morph_amount(&mut self, amount: f32) {
+        self.comms.morph_amount.store(amount.clam(0.0, 1.0));
+    }
+
+    /// The next MIDI controller that is moved will control the morph amount, see
+    /// `update_midi_learn`.
+    pub fn start_morph_midi_learn(&mut self) {
+        self.cancel_midi_learn();
+        self.comms.learned_controller.store(None);
+        self.comms.midi_learn_armed.store(true);
+        self.data.learning_morph_controller = true;
+    }
+
+    pub fn is_learning_morph_controller(&self) -> bool {
+        self.data.learning_morph_controller
+    }
+
+    pub fn get_morph_controller(&self) -> Option<usize> {
+        self.comms.morph_controller.load()
+    }
+
+    pub fn remove_morph_controller(&mut self) {
+        self.comms.morph_controller.store(None);
+    }
+
+    /// Picks random values for the controls in `module`, or in every module if it is None, see
+    /// `engine::randomizer`. A new seed is picked if `seed` is None. Returns the seed that was used.
+    /// The result can be undone in one step.
+    pub fn randomize_controls(&mut self, module: Option<&Rcrc<Module>>, seed: Option<u64>) -> u64 {
+        // Kept short so that it is easy to write down.
+        let seed = seed.unwrap_or_else(|| rand::random::<u32>() as u64);
+        self.record_edit();
+        // Each randomize is undone separately, even when it comes right after a similar edit.
+        self.data.edit_history.stop_merging();
+        let modules = if let Some(module) = module {
+            vec![Rc::clone(module)]
+        } else {
+            self.data.module_graph.borrow().borrow_modules().to_vec()
+        };
+        match self.data.randomizer.randomize(&modules, seed) {
+            UpdateRequest::Nothing => return seed,
+            UpdateRequest::UpdateDynData => self.reload_dyn_data(),
+            UpdateRequest::UpdateCode => self.regenerate_code(),
+        }
+        self.record_edit();
+        self.data.edit_history.stop_merging();
+        seed
+    }
+
+    /// Returns the seed that was used the last time controls were randomized.
+    pub fn get_last_randomize_seed(&self) -> Option<u64> {
+        self.data.randomizer.get_last_seed()
+    }
+
+    /// How far `randomize_controls` can move values, from 0 to 1.
+    pub fn get_randomize_variation(&self) -> f32 {
+        self.data.randomizer.get_variation()
+    }
+
+    pub fn set_randomize_variation(&mut self, variation: f32) {
+        self.data.randomizer.set_variation(variation);
+    }
+
+    /// Locked controls are skipped by `randomize_controls`.
+    pub fn is_control_locked(&self, control: &Rcrc<dyn Control>) -> bool {
+        self.data.randomizer.is_locked(control)
+    }
+
+    pub fn set_control_locked(&mut self, control: &Rcrc<dyn Control>, locked: bool) {
+        self.data.randomizer.set_locked(control, locked);
+    }
+
+    /// In deterministic mode, notes are seeded using the seed of the current patch and how many
+    /// notes have been played since the patch was loaded instead of being seeded randomly.
+    pub fn set_deterministic_mode(&mut self, deterministic: bool) {
+        self.comms.deterministic_mode.store(deterministic);
+    }
+
+    pub fn is_deterministic_mode(&self) -> bool {
+        self.comms.deterministic_mode.load()
+    }
+
+    /// The seed notes are derived from in deterministic mode, which is saved with the patch.
+    pub fn get_patch_seed(&self) -> u32 {
+        self.data.module_graph.borrow().get_seed()
+    }
+
+    pub fn set_patch_seed(&mut self, seed: u32) {
+        self.data.module_graph.borrow_mut().set_seed(seed);
+        self.comms.patch_seed.store(seed);
+        self.data.needs_autosave = true;
+    }
+
+    /// Sends the MIDI bindings of the current module graph to the audio side. This should be called
+    /// whenever a binding is added, removed, or modified.
+    pub fn reload_midi_bindings(&mut self) {
+        let graph = self.data.module_graph.borrow();
+        let mut resolved = Vec::new();
+        for binding in graph.borrow_midi_bindings() {
+            let control = if let Some(control) = binding.get_control() {
+                control
+            } else {
+                continue;
+            };
+            let control = control as Rcrc<dyn Control>;
+            if let Some(dyn_data_index) = self.data.dyn_data_collector.parameter_index_of(&control)
+            {
+                resolved.push(ResolvedMidiBinding {
+                    controller: binding.controller,
+                    dyn_data_index,
+                    range: binding.range,
+                    curve: binding.curve,
+                });
+            }
+        }
+        drop(graph);
+        self.comms.new_midi_bindings.store(Some(resolved));
+        self.comms.julia_poll_pipe.send(()).unwrap();
+    }
+
+    /// The next MIDI controller that is moved will be bound to the specified control. The binding
+    /// is created by update_midi_learn().
+    pub fn start_midi_learn(&mut self, control: &Rcrc<dyn Control>) {
+        let target = self.data.module_graph.borrow().locate_control(control);
+        if target.is_some() {
+            self.comms.learned_controller.store(None);
+            self.comms.midi_learn_armed.store(true);
+        }
+        self.data.midi_learn_target = target;
+    }
+
+    pub fn cancel_midi_learn(&mut self) {
+        self.comms.midi_learn_armed.store(false);
+        self.data.midi_learn_target = None;
+        self.data.learning_morph_controller = false;
+    }
+
+    pub fn is_learning_midi_for(&self, control: &Rcrc<dyn Control>) -> bool {
+        if let Some((module, index)) = &self.data.midi_learn_target {
+            let graph = self.data.module_graph.borrow();
+            graph
+                .locate_control(control)
+                .map_or(false, |(other_module, other_index)| {
+                    Rc::ptr_eq(module, &other_module) && *index == other_index
+                })
+        } else {
+            false
+        }
+    }
+
+    /// Returns the index of the MIDI binding which targets the specified control, if any.
+    pub fn find_midi_binding_for(&self, control: &Rcrc<dyn Control>) -> Option<usize> {
+        let graph = self.data.module_graph.borrow();
+        let (module, index) = graph.locate_control(control)?;
+        graph.borrow_midi_bindings().iter().position(|binding| {
+            Rc::ptr_eq(&binding.module, &module) && binding.control_index == index
+        })
+    }
+
+    pub fn remove_midi_binding(&mut self, index: usize) {
+        self.data
+            .module_graph
+            .borrow_mut()
+            .remove_midi_binding(index);
+        self.reload_midi_bindings();
+    }
+
+    /// If a controller was moved since start_midi_learn() was called, this binds it to the
+    /// control that was specified and returns the number of the controller. The same goes for
+    /// start_morph_midi_learn().
+    pub fn update_midi_learn(&mut self) -> Option<usize> {
+        if self.data.learning_morph_controller {
+            let controller = self.comms.learned_controller.take()?;
+            self.data.learning_morph_controller = false;
+            self.comms.morph_controller.store(Some(controller));
+            return Some(controller);
+        }
+        if self.data.midi_learn_target.is_none() {
+            return None;
+        }
+        let controller = self.comms.learned_controller.take()?;
+        let (module, control_index) = self.data.midi_learn_target.take().unwrap();
+        let mut graph = self.data.module_graph.borrow_mut();
+        // The module may have been removed while we were waiting.
+        graph.index_of_module(&module)?;
+        graph.add_midi_binding(MidiBinding::new(controller, module, control_index)?);
+        drop(graph);
+        self.reload_midi_bindings();
+        Some(controller)
+    }
+
+    /// Returns every module in the graph along with the total time spent executing it since the
+    /// current code was loaded, sorted from slowest to fastest. The result is empty if no timing
+    /// information has been received yet.
+    pub fn get_module_times(&self) -> Vec<(Rcrc<Module>, Duration)> {
+        let graph = self.data.module_graph.borrow();
+        let modules = graph.borrow_modules();
+        if modules.len() != self.data.module_times.len() {
+            return Vec::new();
+        }
+        let mut result: Vec<_> = modules
+            .iter()
+            .map(Rc::clone)
+            .zip(self.data.module_times.iter().cloned())
+            .collect();
+        result.sort_by(|(_, a), (_, b)| b.cmp(a));
+        result
+    }
+
+    /// Returns a human-readable description of where the engine has been spending its time.
+    pub fn perf_report(&self) -> String {
+        let mut report = self.comms.perf_counter.report();
+        let module_times = self.get_module_times();
+        let total: f64 = module_times.iter().map(|(_, t)| t.as_secs_f64()).sum();
+        if total > 0.0 {
+            report += "\n\nMODULE                         | TOTAL TIME | SHARE \n";
+            for (module, time) in &module_times {
+                let time = time.as_secs_f64();
+                report += &format!(
+                    "{:<30} | {:>10} | {:>4.1}% \n",
+                    module.borrow().template.borrow().label,
+                    time.format_metric(6, "s"),
+                    time / total * 100.0
+                );
+            }
+        }
+        report
+    }
+
+    /// Module times are not affected, those are reset whenever new code is loaded.
+    pub fn reset_perf_counter(&mut self) {
+        self.comms.perf_counter.reset();
+    }
+
+    /// Returns how hard the engine has been working since the last time this was called, or None
+    /// if nothing has been rendered since then.
+    pub fn take_engine_load(&mut self) -> Option<EngineLoad> {
+        self.comms.new_engine_load.take()
+    }
+
     /// Feedback data is generated on the audio thread. This method uses a mutex to retrieve that
     /// data and copy it so that it can be displayed in the GUI. Nothing will happen if there is no
     /// new data so this is okay to call relatively often. It also does not block on waiting for
     /// the mutex.
     pub fn display_new_feedback_data(&mut self) {
+        // Module timings are sent alongside feedback data.
+        if let Some(times) = self.comms.new_module_times.take() {
+            self.data.module_times = times;
+        }
         if let Some(data) = self.comms.new_feedback.take() {
             if let Some(widget) = &self.data.module_graph.borrow().current_widget {
                 let widget = Rc::clone(widget);
@@ -352,6 +1030,18 @@ impl AudioThreadEngine {
         );
         assert!(index < 128, "{} is not a valid control index.", index);
         self.data.global_data.controller_values[index] = value;
+        if self.comms.morph_controller.load() == Some(index) {
+            // Controller values go from -1 to 1.
+            self.set_morph_amount((value + 1.0) * 0.5);
+        }
+        if self.comms.midi_learn_armed.swap(false) {
+            self.comms.learned_controller.store(Some(index));
+        }
+    }
+
+    /// Used by the host to automate the morph amount, see `UiThreadEngine::set_morph_amount`.
+    pub fn set_morph_amount(&mut self, amount: f32) {
+        self.comms.morph_amount.store(amount.clam(0.0, 1.0));
     }
 
     pub fn set_bpm(&mut self, bpm: f32) {
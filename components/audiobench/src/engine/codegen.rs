@@ -32,6 +32,15 @@ impl AutomationCode {
     }
 }
 
+fn inert_value_of(typ: JackType) -> &'static str {
+    match typ {
+        JackType::Pitch => "StaticControlSignal(note_input.pitch)",
+        JackType::Waveform => "flat_waveform",
+        JackType::Audio => "StaticMonoAudio(0f0)",
+        JackType::Trigger => "StaticTrigger(false)",
+    }
+}
+
 struct CodeGenerator<'a> {
     graph: &'a ModuleGraph,
     execution_order: Vec<usize>,
@@ -73,6 +82,10 @@ impl<'a> CodeGenerator<'a> {
         for (index, module) in self.graph.borrow_modules().iter().enumerate() {
             let module_ref = module.borrow();
             let template_ref = module_ref.template.borrow();
+            if template_ref.is_placeholder() {
+                code.push_str(&format!("\n    m{}::Nothing", index));
+                continue;
+            }
             code.push_str(&format!(
                 "\n    m{}::Main.Registry.{}.{}Module.StaticData",
                 index, template_ref.lib_name, template_ref.module_name
@@ -86,10 +99,14 @@ impl<'a> CodeGenerator<'a> {
         for (index, module) in self.graph.borrow_modules().iter().enumerate() {
             let module_ref = module.borrow();
             let template_ref = module_ref.template.borrow();
-            code.push_str(&format!(
-                "      Main.Registry.{}.{}Module.static_init()",
-                template_ref.lib_name, template_ref.module_name
-            ));
+            if template_ref.is_placeholder() {
+                code.push_str("      nothing");
+            } else {
+                code.push_str(&format!(
+                    "      Main.Registry.{}.{}Module.static_init()",
+                    template_ref.lib_name, template_ref.module_name
+                ));
+            }
             if index < self.graph.borrow_modules().len() - 1 {
                 code.push_str(",\n");
             }
@@ -107,6 +124,20 @@ impl<'a> CodeGenerator<'a> {
         ));
         code.push_str("  end # function static_init\n\n");
 
+        // Each module's exec function is timed so that the GUI can show which modules are using
+        // the most CPU time.
+        code.push_str(&format!(
+            "  const module_times = zeros(UInt64, {})\n\n",
+            self.graph.borrow_modules().len()
+        ));
+        code.push_str(concat!(
+            "  function take_module_times()\n",
+            "    result = copy(module_times)\n",
+            "    fill!(module_times, 0)\n",
+            "    result\n",
+            "  end # function take_module_times\n\n",
+        ));
+
         code.push_str("  mutable struct FeedbackData\n");
         // code.push_str("    ");
         for (module_index, module_ptr) in self.graph.borrow_modules().iter().enumerate() {
@@ -153,6 +184,19 @@ impl<'a> CodeGenerator<'a> {
             let template_ref = module_ref.template.borrow();
             exec_body.push_str("    \n");
 
+            // Placeholders do nothing, anything connected to them gets a value that has no effect.
+            if template_ref.is_placeholder() {
+                for (output_index, output) in template_ref.outputs.iter().enumerate() {
+                    exec_body.push_str(&format!(
+                        "    m{}o{} = {}\n",
+                        index,
+                        output_index,
+                        inert_value_of(output.get_type())
+                    ));
+                }
+                continue;
+            }
+
             for (control_index, control) in module_ref.controls.iter().enumerate() {
                 let control_ptr = control.as_dyn_ptr();
                 let control = control_ptr.borrow();
@@ -191,7 +235,7 @@ impl<'a> CodeGenerator<'a> {
                 exec_body.push_str("    end\n");
             }
 
-            exec_body.push_str("    ");
+            exec_body.push_str("    exec_start = time_ns()\n    ");
             for output_index in 0..template_ref.outputs.len() {
                 exec_body.push_str(&format!("m{}o{}, ", index, output_index,));
             }
@@ -219,6 +263,10 @@ impl<'a> CodeGenerator<'a> {
                 "\n      static_container[static_index].m{},\n    )\n",
                 index
             ));
+            exec_body.push_str(&format!(
+                "    module_times[{}] += time_ns() - exec_start\n",
+                index + 1
+            ));
         }
         code.push_str("\n  )\n");
         code.push_str(&exec_body);
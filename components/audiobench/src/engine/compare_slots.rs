@@ -0,0 +1,93 @@
+//! Variations of the current patch which are kept in memory so that the user can quickly switch
+//! between them to compare how they sound. Each slot has its own edit history, so undoing after
+//! switching only undoes edits made in that slot. Nothing here is written to disk, saving the
+//! patch saves whichever slot is active.
+
+use crate::engine::{
+    edit_history::{EditHistory, GraphState},
+    parts::ModuleGraph,
+};
+
+pub const NUM_COMPARE_SLOTS: usize = 2;
+
+struct Slot {
+    graph: GraphState,
+    history: EditHistory,
+}
+
+pub struct CompareSlots {
+    /// The active slot is always None since its contents are the module graph itself. Other slots
+    /// are None until they are first used.
+    slots: Vec<Option<Slot>>,
+    active: usize,
+}
+
+impl CompareSlots {
+    pub fn new() -> Self {
+        Self {
+            slots: (0..NUM_COMPARE_SLOTS).map(|_| None).collect(),
+            active: 0,
+        }
+    }
+
+    /// Forgets every slot except the graph itself, used when a different patch is loaded.
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn get_active(&self) -> usize {
+        self.active
+    }
+
+    /// Returns what slot `index` contains, None if it is the active slot or has not been used yet.
+    pub(super) fn borrow_snapshot(&self, index: usize) -> Option<&GraphState> {
+        self.slots[index].as_ref().map(|slot| &slot.graph)
+    }
+
+    /// Stores the graph and its history in the active slot and loads slot `index` in their place.
+    /// A slot which was not used yet starts out as a copy of the active one. Returns None if
+    /// `index` is already active, otherwise whether the structure of the graph changed so that new
+    /// code has to be generated.
+    pub fn switch_to(
+        &mut self,
+        index: usize,
+        graph: &mut ModuleGraph,
+        history: &mut EditHistory,
+    ) -> Option<bool> {
+        assert!(index < NUM_COMPARE_SLOTS);
+        if index == self.active {
+            return None;
+        }
+        let old = GraphState::capture(graph);
+        let structure_changed = if let Some(slot) = self.slots[index].take() {
+            slot.graph.restore(graph);
+            let new_history = slot.history;
+            let structure_changed = !slot.graph.has_same_structure(&old);
+            self.slots[self.active] = Some(Slot {
+                graph: old,
+                history: std::mem::replace(history, new_history),
+            });
+            structure_changed
+        } else {
+            self.slots[self.active] = Some(Slot {
+                graph: old,
+                history: std::mem::replace(history, EditHistory::new(graph)),
+            });
+            false
+        };
+        self.active = index;
+        Some(structure_changed)
+    }
+
+    /// Replaces the contents of slot `index` with a copy of the active slot.
+    pub fn copy_active_to(&mut self, index: usize, graph: &ModuleGraph) {
+        assert!(index < NUM_COMPARE_SLOTS);
+        if index == self.active {
+            return;
+        }
+        self.slots[index] = Some(Slot {
+            graph: GraphState::capture(graph),
+            history: EditHistory::new(graph),
+        });
+    }
+}
@@ -3,7 +3,8 @@ use crate::{
         codegen::AutomationCode,
         controls::{
             DurationControl, FloatInRangeControl, FrequencyControl, InputControl, IntControl,
-            OptionChoiceControl, TimingModeControl, TriggerSequenceControl, ValueSequenceControl,
+            OptionChoiceControl, PlaceholderControl, TimingModeControl, TriggerSequenceControl,
+            ValueSequenceControl,
         },
         data_transfer::{IOData, IOType},
         parts::{JackType, Module},
@@ -11,6 +12,7 @@ use crate::{
     registry::yaml::YamlNode,
 };
 use paste::paste;
+use rand::{Rng, RngCore};
 use shared_util::{
     mini_serde::{MiniDes, MiniSer},
     prelude::*,
@@ -86,12 +88,59 @@ pub trait Control: Debug {
     fn serialize(&self, ser: &mut MiniSer);
 
     fn deserialize(&mut self, des: &mut MiniDes) -> Result<(), ()>;
+
+    /// Describes the value of the control in one line for text patches, see
+    /// `registry::text_patch`. It has to include everything `serialize` writes so that converting
+    /// a patch between formats does not lose anything.
+    fn value_to_text(&self) -> String;
+
+    /// Reads a value written by `value_to_text`. Automation is connected before this is called.
+    fn value_from_text(&mut self, text: &str) -> Result<(), ()>;
+
+    /// Picks random values inside the range the control declares. `variation` is the fraction of
+    /// that range each value can move away from where it currently is, 1.0 allows any value. The
+    /// default implementation leaves controls without a declared range alone.
+    fn randomize(&mut self, _rng: &mut dyn RngCore, _variation: f32) -> UpdateRequest {
+        UpdateRequest::Nothing
+    }
+}
+
+/// Returns a random value in `range` which is at most `variation * (range.1 - range.0)` away from
+/// `current`, used to implement `Control::randomize`.
+pub fn random_in_range(
+    rng: &mut dyn RngCore,
+    current: f32,
+    range: (f32, f32),
+    variation: f32,
+) -> f32 {
+    let distance = (range.1 - range.0) * variation;
+    let low = (current - distance).max(range.0);
+    let high = (current + distance).min(range.1);
+    if low < high {
+        rng.gen_range(low, high)
+    } else {
+        current
+    }
+}
+
+/// Like `random_in_range` but for integers, the result can be either end of the range.
+pub fn random_int_in_range(
+    rng: &mut dyn RngCore,
+    current: i32,
+    range: (i32, i32),
+    variation: f32,
+) -> i32 {
+    let distance = ((range.1 - range.0) as f32 * variation).round() as i32;
+    let low = (current - distance).max(range.0);
+    let high = (current + distance).min(range.1);
+    rng.gen_range(low, high + 1)
 }
 
 macro_rules! any_control_enum {
     ($($control_types:ident),* $(,)?) => {
         paste! {
-            #[derive(Debug)]
+            /// Cloning this only clones the pointer, use `deep_clone` to copy the control itself.
+            #[derive(Clone, Debug)]
             pub enum AnyControl {
                 $($control_types (Rcrc<[<$control_types Control>]>)),*
             }
@@ -113,7 +162,14 @@ macro_rules! any_control_enum {
 
             pub fn from_yaml(yaml: &YamlNode) -> Result<(String, AnyControl), String> {
                 let name = yaml.name.clone();
-                let typ = yaml.value.trim();
+                // A key can't have a value and children in standard YAML, so controls can also be
+                // written as `name:` followed by a single child named after the control type
+                // which contains its settings.
+                let (typ, yaml) = if yaml.value.trim().is_empty() && yaml.children.len() == 1 {
+                    (yaml.children[0].name.trim(), &*yaml.children[0])
+                } else {
+                    (yaml.value.trim(), yaml)
+                };
                 let control = match typ {
                     $(stringify!($control_types) => AnyControl::$control_types(rcrc(
                         [<$control_types Control>]::from_yaml(yaml)?
@@ -141,4 +197,5 @@ any_control_enum! {
     ValueSequence,
     OptionChoice,
     Frequency,
+    Placeholder,
 }
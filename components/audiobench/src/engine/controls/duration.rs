@@ -169,4 +169,23 @@ impl Control for DurationControl {
         }
         Ok(())
     }
+    // Fractions are written like "1/4" so they can be told apart from decimal values.
+    fn value_to_text(&self) -> String {
+        if self.fraction_mode {
+            format!("{}/{}", self.fraction_numerator, self.fraction_denominator)
+        } else {
+            format!("{}", self.decimal_value)
+        }
+    }
+    fn value_from_text(&mut self, text: &str) -> Result<(), ()> {
+        if let Some(slash_index) = text.find('/') {
+            self.fraction_mode = true;
+            self.fraction_numerator = text[..slash_index].trim().parse().map_err(|_| ())?;
+            self.fraction_denominator = text[slash_index + 1..].trim().parse().map_err(|_| ())?;
+        } else {
+            self.fraction_mode = false;
+            self.decimal_value = text.trim().parse().map_err(|_| ())?;
+        }
+        Ok(())
+    }
 }
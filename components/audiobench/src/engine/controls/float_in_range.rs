@@ -1,13 +1,17 @@
 use crate::{
     engine::{
         codegen::AutomationCode,
-        controls::{AutomationSource, Control},
+        controls::{random_in_range, AutomationSource, Control, UpdateRequest},
         data_transfer::{IOData, IOType},
         parts::JackType,
     },
     registry::yaml::YamlNode,
 };
-use shared_util::mini_serde::{MiniDes, MiniSer};
+use rand::RngCore;
+use shared_util::{
+    mini_serde::{MiniDes, MiniSer},
+    prelude::*,
+};
 
 #[derive(Clone, Debug)]
 pub struct AutomationLane {
@@ -47,6 +51,17 @@ impl FloatInRangeControl {
             suffix,
         })
     }
+
+    /// Changes the range of the control, moving the value and the ranges of any automation lanes
+    /// inside the new range if necessary. The default value is left as it is.
+    pub fn set_range(&mut self, range: (f32, f32)) {
+        self.range = range;
+        self.value = self.value.clam(range.0, range.1);
+        for lane in &mut self.automation {
+            lane.range.0 = lane.range.0.clam(range.0, range.1);
+            lane.range.1 = lane.range.1.clam(range.0, range.1);
+        }
+    }
 }
 
 impl Control for FloatInRangeControl {
@@ -141,4 +156,59 @@ impl Control for FloatInRangeControl {
         }
         Ok(())
     }
+
+    fn value_to_text(&self) -> String {
+        if self.automation.len() == 0 {
+            format!("{}", self.value)
+        } else {
+            // One range for each automation lane, like "0..1, 0.5..2".
+            let ranges: Vec<_> = self
+                .automation
+                .iter()
+                .map(|lane| format!("{}..{}", lane.range.0, lane.range.1))
+                .collect();
+            ranges.join(", ")
+        }
+    }
+
+    fn value_from_text(&mut self, text: &str) -> Result<(), ()> {
+        let (min, max) = self.range;
+        // Values outside the range are moved inside it in case the range changed since the patch
+        // was saved.
+        let parse = |text: &str| {
+            let value: f32 = text.trim().parse().map_err(|_| ())?;
+            if value.is_finite() {
+                Ok(value.clam(min, max))
+            } else {
+                Err(())
+            }
+        };
+        if self.automation.len() == 0 {
+            self.value = parse(text)?;
+            return Ok(());
+        }
+        let ranges: Vec<_> = text.split(',').collect();
+        if ranges.len() != self.automation.len() {
+            return Err(());
+        }
+        self.value = self.default;
+        for (lane, range) in self.automation.iter_mut().zip(ranges) {
+            let split = range.find("..").ok_or(())?;
+            lane.range = (parse(&range[..split])?, parse(&range[split + 2..])?);
+        }
+        Ok(())
+    }
+
+    fn randomize(&mut self, rng: &mut dyn RngCore, variation: f32) -> UpdateRequest {
+        let range = self.range;
+        if self.automation.is_empty() {
+            self.value = random_in_range(rng, self.value, range, variation);
+        }
+        // The value is not used while automation is connected, so the lanes are randomized instead.
+        for lane in &mut self.automation {
+            lane.range.0 = random_in_range(rng, lane.range.0, range, variation);
+            lane.range.1 = random_in_range(rng, lane.range.1, range, variation);
+        }
+        UpdateRequest::UpdateDynData
+    }
 }
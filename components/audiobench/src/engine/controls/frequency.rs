@@ -80,4 +80,14 @@ impl Control for FrequencyControl {
             Err(())
         }
     }
+    fn value_to_text(&self) -> String { format!("{}", self.value) }
+    fn value_from_text(&mut self, text: &str) -> Result<(), ()> {
+        let value: f32 = text.trim().parse().map_err(|_| ())?;
+        if (Self::MIN_FREQUENCY..=Self::MAX_FREQUENCY).contains(&value) {
+            self.value = value;
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
 }
@@ -44,11 +44,18 @@ fn default_option_descriptions_for(typ: JackType) -> &'static [DefaultInputDescr
                 icon: "Factory:sine_wave",
             },
         ],
-        JackType::Audio => &[DefaultInputDescription {
-            name: "Silence",
-            code: "StaticMonoAudio(0f0)",
-            icon: "Factory:nothing",
-        }],
+        JackType::Audio => &[
+            DefaultInputDescription {
+                name: "Silence",
+                code: "StaticMonoAudio(0f0)",
+                icon: "Factory:nothing",
+            },
+            DefaultInputDescription {
+                name: "Random Per Note",
+                code: "StaticMonoAudio(note_input.random * 2f0 - 1f0)",
+                icon: "Factory:random",
+            },
+        ],
         JackType::Trigger => &[
             DefaultInputDescription {
                 name: "Note Start",
@@ -69,6 +76,14 @@ fn default_option_descriptions_for(typ: JackType) -> &'static [DefaultInputDescr
     }
 }
 
+/// Names of the default options for `typ` the way they are written in YAML, like `note_start`.
+fn default_option_code_names(typ: JackType) -> Vec<String> {
+    default_option_descriptions_for(typ)
+        .iter()
+        .map(|option| option.name.to_lowercase().replace(' ', "_"))
+        .collect()
+}
+
 #[derive(Clone, Debug)]
 pub struct InputControl {
     typ: JackType,
@@ -80,10 +95,7 @@ impl InputControl {
     pub fn from_yaml(yaml: &YamlNode) -> Result<Self, String> {
         let typ = JackType::from_yaml(yaml.unique_child("type")?)?;
         let default = if let Ok(child) = yaml.unique_child("default") {
-            let mut names = Vec::new();
-            for option in default_option_descriptions_for(typ) {
-                names.push(option.name.to_lowercase().replace(' ', "_"));
-            }
+            let names = default_option_code_names(typ);
             let name_refs: Vec<_> = names.iter().map(|e| &e[..]).collect();
             child.parse_enumerated(&name_refs[..])?
         } else {
@@ -147,4 +159,10 @@ impl Control for InputControl {
         self.default = des.u4()? as _;
         Ok(())
     }
+    fn value_to_text(&self) -> String { default_option_code_names(self.typ).remove(self.default) }
+    fn value_from_text(&mut self, text: &str) -> Result<(), ()> {
+        let names = default_option_code_names(self.typ);
+        self.default = names.iter().position(|name| name == text.trim()).ok_or(())?;
+        Ok(())
+    }
 }
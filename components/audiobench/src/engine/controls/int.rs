@@ -1,11 +1,12 @@
 use crate::{
     engine::{
         codegen::AutomationCode,
-        controls::{Control, UpdateRequest},
+        controls::{random_int_in_range, Control, UpdateRequest},
         data_transfer::{IOData, IOType},
     },
     registry::yaml::YamlNode,
 };
+use rand::RngCore;
 use shared_util::mini_serde::{MiniDes, MiniSer};
 
 #[derive(Clone, Debug)]
@@ -47,6 +48,12 @@ impl IntControl {
     pub fn get_range(&self) -> (i16, i16) {
         self.range
     }
+
+    /// Changes the range of the control, moving the value inside the new range if necessary.
+    pub fn set_range(&mut self, range: (i16, i16)) {
+        self.range = range;
+        self.value = self.value.max(range.0).min(range.1);
+    }
 }
 
 #[rustfmt::skip] // Keeps trying to ruin my perfectly fine one-line functions.
@@ -65,4 +72,17 @@ impl Control for IntControl {
             Ok(())
         }
     }
+    fn value_to_text(&self) -> String { format!("{}", self.value) }
+    fn value_from_text(&mut self, text: &str) -> Result<(), ()> {
+        let value: i16 = text.trim().parse().map_err(|_| ())?;
+        // The range might have changed since the patch was saved.
+        self.value = value.max(self.range.0).min(self.range.1);
+        Ok(())
+    }
+
+    fn randomize(&mut self, rng: &mut dyn RngCore, variation: f32) -> UpdateRequest {
+        let range = (self.range.0 as i32, self.range.1 as i32);
+        let value = random_int_in_range(rng, self.value as i32, range, variation);
+        self.set_value(value as i16)
+    }
 }
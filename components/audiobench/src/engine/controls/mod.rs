@@ -8,6 +8,7 @@ mod frequency;
 mod input;
 mod int;
 mod option_choice;
+mod placeholder;
 mod timing_mode;
 mod trigger_sequence;
 mod value_sequence;
@@ -18,6 +19,7 @@ pub use frequency::*;
 pub use input::*;
 pub use int::*;
 pub use option_choice::*;
+pub use placeholder::*;
 pub use timing_mode::*;
 pub use trigger_sequence::*;
 pub use value_sequence::*;
@@ -1,11 +1,12 @@
 use crate::{
     engine::{
         codegen::AutomationCode,
-        controls::{Control, UpdateRequest},
+        controls::{random_int_in_range, Control, UpdateRequest},
         data_transfer::{IOData, IOType},
     },
     registry::yaml::YamlNode,
 };
+use rand::RngCore;
 use shared_util::mini_serde::{MiniDes, MiniSer};
 
 #[derive(Clone, Debug)]
@@ -73,4 +74,15 @@ impl Control for OptionChoiceControl {
             Ok(())
         }
     }
+    fn value_to_text(&self) -> String { self.options[self.selected_option].clone() }
+    fn value_from_text(&mut self, text: &str) -> Result<(), ()> {
+        self.selected_option = self.options.iter().position(|option| option == text.trim()).ok_or(())?;
+        Ok(())
+    }
+
+    fn randomize(&mut self, rng: &mut dyn RngCore, variation: f32) -> UpdateRequest {
+        let range = (0, self.options.len() as i32 - 1);
+        let option = random_int_in_range(rng, self.selected_option as i32, range, variation);
+        self.set_selected_option(option as usize)
+    }
 }
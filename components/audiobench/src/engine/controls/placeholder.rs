@@ -0,0 +1,55 @@
+use crate::{
+    engine::{
+        codegen::AutomationCode,
+        controls::{AutomationSource, Control},
+        data_transfer::{IOData, IOType},
+        parts::JackType,
+    },
+    registry::yaml::YamlNode,
+};
+use shared_util::mini_serde::{MiniDes, MiniSer, RawSection};
+
+/// Stands in for a control of a module whose library is not installed. It keeps the serialized
+/// data and the automation of the original control so that they can be saved again unchanged.
+#[derive(Clone, Debug, Default)]
+pub struct PlaceholderControl {
+    automation: Vec<AutomationSource>,
+    data: RawSection,
+}
+
+impl PlaceholderControl {
+    pub fn from_yaml(yaml: &YamlNode) -> Result<Self, String> {
+        Err(format!(
+            "ERROR: Placeholder controls cannot be used in modules (found at {}).",
+            &yaml.full_name
+        ))
+    }
+}
+
+#[rustfmt::skip]
+impl Control for PlaceholderControl {
+    // The original control might accept automation but we do not know what kind, so new
+    // connections cannot be made in the GUI. Existing ones are still kept.
+    fn acceptable_automation(&self) -> Vec<JackType> { vec![] }
+    fn connect_automation(&mut self, from: AutomationSource) { self.automation.push(from); }
+    fn get_connected_automation<'a>(&'a self) -> Vec<&'a AutomationSource> {
+        self.automation.iter().collect()
+    }
+    fn remove_automation_by_index(&mut self, index: usize) { self.automation.remove(index); }
+
+    fn get_parameter_types(&self) -> Vec<IOType> { vec![] }
+    fn get_parameter_values(&self) -> Vec<IOData> { vec![] }
+    fn generate_code(&self, _params: &[&str], _automation_code: &AutomationCode) -> String {
+        "nothing".to_owned()
+    }
+    fn serialize(&self, ser: &mut MiniSer) { ser.raw(&self.data); }
+    fn deserialize(&mut self, des: &mut MiniDes) -> Result<(), ()> {
+        self.data = des.rest();
+        Ok(())
+    }
+    fn value_to_text(&self) -> String { self.data.to_bit_string() }
+    fn value_from_text(&mut self, text: &str) -> Result<(), ()> {
+        self.data = RawSection::from_bit_string(text.trim())?;
+        Ok(())
+    }
+}
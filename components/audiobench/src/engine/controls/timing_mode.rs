@@ -80,4 +80,21 @@ impl Control for TimingModeControl {
         self.beat_synchronized = raw_value & 0b10 == 0b10;
         Ok(())
     }
+    // Uses the same words as default_source and default_units, like "song beats".
+    fn value_to_text(&self) -> String {
+        let source = if self.use_elapsed_time { "song" } else { "note" };
+        let units = if self.beat_synchronized { "beats" } else { "seconds" };
+        format!("{} {}", source, units)
+    }
+    fn value_from_text(&mut self, text: &str) -> Result<(), ()> {
+        let words: Vec<_> = text.split_whitespace().collect();
+        match &words[..] {
+            [source, units] => {
+                self.use_elapsed_time = match *source { "note" => false, "song" => true, _ => return Err(()) };
+                self.beat_synchronized = match *units { "seconds" => false, "beats" => true, _ => return Err(()) };
+                Ok(())
+            }
+            _ => Err(()),
+        }
+    }
 }
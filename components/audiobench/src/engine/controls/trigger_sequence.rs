@@ -52,18 +52,34 @@ impl Control for TriggerSequenceControl {
         params[0].to_owned()
     }
     fn serialize(&self, ser: &mut MiniSer) { 
-        assert!(self.sequence.len() <= 0xFF);
-        ser.u8(self.sequence.len() as u8); 
+        ser.u8_or_varint(self.sequence.len());
         for bool in &self.sequence {
             ser.bool(*bool);
         }
     }
     fn deserialize(&mut self, des: &mut MiniDes) -> Result<(), ()> { 
-        let len = des.u8()?;
+        let len = des.u8_or_varint()?;
         self.sequence = Vec::new();
         for _ in 0..len {
             self.sequence.push(des.bool()?);
         }
         Ok(())
     }
+    // Written like "1 0 0 1", one digit for each step.
+    fn value_to_text(&self) -> String {
+        let steps: Vec<_> = self.sequence.iter().map(|step| if *step { "1" } else { "0" }).collect();
+        steps.join(" ")
+    }
+    fn value_from_text(&mut self, text: &str) -> Result<(), ()> {
+        let mut sequence = Vec::new();
+        for step in text.split_whitespace() {
+            sequence.push(match step {
+                "0" => false,
+                "1" => true,
+                _ => return Err(()),
+            });
+        }
+        self.sequence = sequence;
+        Ok(())
+    }
 }
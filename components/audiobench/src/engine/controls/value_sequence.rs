@@ -1,11 +1,12 @@
 use crate::{
     engine::{
         codegen::AutomationCode,
-        controls::{Control, UpdateRequest},
+        controls::{random_in_range, Control, UpdateRequest},
         data_transfer::{IOData, IOType},
     },
     registry::yaml::YamlNode,
 };
+use rand::RngCore;
 use shared_util::mini_serde::{MiniDes, MiniSer};
 
 #[derive(Clone, Debug)]
@@ -57,18 +58,40 @@ impl Control for ValueSequenceControl {
         params[0].to_owned()
     }
     fn serialize(&self, ser: &mut MiniSer) { 
-        assert!(self.sequence.len() <= 0xFF);
-        ser.u8(self.sequence.len() as u8); 
+        ser.u8_or_varint(self.sequence.len());
         for value in &self.sequence {
             ser.f32_in_range(*value, -1.0, 1.0);
         }
     }
     fn deserialize(&mut self, des: &mut MiniDes) -> Result<(), ()> { 
-        let len = des.u8()?;
+        let len = des.u8_or_varint()?;
         self.sequence.clear();
         for _ in 0..len {
             self.sequence.push(des.f32_in_range(-1.0, 1.0)?);
         }
         Ok(())
     }
+    fn value_to_text(&self) -> String {
+        let values: Vec<_> = self.sequence.iter().map(|value| format!("{}", value)).collect();
+        values.join(" ")
+    }
+    fn value_from_text(&mut self, text: &str) -> Result<(), ()> {
+        let mut sequence = Vec::new();
+        for value in text.split_whitespace() {
+            let value: f32 = value.parse().map_err(|_| ())?;
+            if !(-1.0..=1.0).contains(&value) {
+                return Err(());
+            }
+            sequence.push(value);
+        }
+        self.sequence = sequence;
+        Ok(())
+    }
+
+    fn randomize(&mut self, rng: &mut dyn RngCore, variation: f32) -> UpdateRequest {
+        for value in &mut self.sequence {
+            *value = random_in_range(rng, *value, (-1.0, 1.0), variation);
+        }
+        UpdateRequest::UpdateDynData
+    }
 }
@@ -63,6 +63,11 @@ pub struct NoteData {
     pub velocity: f32,
     pub elapsed_samples: usize,
     pub elapsed_beats: f32,
+    /// Unique to each note. In deterministic mode this only depends on the patch seed and how many
+    /// notes were played before this one.
+    pub seed: u32,
+    /// A value between 0 and 1 derived from the seed.
+    pub random: f32,
     pub start_trigger: bool,
     pub release_trigger: bool,
 }
@@ -70,6 +75,33 @@ pub struct NoteData {
 #[derive(Clone, Debug, Default)]
 pub struct FeedbackData(pub Vec<Vec<f32>>);
 
+/// Describes how hard the Julia thread has been working since the last time this was retrieved.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EngineLoad {
+    /// Time spent rendering the most recent buffer divided by how long that buffer lasts. Anything
+    /// above 1 means the audio could not be rendered fast enough to play it in real time.
+    pub cpu_load: f32,
+    /// The highest cpu_load of any buffer rendered since this was last retrieved.
+    pub peak_cpu_load: f32,
+    /// How many buffers took longer to render than they last.
+    pub num_overruns: usize,
+    pub held_voices: usize,
+    pub decaying_voices: usize,
+}
+
+impl EngineLoad {
+    /// Combines this with load data from a later buffer.
+    pub fn then(self, newer: Self) -> Self {
+        Self {
+            cpu_load: newer.cpu_load,
+            peak_cpu_load: self.peak_cpu_load.max(newer.peak_cpu_load),
+            num_overruns: self.num_overruns + newer.num_overruns,
+            held_voices: newer.held_voices,
+            decaying_voices: newer.decaying_voices,
+        }
+    }
+}
+
 /// Represents the data type of a variable which is either an input or output in the generated
 /// program. E.G. `IOType::FloatArray(20)` would be the type of `input [20]FLOAT some_data;`.
 #[derive(Eq, PartialEq, Clone, Copy)]
@@ -96,7 +128,7 @@ impl Display for IOType {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum IOData {
     Bool(bool),
     Int(i32),
@@ -133,6 +165,20 @@ impl DynDataCollector {
         }
         result
     }
+
+    /// Returns the index in the output of collect() where the parameters of the specified control
+    /// start.
+    pub fn parameter_index_of(&self, control: &Rcrc<dyn Control>) -> Option<usize> {
+        let target = Rc::as_ptr(control) as *const ();
+        let mut index = 0;
+        for other in &self.controls {
+            if Rc::as_ptr(other) as *const () == target {
+                return Some(index);
+            }
+            index += other.borrow().get_parameter_types().len();
+        }
+        None
+    }
 }
 
 #[scones::make_constructor]
@@ -0,0 +1,291 @@
+//! Snapshots of the module graph which let the user undo and redo their edits. Instead of every
+//! part of the GUI describing the edits it makes, `EditHistory::record` is called whenever the user
+//! may have finished an edit (e.g. when the mouse is released) and compares the graph with the
+//! last snapshot to find out whether anything changed.
+
+use crate::engine::{
+    controls::{AnyControl, AutomationSource},
+    midi_bindings::{MidiBinding, PlaceholderMidiBinding},
+    parts::{Module, ModuleGraph},
+};
+use shared_util::prelude::*;
+use std::time::{Duration, Instant};
+
+/// How many edits can be undone before the oldest ones are forgotten.
+const MAX_UNDO_STEPS: usize = 100;
+/// Changes to the values of the same controls closer together than this are undone as a single
+/// edit, so that e.g. scrolling over a knob does not create one edit for every step.
+const MERGE_TIME: Duration = Duration::from_millis(1000);
+
+struct ControlState {
+    control: AnyControl,
+    automation: Vec<AutomationSource>,
+    /// From `Control::value_to_text`.
+    value: String,
+}
+
+struct ModuleState {
+    // Keeps removed modules alive so that removing them can be undone.
+    module: Rcrc<Module>,
+    pos: (f32, f32),
+    controls: Vec<ControlState>,
+}
+
+pub(super) struct GraphState {
+    modules: Vec<ModuleState>,
+    midi_bindings: Vec<MidiBinding>,
+    placeholder_midi_bindings: Vec<PlaceholderMidiBinding>,
+}
+
+impl GraphState {
+    pub(super) fn capture(graph: &ModuleGraph) -> Self {
+        let modules = graph
+            .borrow_modules()
+            .iter()
+            .map(|module_ptr| {
+                let module = module_ptr.borrow();
+                let controls = module
+                    .controls
+                    .iter()
+                    .map(|control| {
+                        let control_ptr = control.as_dyn_ptr();
+                        let control_ref = control_ptr.borrow();
+                        ControlState {
+                            control: control.clone(),
+                            automation: control_ref
+                                .get_connected_automation()
+                                .into_iter()
+                                .cloned()
+                                .collect(),
+                            value: control_ref.value_to_text(),
+                        }
+                    })
+                    .collect();
+                ModuleState {
+                    module: Rc::clone(module_ptr),
+                    pos: module.pos,
+                    controls,
+                }
+            })
+            .collect();
+        Self {
+            modules,
+            midi_bindings: graph.borrow_midi_bindings().to_vec(),
+            placeholder_midi_bindings: graph.borrow_placeholder_midi_bindings().to_vec(),
+        }
+    }
+
+    /// Puts `graph` back into the state it was in when this snapshot was captured.
+    pub(super) fn restore(&self, graph: &mut ModuleGraph) {
+        // This severs every module in the graph, so all connections are restored below.
+        graph.set_modules(self.modules.imc(|state| Rc::clone(&state.module)));
+        for state in &self.modules {
+            let mut module = state.module.borrow_mut();
+            module.pos = state.pos;
+            module.controls = state.controls.imc(|control| control.control.clone());
+            for control in &state.controls {
+                let control_ptr = control.control.as_dyn_ptr();
+                let mut control_ref = control_ptr.borrow_mut();
+                let num_sources = control_ref.get_connected_automation().len();
+                for index in (0..num_sources).rev() {
+                    control_ref.remove_automation_by_index(index);
+                }
+                for source in &control.automation {
+                    control_ref.connect_automation(source.clone());
+                }
+                // The text came from the same control, so it is always valid.
+                let result = control_ref.value_from_text(&control.value);
+                debug_assert!(result.is_ok());
+            }
+        }
+        graph.set_midi_bindings(self.midi_bindings.clone());
+        graph.set_placeholder_midi_bindings(self.placeholder_midi_bindings.clone());
+    }
+
+    /// Every control in the snapshot along with its value from `Control::value_to_text`.
+    pub(super) fn control_values(&self) -> impl Iterator<Item = (&AnyControl, &str)> {
+        self.modules.iter().flat_map(|module| {
+            module
+                .controls
+                .iter()
+                .map(|control| (&control.control, &control.value[..]))
+        })
+    }
+
+    /// True if both snapshots contain the same modules, controls and connections, in which case
+    /// switching between them does not require generating new code.
+    pub(super) fn has_same_structure(&self, other: &Self) -> bool {
+        let same_source = |a: &AutomationSource, b: &AutomationSource| {
+            Rc::ptr_eq(&a.module, &b.module) && a.output_index == b.output_index
+        };
+        let same_control = |a: &ControlState, b: &ControlState| {
+            Rc::as_ptr(&a.control.as_dyn_ptr()) as *const ()
+                == Rc::as_ptr(&b.control.as_dyn_ptr()) as *const ()
+                && a.automation.len() == b.automation.len()
+                && a.automation
+                    .iter()
+                    .zip(b.automation.iter())
+                    .all(|(a, b)| same_source(a, b))
+        };
+        let same_module = |a: &ModuleState, b: &ModuleState| {
+            Rc::ptr_eq(&a.module, &b.module)
+                && a.controls.len() == b.controls.len()
+                && a.controls
+                    .iter()
+                    .zip(b.controls.iter())
+                    .all(|(a, b)| same_control(a, b))
+        };
+        self.modules.len() == other.modules.len()
+            && self
+                .modules
+                .iter()
+                .zip(other.modules.iter())
+                .all(|(a, b)| same_module(a, b))
+    }
+
+    fn has_same_layout_and_bindings(&self, other: &Self) -> bool {
+        let same_binding = |a: &MidiBinding, b: &MidiBinding| {
+            a.controller == b.controller
+                && Rc::ptr_eq(&a.module, &b.module)
+                && a.control_index == b.control_index
+                && a.range == b.range
+                && a.curve == b.curve
+        };
+        let same_placeholder_binding = |a: &PlaceholderMidiBinding, b: &PlaceholderMidiBinding| {
+            a.controller == b.controller
+                && Rc::ptr_eq(&a.module, &b.module)
+                && a.control_index == b.control_index
+                && a.raw_range == b.raw_range
+                && a.curve == b.curve
+        };
+        self.modules
+            .iter()
+            .zip(other.modules.iter())
+            .all(|(a, b)| a.pos == b.pos)
+            && self.midi_bindings.len() == other.midi_bindings.len()
+            && self
+                .midi_bindings
+                .iter()
+                .zip(other.midi_bindings.iter())
+                .all(|(a, b)| same_binding(a, b))
+            && self.placeholder_midi_bindings.len() == other.placeholder_midi_bindings.len()
+            && self
+                .placeholder_midi_bindings
+                .iter()
+                .zip(other.placeholder_midi_bindings.iter())
+                .all(|(a, b)| same_placeholder_binding(a, b))
+    }
+
+    /// Returns the module and control index of every control whose value differs between two
+    /// snapshots with the same structure.
+    fn changed_values(&self, other: &Self) -> Vec<(usize, usize)> {
+        let mut changed = Vec::new();
+        for (module_index, (a, b)) in self.modules.iter().zip(other.modules.iter()).enumerate() {
+            for (control_index, (a, b)) in a.controls.iter().zip(b.controls.iter()).enumerate() {
+                if a.value != b.value {
+                    changed.push((module_index, control_index));
+                }
+            }
+        }
+        changed
+    }
+}
+
+pub struct EditHistory {
+    /// What the graph looked like after the last recorded edit.
+    current: GraphState,
+    undo_stack: Vec<GraphState>,
+    redo_stack: Vec<GraphState>,
+    /// Which controls the last edit changed and when, if it only changed control values.
+    last_value_edit: Option<(Vec<(usize, usize)>, Instant)>,
+}
+
+impl EditHistory {
+    pub fn new(graph: &ModuleGraph) -> Self {
+        Self {
+            current: GraphState::capture(graph),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            last_value_edit: None,
+        }
+    }
+
+    /// Forgets all edits, used when a different patch is loaded.
+    pub fn clear(&mut self, graph: &ModuleGraph) {
+        *self = Self::new(graph);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Records any changes made to the graph since the last time this was called. Returns false if
+    /// there were none.
+    pub fn record(&mut self, graph: &ModuleGraph) -> bool {
+        let new = GraphState::capture(graph);
+        let values_only = new.has_same_structure(&self.current)
+            && new.has_same_layout_and_bindings(&self.current);
+        let changed_values = if values_only {
+            let changed = new.changed_values(&self.current);
+            if changed.is_empty() {
+                return false;
+            }
+            Some(changed)
+        } else {
+            None
+        };
+        let now = Instant::now();
+        let merge = match (&self.last_value_edit, &changed_values) {
+            (Some((last_changed, time)), Some(changed)) => {
+                last_changed == changed && now.duration_since(*time) < MERGE_TIME
+            }
+            _ => false,
+        };
+        let old = std::mem::replace(&mut self.current, new);
+        if !merge {
+            self.undo_stack.push(old);
+            if self.undo_stack.len() > MAX_UNDO_STEPS {
+                self.undo_stack.remove(0);
+            }
+        }
+        self.redo_stack.clear();
+        self.last_value_edit = changed_values.map(|changed| (changed, now));
+        true
+    }
+
+    /// Makes sure the next recorded edit becomes its own undo step, even if it changes the same
+    /// values as the last one shortly after it.
+    pub fn stop_merging(&mut self) {
+        self.last_value_edit = None;
+    }
+
+    /// Returns None if there is nothing to undo, otherwise whether the structure of the graph
+    /// changed so that new code has to be generated.
+    pub fn undo(&mut self, graph: &mut ModuleGraph) -> Option<bool> {
+        let state = self.undo_stack.pop()?;
+        Some(self.switch_to(state, graph, false))
+    }
+
+    /// Like `undo`.
+    pub fn redo(&mut self, graph: &mut ModuleGraph) -> Option<bool> {
+        let state = self.redo_stack.pop()?;
+        Some(self.switch_to(state, graph, true))
+    }
+
+    fn switch_to(&mut self, state: GraphState, graph: &mut ModuleGraph, redoing: bool) -> bool {
+        let structure_changed = !state.has_same_structure(&self.current);
+        state.restore(graph);
+        let old = std::mem::replace(&mut self.current, state);
+        if redoing {
+            self.undo_stack.push(old);
+        } else {
+            self.redo_stack.push(old);
+        }
+        self.last_value_edit = None;
+        structure_changed
+    }
+}
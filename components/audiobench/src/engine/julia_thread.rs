@@ -1,11 +1,17 @@
 use crate::engine::{
-    data_transfer::{GlobalData, GlobalParameters, IOData},
+    data_transfer::{EngineLoad, GlobalData, GlobalParameters, IOData},
+    midi_bindings::ResolvedMidiBinding,
+    morph::ResolvedMorph,
     program_wrapper::{AudiobenchExecutor, NoteTracker},
     Communication,
 };
 use crossbeam_channel::{Receiver, Sender};
 use julia_helper::GeneratedCode;
-use std::sync::Arc;
+use shared_util::perf_counter::sections;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 #[derive(Clone, Copy, Eq, PartialEq)]
 pub enum Status {
@@ -95,6 +101,9 @@ pub(super) fn entry(
         executor,
         global_params,
         dyn_data,
+        midi_bindings: Vec::new(),
+        morph: Default::default(),
+        module_times: Vec::new(),
         notes: NoteTracker::new(),
         render_pipe,
         poll_pipe,
@@ -108,6 +117,10 @@ struct JuliaThread {
     executor: AudiobenchExecutor,
     global_params: GlobalParameters,
     dyn_data: Vec<IOData>,
+    midi_bindings: Vec<ResolvedMidiBinding>,
+    morph: ResolvedMorph,
+    /// Total time spent in each module since the current code was loaded.
+    module_times: Vec<Duration>,
     notes: NoteTracker,
     render_pipe: Receiver<RenderRequest>,
     poll_pipe: Receiver<()>,
@@ -146,8 +159,24 @@ impl JuliaThread {
         self.set_status(Status::Error);
     }
 
+    fn report_error(&self, err: String) {
+        eprintln!("{}", err);
+        self.comms.new_julia_error.store(Some(err));
+    }
+
     fn poll_comms(&mut self) {
         self.set_status(Status::Busy);
+        // This has to happen before loading new note graph code because that code uses the
+        // registry code.
+        if let Some(code) = self.comms.new_registry_code.take() {
+            let res = self
+                .executor
+                .change_registry_code(code)
+                .map_err(|err| format!("ERROR: Failed to load library code, caused by:\n{}", err));
+            if let Err(err) = res {
+                self.report_error(err);
+            }
+        }
         if let Some(_) = self.comms.new_global_params.take() {
             let params = self.comms.global_params.load();
             self.executor
@@ -157,35 +186,56 @@ impl JuliaThread {
         } else if let Some((code, dyn_data)) = self.comms.new_note_graph_code.take() {
             self.notes.silence_all();
             self.dyn_data = dyn_data;
+            self.module_times.clear();
+            let perf_section = self
+                .comms
+                .perf_counter
+                .begin_section(&sections::COMPILE_CODE);
             let res = self
                 .executor
                 .change_generated_code(code)
                 .map_err(|err| format!("Error encountered while loading new patch code:\n{}", err));
+            self.comms.perf_counter.end_section(perf_section);
+            // The audio stays silent until code that works is loaded.
             if let Err(err) = res {
-                self.set_status(Status::Error);
-                unimplemented!("Julia error:\n{}", err);
-                // let mut ctd = self.ctd_mux.lock().unwrap();
-                // ctd.julia_thread_status = Status::Error;
-                // ctd.critical_error = Some(err);
-                // return;
+                self.report_error(err);
             }
         } else if let Some(data) = self.comms.new_dyn_data.take() {
             self.dyn_data = data;
         }
+        if let Some(bindings) = self.comms.new_midi_bindings.take() {
+            self.midi_bindings = bindings;
+        }
+        if let Some(morph) = self.comms.new_morph.take() {
+            self.morph = morph;
+        }
     }
 
     fn render(&mut self, global_data: GlobalData, do_feedback: bool) {
+        let render_start = Instant::now();
         self.set_status(Status::Rendering);
         let mut nel = self.comms.note_events.lock().unwrap();
         let note_events = std::mem::replace(&mut *nel, Default::default());
         drop(nel);
+        self.notes.deterministic = self.comms.deterministic_mode.load();
+        self.notes.patch_seed = self.comms.patch_seed.load();
         for event in note_events {
             match event {
                 NoteEvent::StartNote { index, velocity } => {
+                    let perf_section = self.comms.perf_counter.begin_section(&sections::NOTE_SETUP);
                     let static_index = self.notes.start_note(index, velocity);
-                    self.executor
-                        .reset_static_data(static_index)
-                        .expect("TODO: Handle error.");
+                    if self.executor.is_generated_code_loaded() {
+                        if let Err(err) = self.executor.reset_static_data(static_index) {
+                            // Like in the render below, the audio stays silent until code that
+                            // works is loaded.
+                            self.executor.unload_generated_code();
+                            self.report_error(format!(
+                                "ERROR: Failed to set up a new note, caused by:\n{}",
+                                err
+                            ));
+                        }
+                    }
+                    self.comms.perf_counter.end_section(perf_section);
                 }
                 NoteEvent::ReleaseNote { index } => {
                     self.notes.release_note(index);
@@ -193,23 +243,93 @@ impl JuliaThread {
             }
         }
 
+        // These overwrite parts of the dyn data, so they have to happen every time in case new dyn
+        // data was received. MIDI bindings come last so that they take priority over the morph.
+        self.morph
+            .apply(self.comms.morph_amount.load(), &mut self.dyn_data[..]);
+        for binding in &self.midi_bindings {
+            binding.apply(&global_data, &mut self.dyn_data[..]);
+        }
+
         let mut output = vec![0.0; self.global_params.channels * self.global_params.buffer_length];
-        let result = self.executor.execute(
-            do_feedback,
-            &global_data,
-            &mut self.notes,
-            &self.dyn_data[..],
-            &mut output[..],
-        );
-        let new_feedback_data = match result {
-            Ok(v) => v,
-            Err(err) => unimplemented!("Handle Julia error:\n{}", err),
+        let loaded = self.executor.is_generated_code_loaded();
+        let result = if loaded {
+            self.executor.execute(
+                do_feedback,
+                &global_data,
+                &mut self.notes,
+                &self.dyn_data[..],
+                &mut output[..],
+                &self.comms.perf_counter,
+            )
+        } else {
+            Ok(None)
         };
-        if new_feedback_data.is_some() {
-            self.comms.new_feedback.store(new_feedback_data);
+        match result {
+            Ok(Some(new_feedback_data)) => {
+                self.comms.new_feedback.store(Some(new_feedback_data));
+            }
+            Ok(None) => (),
+            Err(err) => {
+                // Keep the audio silent instead of producing the same error over and over.
+                self.executor.unload_generated_code();
+                self.report_error(format!(
+                    "ERROR: Failed to render audio, caused by:\n{}",
+                    err
+                ));
+            }
         }
+        self.report_load(render_start.elapsed());
         self.audio_response_pipe
             .send(AudioResponse { audio: output })
             .unwrap();
+
+        // This happens after sending the audio so that it does not delay it.
+        if do_feedback && self.executor.is_generated_code_loaded() {
+            self.update_module_times();
+        }
+    }
+
+    fn report_load(&mut self, render_time: Duration) {
+        let params = &self.global_params;
+        let buffer_time = params.buffer_length as f32 / params.sample_rate as f32;
+        let cpu_load = render_time.as_secs_f32() / buffer_time;
+        let load = EngineLoad {
+            cpu_load,
+            peak_cpu_load: cpu_load,
+            num_overruns: if cpu_load > 1.0 { 1 } else { 0 },
+            held_voices: self.notes.num_held_notes(),
+            decaying_voices: self.notes.num_decaying_notes(),
+        };
+        // If the UI has not picked up the last report yet, combine them so that peaks and overruns
+        // are not lost.
+        let load = match self.comms.new_engine_load.take() {
+            Some(previous) => previous.then(load),
+            None => load,
+        };
+        self.comms.new_engine_load.store(Some(load));
+    }
+
+    fn update_module_times(&mut self) {
+        let new_times = match self.executor.take_module_times() {
+            Ok(v) => v,
+            Err(err) => {
+                // The audio was already rendered, so only the timings of this buffer are lost.
+                self.report_error(format!(
+                    "ERROR: Failed to collect module timings, caused by:\n{}",
+                    err
+                ));
+                return;
+            }
+        };
+        if self.module_times.len() != new_times.len() {
+            self.module_times = vec![Duration::from_secs(0); new_times.len()];
+        }
+        for (total, new) in self.module_times.iter_mut().zip(new_times.into_iter()) {
+            *total += new;
+        }
+        self.comms
+            .new_module_times
+            .store(Some(self.module_times.clone()));
     }
 }
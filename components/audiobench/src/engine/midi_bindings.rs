@@ -0,0 +1,155 @@
+use crate::engine::{
+    controls::{AnyControl, FloatInRangeControl},
+    data_transfer::{GlobalData, IOData},
+    parts::Module,
+};
+use shared_util::prelude::*;
+
+/// MIDI specifies each MIDI Channel has 128 controls.
+pub const NUM_MIDI_CONTROLLERS: usize = 128;
+
+/// Describes how the position of a hardware controller is mapped onto the range of a binding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MidiCurve {
+    Linear,
+    /// Spends more of the controller's travel on the low end of the range.
+    Exponential,
+    /// Spends more of the controller's travel on the high end of the range.
+    Logarithmic,
+}
+
+impl MidiCurve {
+    pub const ALL: [MidiCurve; 3] = [Self::Linear, Self::Exponential, Self::Logarithmic];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Linear => "Linear",
+            Self::Exponential => "Exponential",
+            Self::Logarithmic => "Logarithmic",
+        }
+    }
+
+    pub fn ordinal(&self) -> u8 {
+        match self {
+            Self::Linear => 0,
+            Self::Exponential => 1,
+            Self::Logarithmic => 2,
+        }
+    }
+
+    pub fn from_ordinal(ordinal: u8) -> Option<Self> {
+        Self::ALL.get(ordinal as usize).cloned()
+    }
+
+    /// Returns the curve that should be used after this one when the user clicks to change it.
+    pub fn next(&self) -> Self {
+        Self::ALL[(self.ordinal() as usize + 1) % Self::ALL.len()]
+    }
+
+    /// Takes a value between 0 and 1 and returns a value between 0 and 1.
+    pub fn apply(&self, value: f32) -> f32 {
+        match self {
+            Self::Linear => value,
+            Self::Exponential => value * value,
+            Self::Logarithmic => value.sqrt(),
+        }
+    }
+}
+
+/// Connects a MIDI controller to the manual value of a FloatInRange control. This is applied
+/// directly to the dynamic data of the generated code, so changing the controller does not require
+/// recompiling anything. Automation lanes on the control are not affected.
+#[derive(Clone, Debug)]
+pub struct MidiBinding {
+    pub controller: usize,
+    pub module: Rcrc<Module>,
+    pub control_index: usize,
+    /// The values the control should have when the controller is at its minimum and maximum.
+    pub range: (f32, f32),
+    pub curve: MidiCurve,
+}
+
+impl MidiBinding {
+    /// Creates a binding which sweeps the entire range of the specified control. Returns None if
+    /// the control is not a FloatInRange control.
+    pub fn new(controller: usize, module: Rcrc<Module>, control_index: usize) -> Option<Self> {
+        assert!(controller < NUM_MIDI_CONTROLLERS);
+        let range = {
+            let module_ref = module.borrow();
+            if let Some(AnyControl::FloatInRange(control)) = module_ref.controls.get(control_index)
+            {
+                control.borrow().range
+            } else {
+                return None;
+            }
+        };
+        Some(Self {
+            controller,
+            module,
+            control_index,
+            range,
+            curve: MidiCurve::Linear,
+        })
+    }
+
+    pub fn get_control(&self) -> Option<Rcrc<FloatInRangeControl>> {
+        let module_ref = self.module.borrow();
+        if let Some(AnyControl::FloatInRange(control)) = module_ref.controls.get(self.control_index)
+        {
+            Some(Rc::clone(control))
+        } else {
+            None
+        }
+    }
+
+    /// Returns something like "Envelope > attack_time" for displaying in the GUI.
+    pub fn describe_target(&self) -> String {
+        let module_ref = self.module.borrow();
+        let template_ref = module_ref.template.borrow();
+        let control_name = template_ref
+            .default_controls
+            .get(self.control_index)
+            .map(|(name, _)| &name[..])
+            .unwrap_or("?");
+        format!("{} > {}", template_ref.label, control_name)
+    }
+}
+
+/// A MIDI binding to a control of a placeholder module, see `ModuleTemplate::new_placeholder`. It
+/// does nothing but is saved again unchanged.
+#[derive(Clone, Debug)]
+pub struct PlaceholderMidiBinding {
+    pub controller: usize,
+    pub module: Rcrc<Module>,
+    pub control_index: usize,
+    /// The range of the control is not known, so the range of the binding is kept in the form
+    /// `MiniSer::f32_in_range` writes it in.
+    pub raw_range: (u16, u16),
+    pub curve: MidiCurve,
+}
+
+/// A MIDI binding which has been converted into a form that can be applied on the audio side
+/// without needing to look at the module graph.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResolvedMidiBinding {
+    pub controller: usize,
+    /// Index into the dyn data produced by a DynDataCollector.
+    pub dyn_data_index: usize,
+    pub range: (f32, f32),
+    pub curve: MidiCurve,
+}
+
+impl ResolvedMidiBinding {
+    pub fn apply(&self, global_data: &GlobalData, dyn_data: &mut [IOData]) {
+        // Controller values go from -1 to 1.
+        let position = (global_data.controller_values[self.controller] + 1.0) * 0.5;
+        let position = self.curve.apply(position.clam(0.0, 1.0));
+        let value = position.to_range(self.range.0, self.range.1);
+        if let Some(IOData::FloatArray(values)) = dyn_data.get_mut(self.dyn_data_index) {
+            // The first value is the manual value of the control, the rest describe automation.
+            if let Some(manual_value) = values.get_mut(0) {
+                *manual_value = value;
+            }
+        }
+    }
+}
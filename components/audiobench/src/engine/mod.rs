@@ -1,9 +1,15 @@
 mod base;
 mod codegen;
+mod compare_slots;
 pub mod controls;
 pub mod data_transfer;
+mod edit_history;
 mod julia_thread;
+pub mod midi_bindings;
+mod morph;
 pub mod parts;
 mod program_wrapper;
+mod randomizer;
 
 pub use base::*;
+pub use compare_slots::NUM_COMPARE_SLOTS;
@@ -0,0 +1,125 @@
+//! Blends between the variations of the current patch in the first two compare slots, see
+//! `engine::compare_slots`. This only works when both variations have the same modules and
+//! connections, so that they only differ in the values of their controls. The difference is
+//! resolved into two versions of the dyn data of each control which differs, which are blended on
+//! the audio side so that the morph can be automated without the GUI being open and without
+//! recompiling anything. The module graph itself is not changed, so saving the patch saves the
+//! active slot as it is.
+
+use crate::engine::{
+    controls::AnyControl,
+    data_transfer::{DynDataCollector, IOData},
+    edit_history::GraphState,
+};
+
+struct MorphItem {
+    /// Index into the dyn data produced by a DynDataCollector.
+    dyn_data_index: usize,
+    from: Vec<IOData>,
+    to: Vec<IOData>,
+    /// True for FloatInRange controls, whose manual value and automation lane ranges are
+    /// interpolated. Other controls switch from one value to the other halfway through.
+    interpolate: bool,
+}
+
+/// A morph which has been converted into a form that can be applied on the audio side without
+/// needing to look at the module graph. The default value does nothing.
+#[derive(Default)]
+pub struct ResolvedMorph {
+    items: Vec<MorphItem>,
+}
+
+impl ResolvedMorph {
+    /// Returns None if the snapshots do not have the same structure. One of them has to be a
+    /// snapshot of the graph `collector` was generated for.
+    pub(super) fn resolve(
+        from: &GraphState,
+        to: &GraphState,
+        collector: &DynDataCollector,
+    ) -> Option<Self> {
+        if !from.has_same_structure(to) {
+            return None;
+        }
+        let mut items = Vec::new();
+        for ((control, from_text), (_, to_text)) in from.control_values().zip(to.control_values()) {
+            if from_text == to_text {
+                continue;
+            }
+            let dyn_data_index =
+                if let Some(index) = collector.parameter_index_of(&control.as_dyn_ptr()) {
+                    index
+                } else {
+                    continue;
+                };
+            // Copies of the control are used so that the graph itself is not changed.
+            let values = |text: &str| {
+                let copy = control.deep_clone().as_dyn_ptr();
+                let mut copy = copy.borrow_mut();
+                copy.value_from_text(text).ok()?;
+                Some(copy.get_parameter_values())
+            };
+            if let (Some(from), Some(to)) = (values(from_text), values(to_text)) {
+                items.push(MorphItem {
+                    dyn_data_index,
+                    from,
+                    to,
+                    interpolate: matches!(control, AnyControl::FloatInRange(..)),
+                });
+            }
+        }
+        Some(Self { items })
+    }
+
+    /// Overwrites the parts of `dyn_data` which differ between the two variations. An amount of 0
+    /// gives the first variation and 1 gives the second. This runs on the audio thread, so values
+    /// are written into the existing arrays instead of allocating new ones.
+    pub fn apply(&self, amount: f32, dyn_data: &mut [IOData]) {
+        for item in &self.items {
+            let values = item.from.iter().zip(item.to.iter());
+            for (offset, (from, to)) in values.enumerate() {
+                let target = if let Some(target) = dyn_data.get_mut(item.dyn_data_index + offset) {
+                    target
+                } else {
+                    continue;
+                };
+                match (from, to, target) {
+                    (
+                        IOData::FloatArray(from),
+                        IOData::FloatArray(to),
+                        IOData::FloatArray(target),
+                    ) if item.interpolate
+                        && from.len() == to.len()
+                        && from.len() == target.len() =>
+                    {
+                        // The values of automation lanes are linear in the range of the lane, so
+                        // interpolating them interpolates the range.
+                        let values = from.iter().zip(to.iter());
+                        for (target, (a, b)) in target.iter_mut().zip(values) {
+                            *target = a + (b - a) * amount;
+                        }
+                    }
+                    (from, _, target) if amount < 0.5 => copy_into(target, from),
+                    (_, to, target) => copy_into(target, to),
+                }
+            }
+        }
+    }
+}
+
+/// Sets `target` to `source`, reusing the memory of `target` if they are arrays of the same size.
+fn copy_into(target: &mut IOData, source: &IOData) {
+    match (target, source) {
+        (IOData::BoolArray(target), IOData::BoolArray(source)) if target.len() == source.len() => {
+            target.copy_from_slice(source)
+        }
+        (IOData::IntArray(target), IOData::IntArray(source)) if target.len() == source.len() => {
+            target.copy_from_slice(source)
+        }
+        (IOData::FloatArray(target), IOData::FloatArray(source))
+            if target.len() == source.len() =>
+        {
+            target.copy_from_slice(source)
+        }
+        (target, source) => *target = source.clone(),
+    }
+}
@@ -1,5 +1,8 @@
 use crate::{
-    engine::controls::{AnyControl, Control},
+    engine::{
+        controls::{AnyControl, Control},
+        midi_bindings::{MidiBinding, PlaceholderMidiBinding},
+    },
     gui::top_level::graph::ModuleGraph as ModuleGraphWidget,
     registry::{module_template::ModuleTemplate, yaml::YamlNode},
 };
@@ -71,6 +74,19 @@ impl IOJack {
         }
     }
 
+    /// Creates an output for a placeholder module, see `ModuleTemplate::new_placeholder`.
+    pub fn placeholder(typ: JackType, icon_index: usize, index: usize) -> Self {
+        Self {
+            typ,
+            icon_index,
+            custom_icon_index: None,
+            code_name: format!("output{}", index),
+            label: format!("Output {}", index + 1),
+            tooltip: "Output of a missing module, it does not change anything it is connected to"
+                .to_owned(),
+        }
+    }
+
     pub fn get_type(&self) -> JackType {
         self.typ
     }
@@ -155,6 +171,11 @@ impl Module {
 
 pub struct ModuleGraph {
     modules: Vec<Rcrc<Module>>,
+    midi_bindings: Vec<MidiBinding>,
+    placeholder_midi_bindings: Vec<PlaceholderMidiBinding>,
+    /// Combined with a note counter to produce the seed of each note when the engine is in
+    /// deterministic mode.
+    seed: u32,
     pub current_widget: Option<Rc<ModuleGraphWidget>>,
 }
 
@@ -162,6 +183,9 @@ impl ModuleGraph {
     pub fn new() -> Self {
         Self {
             modules: Vec::new(),
+            midi_bindings: Vec::new(),
+            placeholder_midi_bindings: Vec::new(),
+            seed: 0,
             current_widget: None,
         }
     }
@@ -177,6 +201,10 @@ impl ModuleGraph {
 
     fn remove_index(&mut self, index: usize) {
         let module = self.modules.remove(index);
+        self.midi_bindings
+            .retain(|binding| !Rc::ptr_eq(&binding.module, &module));
+        self.placeholder_midi_bindings
+            .retain(|binding| !Rc::ptr_eq(&binding.module, &module));
         module.borrow_mut().sever();
         for other in &self.modules {
             other.borrow_mut().sever_connections_from(&module);
@@ -197,6 +225,64 @@ impl ModuleGraph {
             module.borrow_mut().sever();
         }
         self.modules.clear();
+        self.midi_bindings.clear();
+        self.placeholder_midi_bindings.clear();
+    }
+
+    /// Adds a new MIDI binding, replacing any other binding that targets the same control.
+    pub fn add_midi_binding(&mut self, binding: MidiBinding) {
+        self.midi_bindings.retain(|other| {
+            !Rc::ptr_eq(&other.module, &binding.module)
+                || other.control_index != binding.control_index
+        });
+        self.midi_bindings.push(binding);
+    }
+
+    pub fn set_midi_bindings(&mut self, bindings: Vec<MidiBinding>) {
+        self.midi_bindings = bindings;
+    }
+
+    pub fn remove_midi_binding(&mut self, index: usize) {
+        self.midi_bindings.remove(index);
+    }
+
+    pub fn borrow_midi_bindings(&self) -> &[MidiBinding] {
+        &self.midi_bindings[..]
+    }
+
+    pub fn borrow_midi_bindings_mut(&mut self) -> &mut [MidiBinding] {
+        &mut self.midi_bindings[..]
+    }
+
+    pub fn set_placeholder_midi_bindings(&mut self, bindings: Vec<PlaceholderMidiBinding>) {
+        self.placeholder_midi_bindings = bindings;
+    }
+
+    pub fn borrow_placeholder_midi_bindings(&self) -> &[PlaceholderMidiBinding] {
+        &self.placeholder_midi_bindings[..]
+    }
+
+    pub fn get_seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u32) {
+        self.seed = seed;
+    }
+
+    /// Returns the module containing the specified control and the index of the control in that
+    /// module.
+    pub fn locate_control(&self, control: &Rcrc<dyn Control>) -> Option<(Rcrc<Module>, usize)> {
+        let target = Rc::as_ptr(control) as *const ();
+        for module in &self.modules {
+            let module_ref = module.borrow();
+            for (index, other) in module_ref.controls.iter().enumerate() {
+                if Rc::as_ptr(&other.as_dyn_ptr()) as *const () == target {
+                    return Some((Rc::clone(module), index));
+                }
+            }
+        }
+        None
     }
 
     pub fn rebuild_widget(&self) {
@@ -205,6 +291,13 @@ impl ModuleGraph {
         }
     }
 
+    /// Like `rebuild_widget` but keeps the view of the graph where it is.
+    pub fn rebuild_widget_in_place(&self) {
+        if let Some(widget) = &self.current_widget {
+            widget.rebuild_in_place();
+        }
+    }
+
     pub fn borrow_modules(&self) -> &[Rcrc<Module>] {
         &self.modules[..]
     }
@@ -215,6 +308,123 @@ impl ModuleGraph {
             .position(|other| Rc::ptr_eq(module, other))
     }
 
+    /// Returns the names of the controls of each module according to its template, for use with
+    /// `adapt_to_reloaded_templates`.
+    pub fn control_names(&self) -> Vec<Vec<String>> {
+        self.modules
+            .iter()
+            .map(|module| {
+                let module = module.borrow();
+                let template = module.template.borrow();
+                template.default_controls.imc(|(name, _)| name.clone())
+            })
+            .collect()
+    }
+
+    /// Makes modules consistent with their templates after the templates were reloaded.
+    /// `old_control_names` is what `control_names` returned before the templates changed. Controls
+    /// are matched by name and keep their values and connections, other controls get their
+    /// default values. Connections from outputs that no longer exist and MIDI bindings to controls
+    /// that no longer exist are removed, as are modules whose template no longer exists.
+    pub fn adapt_to_reloaded_templates(
+        &mut self,
+        old_control_names: Vec<Vec<String>>,
+        template_exists: impl Fn(&Rcrc<ModuleTemplate>) -> bool,
+    ) {
+        // For each module, which new index each old control ended up at.
+        let mut index_maps = Vec::new();
+        for (module, old_names) in self.modules.iter().zip(old_control_names) {
+            let mut module = module.borrow_mut();
+            let template_ptr = Rc::clone(&module.template);
+            let template = template_ptr.borrow();
+            if template.is_placeholder() {
+                index_maps.push(None);
+                continue;
+            }
+            let mut old_controls: Vec<_> = std::mem::take(&mut module.controls)
+                .into_iter()
+                .map(Some)
+                .collect();
+            let mut index_map = vec![None; old_names.len()];
+            for (new_index, (name, default)) in template.default_controls.iter().enumerate() {
+                let old_index = old_names.iter().position(|old_name| old_name == name);
+                let old = old_index.and_then(|index| old_controls[index].take());
+                let control = match old {
+                    Some(old)
+                        if std::mem::discriminant(&old) == std::mem::discriminant(default) =>
+                    {
+                        index_map[old_index.unwrap()] = Some(new_index);
+                        old
+                    }
+                    _ => default.deep_clone(),
+                };
+                module.controls.push(control);
+            }
+            index_maps.push(Some(index_map));
+        }
+
+        for module in &self.modules {
+            let module = module.borrow();
+            if module.template.borrow().is_placeholder() {
+                continue;
+            }
+            for control in &module.controls {
+                let control_ptr = control.as_dyn_ptr();
+                let mut control = control_ptr.borrow_mut();
+                let acceptable = control.acceptable_automation();
+                let condemned: Vec<_> = control
+                    .get_connected_automation()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, source)| {
+                        let source_module = source.module.borrow();
+                        let source_template = source_module.template.borrow();
+                        let still_valid = source_template
+                            .outputs
+                            .get(source.output_index)
+                            .map(|output| output.get_type() == source.output_type)
+                            .unwrap_or(false);
+                        !still_valid || !acceptable.contains(&source.output_type)
+                    })
+                    .map(|(index, _)| index)
+                    .collect();
+                for index in condemned.into_iter().rev() {
+                    control.remove_automation_by_index(index);
+                }
+            }
+        }
+
+        let bindings = std::mem::take(&mut self.midi_bindings);
+        self.midi_bindings = bindings
+            .into_iter()
+            .filter_map(|mut binding| {
+                let module_index = self.index_of_module(&binding.module)?;
+                if let Some(index_map) = &index_maps[module_index] {
+                    binding.control_index = (*index_map.get(binding.control_index)?)?;
+                }
+                let range = binding.get_control()?.borrow().range;
+                binding.range = (
+                    binding.range.0.clam(range.0, range.1),
+                    binding.range.1.clam(range.0, range.1),
+                );
+                Some(binding)
+            })
+            .collect();
+
+        let removed: Vec<_> = self
+            .modules
+            .iter()
+            .filter(|module| {
+                let template = &module.borrow().template;
+                !template.borrow().is_placeholder() && !template_exists(template)
+            })
+            .cloned()
+            .collect();
+        for module in removed {
+            self.remove_module(&module);
+        }
+    }
+
     pub fn compute_execution_order(&self) -> Result<Vec<usize>, ()> {
         let mut execution_order = Vec::new();
         struct ModuleRepr {
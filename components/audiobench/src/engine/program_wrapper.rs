@@ -6,7 +6,8 @@ use crate::{
 use array_macro::array;
 use jlrs_derive::IntoJulia;
 use julia_helper::{DataType, ExecutionEngine, GeneratedCode, JuliaStruct, TypedArray, Value};
-use std::collections::HashSet;
+use shared_util::perf_counter::{sections, AtomicPerfCounter};
+use std::{collections::HashSet, time::Duration};
 
 /// The MIDI protocol can provide notes at 128 different pitches.
 const NUM_MIDI_NOTES: usize = 128;
@@ -23,6 +24,8 @@ struct NoteInput {
     pub velocity: f32,
     pub elapsed_time: f32,
     pub elapsed_beats: f32,
+    pub seed: u32,
+    pub random: f32,
     pub start_trigger: bool,
     pub release_trigger: bool,
 }
@@ -34,6 +37,8 @@ impl NoteInput {
             velocity: other.velocity,
             elapsed_time: other.elapsed_samples as f32 / params.sample_rate as f32,
             elapsed_beats: other.elapsed_beats,
+            seed: other.seed,
+            random: other.random,
             start_trigger: other.start_trigger,
             release_trigger: other.release_trigger,
         }
@@ -51,6 +56,11 @@ pub struct NoteTracker {
     held_notes: [Option<CompleteNoteData>; NUM_MIDI_NOTES],
     decaying_notes: Vec<CompleteNoteData>,
     reserved_static_indexes: HashSet<usize>,
+    /// When true, note seeds are derived from patch_seed instead of being truly random.
+    pub deterministic: bool,
+    pub patch_seed: u32,
+    /// How many notes have been started since the last time all notes were silenced.
+    note_counter: u32,
 }
 
 impl NoteTracker {
@@ -59,6 +69,9 @@ impl NoteTracker {
             held_notes: array![None; NUM_MIDI_NOTES],
             decaying_notes: Vec::new(),
             reserved_static_indexes: HashSet::new(),
+            deterministic: false,
+            patch_seed: 0,
+            note_counter: 0,
         }
     }
 
@@ -66,6 +79,7 @@ impl NoteTracker {
         self.held_notes = array![None; NUM_MIDI_NOTES];
         self.decaying_notes.clear();
         self.reserved_static_indexes.clear();
+        self.note_counter = 0;
     }
 
     fn equal_tempered_tuning(index: usize) -> f32 {
@@ -73,6 +87,26 @@ impl NoteTracker {
         440.0 * (2.0f32).powf((index as i32 - 69) as f32 / 12.0)
     }
 
+    /// Scrambles the bits of the patch seed and note counter so that similar inputs produce very
+    /// different seeds. This is the finalizer from SplitMix64.
+    fn mix_seed(patch_seed: u32, note_counter: u32) -> u32 {
+        let mut x = ((patch_seed as u64) << 32) | note_counter as u64;
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D049BB133111EB);
+        x ^= x >> 31;
+        (x >> 32) as u32
+    }
+
+    fn next_seed(&mut self) -> u32 {
+        let counter = self.note_counter;
+        self.note_counter = self.note_counter.wrapping_add(1);
+        if self.deterministic {
+            Self::mix_seed(self.patch_seed, counter)
+        } else {
+            rand::random()
+        }
+    }
+
     pub fn start_note(&mut self, index: usize, velocity: f32) -> usize {
         if let Some(note) = &self.held_notes[index] {
             return note.static_index;
@@ -83,12 +117,16 @@ impl NoteTracker {
         }
         self.reserved_static_indexes.insert(static_index);
         let static_index = static_index;
+        let seed = self.next_seed();
         self.held_notes[index] = Some(CompleteNoteData {
             data: NoteData {
                 pitch: Self::equal_tempered_tuning(index),
                 velocity,
                 elapsed_samples: 0,
                 elapsed_beats: 0.0,
+                seed,
+                // Use the top 24 bits because that is all an f32 can represent exactly.
+                random: (Self::mix_seed(seed, 0) >> 8) as f32 / (1 << 24) as f32,
                 start_trigger: true,
                 release_trigger: false,
             },
@@ -154,6 +192,14 @@ impl NoteTracker {
         None
     }
 
+    pub fn num_held_notes(&self) -> usize {
+        self.held_notes.iter().filter(|note| note.is_some()).count()
+    }
+
+    pub fn num_decaying_notes(&self) -> usize {
+        self.decaying_notes.len()
+    }
+
     fn active_notes_mut(&mut self) -> impl Iterator<Item = &mut CompleteNoteData> {
         // println!(
         //     "{} held notes, {} decaying notes.",
@@ -235,17 +281,36 @@ impl AudiobenchExecutor {
             return Err(format!("ERROR: Failed to open {:?} for writing.", temp_file));
         }
         self.generated_source = generated_code.clone();
+        self.loaded = false;
         self.base.add_global_code(generated_code)?;
         self.loaded = true;
         Ok(())
     }
 
+    /// Replaces the code of all the libraries. If the new code has errors, the old code is put
+    /// back. Either way, the generated code has to be loaded again before anything can execute.
+    pub fn change_registry_code(&mut self, registry_source: GeneratedCode) -> Result<(), String> {
+        self.loaded = false;
+        if let Err(err) = self.base.add_global_code(registry_source.clone()) {
+            self.base.add_global_code(self.registry_source.clone())?;
+            return Err(err);
+        }
+        self.registry_source = registry_source;
+        Ok(())
+    }
+
     /// If this returns false, you should not call reset_static_data or execute as they are
     /// guaranteed to return errors.
     pub fn is_generated_code_loaded(&self) -> bool {
         self.loaded
     }
 
+    /// Stops the generated code from being used, for example after it produced an error. Code has
+    /// to be loaded again with change_generated_code before anything can execute.
+    pub fn unload_generated_code(&mut self) {
+        self.loaded = false;
+    }
+
     pub fn reset_static_data(&mut self, index: usize) -> Result<(), String> {
         self.base.call_fn(
             &["Main", "Generated", "static_init"],
@@ -257,6 +322,20 @@ impl AudiobenchExecutor {
         )
     }
 
+    /// Returns how much time was spent in each module's exec function since the last time this was
+    /// called, in the order the modules appear in the module graph.
+    pub fn take_module_times(&mut self) -> Result<Vec<Duration>, String> {
+        self.base.call_fn(
+            &["Main", "Generated", "take_module_times"],
+            |_, _| Ok(()),
+            |frame, output| {
+                let times = output.cast::<TypedArray<'_, '_, u64>>()?;
+                let times = times.inline_data(frame)?.into_slice();
+                Ok(times.iter().map(|nanos| Duration::from_nanos(*nanos)).collect())
+            },
+        )
+    }
+
     /// This handles everything from global setup, note iteration, program execution, note teardown,
     /// and finally global teardown. Returns true if feedback data was updated.
     pub fn execute(
@@ -266,6 +345,7 @@ impl AudiobenchExecutor {
         notes: &mut NoteTracker,
         dyn_data: &[IOData],
         audio_output: &mut [f32],
+        perf_counter: &AtomicPerfCounter,
     ) -> Result<Option<FeedbackData>, String> {
         let channels = self.parameters.channels;
         let buf_len = self.parameters.buffer_length;
@@ -288,7 +368,8 @@ impl AudiobenchExecutor {
             let static_index = note.static_index;
             let do_feedback = feedback_note == Some(static_index);
 
-            self.base.call_fn(
+            let perf_section = perf_counter.begin_section(&sections::JULIA_EXEC);
+            let result = self.base.call_fn(
                 &["Main", "Generated", "exec"],
                 |frame, inputs| {
                     inputs.append(&mut global_data.as_julia_values(frame)?);
@@ -354,7 +435,9 @@ impl AudiobenchExecutor {
 
                     Ok(Ok(()))
                 },
-            )??;
+            );
+            perf_counter.end_section(perf_section);
+            result??;
         }
 
         notes.advance_all_notes(&self.parameters, global_data);
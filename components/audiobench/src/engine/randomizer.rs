@@ -0,0 +1,85 @@
+//! Picks random values for the controls in the module graph so that the user can explore what a
+//! patch can sound like. Controls can be locked to keep them as they are. The same seed applied to
+//! the same values always gives the same result, so a good result can be reproduced by undoing it
+//! and randomizing again with the seed it was made with. Locks are not saved with the patch.
+
+use crate::engine::{
+    controls::{Control, UpdateRequest},
+    parts::Module,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use shared_util::prelude::*;
+
+pub struct Randomizer {
+    locks: Vec<Rcrc<dyn Control>>,
+    /// How far values can move from where they are, as a fraction of the range of each control.
+    variation: f32,
+    last_seed: Option<u64>,
+}
+
+impl Randomizer {
+    pub fn new() -> Self {
+        Self {
+            locks: Vec::new(),
+            variation: 1.0,
+            last_seed: None,
+        }
+    }
+
+    /// Unlocks every control, used when a different patch is loaded.
+    pub fn clear_locks(&mut self) {
+        self.locks.clear();
+    }
+
+    pub fn is_locked(&self, control: &Rcrc<dyn Control>) -> bool {
+        self.locks.iter().any(|other| Rc::ptr_eq(other, control))
+    }
+
+    pub fn set_locked(&mut self, control: &Rcrc<dyn Control>, locked: bool) {
+        self.locks.retain(|other| !Rc::ptr_eq(other, control));
+        if locked {
+            self.locks.push(Rc::clone(control));
+        }
+    }
+
+    pub fn get_variation(&self) -> f32 {
+        self.variation
+    }
+
+    /// The variation is clamped between 0 (nothing changes) and 1 (any value can be picked).
+    pub fn set_variation(&mut self, variation: f32) {
+        self.variation = variation.clam(0.0, 1.0);
+    }
+
+    /// Returns the seed most recently passed to `randomize`.
+    pub fn get_last_seed(&self) -> Option<u64> {
+        self.last_seed
+    }
+
+    /// Randomizes every control in `modules` which is not locked, in the order they are given.
+    /// Returns what needs to be updated for the changes to be heard.
+    pub fn randomize(&mut self, modules: &[Rcrc<Module>], seed: u64) -> UpdateRequest {
+        self.last_seed = Some(seed);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut request = UpdateRequest::Nothing;
+        for module in modules {
+            for control in &module.borrow().controls {
+                let control = control.as_dyn_ptr();
+                if self.is_locked(&control) {
+                    continue;
+                }
+                let this_request = control.borrow_mut().randomize(&mut rng, self.variation);
+                request = match (request, this_request) {
+                    (UpdateRequest::UpdateCode, _) | (_, UpdateRequest::UpdateCode) => {
+                        UpdateRequest::UpdateCode
+                    }
+                    (UpdateRequest::UpdateDynData, _) | (_, UpdateRequest::UpdateDynData) => {
+                        UpdateRequest::UpdateDynData
+                    }
+                    _ => UpdateRequest::Nothing,
+                };
+            }
+        }
+        request
+    }
+}
@@ -88,7 +88,7 @@ impl Tooltip {
 #[derive(Clone)]
 pub struct Status {
     pub text: String,
-    pub color: (u8, u8, u8),
+    pub color: ThemeColor,
 }
 
 impl Status {
@@ -99,6 +99,13 @@ impl Status {
         }
     }
 
+    fn warning(text: String) -> Self {
+        Self {
+            text,
+            color: COLOR_WARNING,
+        }
+    }
+
     fn error(text: String) -> Self {
         Self {
             text,
@@ -163,6 +170,10 @@ impl GuiState {
         self.status = Some(Status::success(message));
     }
 
+    pub fn add_warning_status(&mut self, message: String) {
+        self.status = Some(Status::warning(message));
+    }
+
     pub fn add_error_status(&mut self, message: String) {
         self.status = Some(Status::error(message));
     }
@@ -189,9 +200,13 @@ impl Root {
         let this = Rc::new(Self::create(parent, state));
         let tab1 = PatchBrowser::new(&this);
         let tab2 = NoteGraph::new(&this);
+        let tab3 = MidiMappings::new(&this);
+        let tab4 = LibraryManager::new(&this);
         this.with_gui_state_mut(|state| {
             state.add_tab(tab1);
             state.add_tab(tab2);
+            state.add_tab(tab3);
+            state.add_tab(tab4);
         });
         let header = Header::new(&this);
         this.children.borrow_mut().header = Some(header);
@@ -241,6 +256,43 @@ impl WidgetImpl<Renderer, DropTarget> for Root {
     }
 
     fn draw_impl(self: &Rc<Self>, renderer: &mut Renderer) {
+        let learned = self.with_gui_state(|state| state.engine.borrow_mut().update_midi_learn());
+        if let Some(controller) = learned {
+            self.with_gui_state_mut(|state| {
+                state.add_success_status(format!("Bound MIDI controller #{}.", controller))
+            });
+        }
+        let reloaded =
+            self.with_gui_state(|state| state.engine.borrow_mut().poll_library_changes());
+        let julia_error = self.with_gui_state(|state| state.engine.borrow_mut().take_julia_error());
+        self.with_gui_state(|state| state.engine.borrow_mut().autosave());
+        let updates = self.with_gui_state(|state| {
+            let mut registry = state.registry.borrow_mut();
+            registry.poll_update_checker();
+            registry.poll_library_updates()
+        });
+        self.with_gui_state_mut(|state| {
+            for update in updates {
+                match update {
+                    Ok(name) => state.add_success_status(format!(
+                        "Updated {}, restart Audiobench to start using the new version.",
+                        name
+                    )),
+                    Err(err) => state.add_error_status(err),
+                }
+            }
+            match reloaded {
+                Ok(names) if !names.is_empty() => {
+                    state.add_success_status(format!("Reloaded {}.", names.join(", ")))
+                }
+                Ok(..) => (),
+                Err(err) => state.add_error_status(err),
+            }
+            if let Some(err) = julia_error {
+                state.add_error_status(err);
+            }
+        });
+
         renderer.set_color(&COLOR_BG0);
         renderer.draw_rect(0, (ROOT_WIDTH, ROOT_HEIGHT));
         self.get_current_tab().draw(renderer);
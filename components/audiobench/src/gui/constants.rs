@@ -1,3 +1,4 @@
+pub use crate::registry::theme::ThemeColor;
 use scui::Vec2D;
 
 /// Pixels of padding between grid squares.
@@ -84,30 +85,22 @@ pub const PRECISE_SNAP_STEPS: f32 = 72.0;
 /// What to multiply input values by when holding PrecisionModifier.
 pub const PRECISION_MULTIPLIER: f32 = 0.2;
 
-const fn hex_color(hex: u32) -> (u8, u8, u8) {
-    (
-        ((hex >> 16) & 0xFF) as u8,
-        ((hex >> 8) & 0xFF) as u8,
-        ((hex >> 0) & 0xFF) as u8,
-    )
-}
-
-// pub const COLOR_DEBUG: (u8, u8, u8) = hex_color(0xFF00FF);
+// Colors are looked up in the active theme when they are drawn, see registry::theme for their
+// default values.
 /// The darkest background color.
-pub const COLOR_BG0: (u8, u8, u8) = hex_color(0x121520);
+pub const COLOR_BG0: ThemeColor = ThemeColor::Bg0;
 /// A medium-intensity background color.
-pub const COLOR_BG1: (u8, u8, u8) = hex_color(0x2F434F);
+pub const COLOR_BG1: ThemeColor = ThemeColor::Bg1;
 /// The brightest background color.
-pub const COLOR_BG2: (u8, u8, u8) = hex_color(0x48525F);
-pub const COLOR_ERROR: (u8, u8, u8) = hex_color(0xFF0022);
-pub const COLOR_SUCCESS: (u8, u8, u8) = hex_color(0x038c23);
-pub const COLOR_WARNING: (u8, u8, u8) = hex_color(0xCC7722);
+pub const COLOR_BG2: ThemeColor = ThemeColor::Bg2;
+pub const COLOR_ERROR: ThemeColor = ThemeColor::Error;
+pub const COLOR_SUCCESS: ThemeColor = ThemeColor::Success;
+pub const COLOR_WARNING: ThemeColor = ThemeColor::Warning;
 /// Used to indicate an editable control. COLOR_FG1 should be used for editable values that are
 /// represented as text since it is white and COLOR_EDITABLE is red, which is less readable.
-pub const COLOR_EDITABLE: (u8, u8, u8) = hex_color(0xFF0022);
-pub const COLOR_AUTOMATION: (u8, u8, u8) = hex_color(0xC7D5E8);
-// pub const COLOR_AUTOMATION_FOCUSED: (u8, u8, u8) = hex_color(0x54bdff);
+pub const COLOR_EDITABLE: ThemeColor = ThemeColor::Editable;
+pub const COLOR_AUTOMATION: ThemeColor = ThemeColor::Automation;
 /// The darkest foreground color.
-pub const COLOR_FG0: (u8, u8, u8) = (0x77, 0x77, 0x77);
+pub const COLOR_FG0: ThemeColor = ThemeColor::Fg0;
 /// The brightest foreground color. Use for text and for diagrams.
-pub const COLOR_FG1: (u8, u8, u8) = (0xFF, 0xFF, 0xFF);
+pub const COLOR_FG1: ThemeColor = ThemeColor::Fg1;
@@ -1,5 +1,9 @@
-use crate::gui::constants::FONT_SIZE;
+use crate::{
+    gui::constants::{BIG_FONT_SIZE, CORNER_SIZE, FONT_SIZE},
+    registry::theme::{Theme, ThemeColor},
+};
 use scui::Vec2D;
+use std::rc::Rc;
 
 #[repr(C)]
 pub struct GraphicsFunctions {
@@ -153,6 +157,7 @@ pub struct GrahpicsWrapper {
     graphics_fns: std::rc::Rc<GraphicsFunctions>,
     aux_data: *mut i8,
     icon_store: *mut i8,
+    theme: Rc<Theme>,
 }
 
 impl<'a> GrahpicsWrapper {
@@ -160,11 +165,13 @@ impl<'a> GrahpicsWrapper {
         graphics_fns: std::rc::Rc<GraphicsFunctions>,
         aux_data: *mut i8,
         icon_store: *mut i8,
+        theme: Rc<Theme>,
     ) -> GrahpicsWrapper {
         GrahpicsWrapper {
             graphics_fns,
             aux_data,
             icon_store,
+            theme,
         }
     }
 
@@ -185,7 +192,8 @@ impl<'a> GrahpicsWrapper {
         (self.graphics_fns.apply_scale)(self.aux_data, s);
     }
 
-    pub fn set_color(&mut self, color: &(u8, u8, u8)) {
+    pub fn set_color(&mut self, color: &ThemeColor) {
+        let color = self.theme.resolve(*color);
         (self.graphics_fns.set_color)(self.aux_data, color.0, color.1, color.2);
     }
 
@@ -217,6 +225,8 @@ impl<'a> GrahpicsWrapper {
     ) {
         let top_right = top_right.into();
         let size = size.into();
+        // Widgets are laid out using CORNER_SIZE, so the theme only changes how round corners are.
+        let corner_size = corner_size * self.theme.corner_size / CORNER_SIZE;
         (self.graphics_fns.fill_rounded_rect)(
             self.aux_data,
             top_right.x,
@@ -258,6 +268,18 @@ impl<'a> GrahpicsWrapper {
         )
     }
 
+    /// FONT_SIZE and BIG_FONT_SIZE are replaced with the sizes from the theme, other sizes are
+    /// scaled by the same amount as FONT_SIZE.
+    fn themed_font_size(&self, font_size: f32) -> f32 {
+        if font_size == FONT_SIZE {
+            self.theme.font_size
+        } else if font_size == BIG_FONT_SIZE {
+            self.theme.big_font_size
+        } else {
+            font_size * self.theme.font_size / FONT_SIZE
+        }
+    }
+
     pub fn draw_text<T: Into<Vec2D>, U: Into<Vec2D>, H: Into<HAlign>, V: Into<VAlign>>(
         &mut self,
         font_size: f32,
@@ -269,6 +291,7 @@ impl<'a> GrahpicsWrapper {
     ) {
         let top_left = top_left.into();
         let size = size.into();
+        let font_size = self.themed_font_size(font_size);
         // TODO: Assert that text is ASCII.
         let raw_text = text.as_bytes();
         let mut raw_text = Vec::from(raw_text);
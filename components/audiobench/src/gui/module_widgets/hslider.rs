@@ -5,11 +5,12 @@ use crate::{
         module_widgets::ModuleWidgetImpl,
         mouse_behaviors::{ManipulateControl, ManipulateLane},
         top_level::graph::{Module, ModuleGraph},
+        ui_widgets::MidiLearnButton,
         InteractionHint, Tooltip,
     },
     scui_config::{DropTarget, MaybeMouseBehavior, Renderer},
 };
-use scui::{MouseMods, OnClickBehavior, Vec2D, Widget, WidgetImpl};
+use scui::{ChildHolder, MouseMods, OnClickBehavior, Vec2D, Widget, WidgetImpl};
 use shared_util::prelude::*;
 
 yaml_widget_boilerplate::make_widget_outline! {
@@ -207,6 +208,9 @@ scui::widget! {
         label: String,
         tooltip: String,
     }
+    Children {
+        learn_button: ChildHolder<Rc<MidiLearnButton>>,
+    }
 }
 
 impl HSliderEditor {
@@ -227,15 +231,20 @@ impl HSliderEditor {
             width + grid(4) + GRID_P * 2.0,
             required_height + GRID_P * 2.0,
         );
+        // Goes in the empty space to the left of the slider.
+        let learn_button_pos = (GRID_P, size.y - GRID_P - grid(1));
         let state = HSliderEditorState {
-            control,
+            control: Rc::clone(&control),
             value,
             pos: center_pos - size / 2.0,
             size,
             label,
             tooltip,
         };
-        Rc::new(Self::create(parent, state))
+        let this = Rc::new(Self::create(parent, state));
+        let learn_button = MidiLearnButton::new(&this, learn_button_pos, control);
+        this.children.borrow_mut().learn_button = learn_button.into();
+        this
     }
 
     fn value_to_point(&self, value: f32) -> f32 {
@@ -262,6 +271,7 @@ impl WidgetImpl<Renderer, DropTarget> for HSliderEditor {
         pos: Vec2D,
         mods: &MouseMods,
     ) -> MaybeMouseBehavior {
+        ris!(self.get_mouse_behavior_children(pos, mods));
         let state = self.state.borrow();
         const GP: f32 = GRID_P;
         const GAP: f32 = KNOB_MENU_LANE_GAP;
@@ -312,6 +322,7 @@ impl WidgetImpl<Renderer, DropTarget> for HSliderEditor {
     }
 
     fn on_hover_impl(self: &Rc<Self>, pos: Vec2D) -> Option<()> {
+        ris!(self.on_hover_children(pos));
         let state = self.state.borrow();
         const GP: f32 = GRID_P;
         const GAP: f32 = KNOB_MENU_LANE_GAP;
@@ -431,5 +442,6 @@ impl WidgetImpl<Renderer, DropTarget> for HSliderEditor {
             1,
             &value_text,
         );
+        self.draw_children(g);
     }
 }
@@ -173,7 +173,7 @@ impl WidgetImpl<Renderer, DropTarget> for Input {
         if dim {
             g.set_color(&COLOR_FG0);
         } else {
-            g.set_color(&COLOR_FG1);
+            g.set_color(&ThemeColor::Jack(control.get_type()));
         }
 
         if let Some(default) = control.get_used_default() {
@@ -197,6 +197,9 @@ impl WidgetImpl<Renderer, DropTarget> for Input {
         g.draw_icon(state.icon, (JS + JIP, JIP), JS - JIP * 2.0);
 
         if hovered {
+            if !dim {
+                g.set_color(&COLOR_FG1);
+            }
             if let Some(default) = control.get_used_default() {
                 g.draw_text(
                     FONT_SIZE,
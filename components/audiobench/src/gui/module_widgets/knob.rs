@@ -5,11 +5,12 @@ use crate::{
         module_widgets::ModuleWidgetImpl,
         mouse_behaviors::{ManipulateControl, ManipulateLane},
         top_level::graph::{Module, ModuleGraph},
+        ui_widgets::MidiLearnButton,
         InteractionHint, Tooltip,
     },
     scui_config::{DropTarget, MaybeMouseBehavior, Renderer},
 };
-use scui::{MouseMods, OnClickBehavior, Vec2D, Widget, WidgetImpl};
+use scui::{ChildHolder, MouseMods, OnClickBehavior, Vec2D, Widget, WidgetImpl};
 use shared_util::prelude::*;
 use std::f32::consts::PI;
 
@@ -206,6 +207,9 @@ scui::widget! {
         label: String,
         tooltip: String,
     }
+    Children {
+        learn_button: ChildHolder<Rc<MidiLearnButton>>,
+    }
 }
 
 impl KnobEditor {
@@ -220,16 +224,20 @@ impl KnobEditor {
         let num_channels = control.borrow().automation.len().max(2) as f32;
         let required_radius =
             (KNOB_MENU_LANE_SIZE + KNOB_MENU_LANE_GAP) * num_channels + KNOB_MENU_KNOB_OR + GRID_P;
-        let size = (required_radius * 2.0, required_radius + fatgrid(1)).into();
+        let size: Vec2D = (required_radius * 2.0, required_radius + fatgrid(1)).into();
+        let learn_button_pos = (GRID_P, size.y - GRID_P - grid(1));
         let state = KnobEditorState {
-            control,
+            control: Rc::clone(&control),
             value,
             pos: (center_pos - size / 2.0),
             size,
             label,
             tooltip,
         };
-        Rc::new(Self::create(parent, state))
+        let this = Rc::new(Self::create(parent, state));
+        let learn_button = MidiLearnButton::new(&this, learn_button_pos, control);
+        this.children.borrow_mut().learn_button = learn_button.into();
+        this
     }
 }
 
@@ -247,6 +255,7 @@ impl WidgetImpl<Renderer, DropTarget> for KnobEditor {
         pos: Vec2D,
         mods: &MouseMods,
     ) -> MaybeMouseBehavior {
+        ris!(self.get_mouse_behavior_children(pos, mods));
         let state = self.state.borrow();
         // Yes, the x is intentional. The center of the knob is not vertically centered.
         // y coordinate is inverted from how it appears on screen.
@@ -330,6 +339,7 @@ impl WidgetImpl<Renderer, DropTarget> for KnobEditor {
     }
 
     fn on_hover_impl(self: &Rc<Self>, pos: Vec2D) -> Option<()> {
+        ris!(self.on_hover_children(pos));
         let state = self.state.borrow();
         // Yes, the x is intentional. The center of the knob is not vertically centered.
         // y coordinate is inverted from how it appears on screen.
@@ -410,6 +420,7 @@ impl WidgetImpl<Renderer, DropTarget> for KnobEditor {
         g.draw_inset_box_shadow(0, state.size, BSR, CS);
         g.set_color(&COLOR_BG2);
         g.draw_rounded_rect(0, state.size, CS);
+        self.draw_children(g);
 
         fn value_to_angle(range: (f32, f32), value: f32) -> f32 {
             value.from_range_to_range(range.0, range.1, PI, 0.0)
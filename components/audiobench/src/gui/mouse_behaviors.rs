@@ -171,6 +171,93 @@ impl MouseBehavior<DropTarget> for ManipulateLane {
     }
 }
 
+#[make_constructor((widget: &impl GuiInterfaceProvider<GuiState, DropTarget>, ..))]
+#[make_constructor(pub start_only(widget: &impl GuiInterfaceProvider<GuiState, DropTarget>, ..))]
+#[make_constructor(pub end_only(widget: &impl GuiInterfaceProvider<GuiState, DropTarget>, ..))]
+pub struct ManipulateMidiBinding {
+    #[value(Rc::clone(&widget.provide_gui_interface().state.borrow().engine))]
+    engine: Rcrc<UiThreadEngine>,
+    #[value(Rc::clone(&widget.provide_gui_interface()))]
+    gui_interface: Rc<GuiInterface<GuiState, DropTarget>>,
+    binding_index: usize,
+    #[value(true)]
+    #[value(false for end_only)]
+    start: bool,
+    #[value(true)]
+    #[value(false for start_only)]
+    end: bool,
+}
+
+impl ManipulateMidiBinding {
+    fn modify_range(&self, modifier: impl FnOnce(&mut (f32, f32), (f32, f32))) -> Option<Tooltip> {
+        let mut engine = self.engine.borrow_mut();
+        let graph_ptr = Rc::clone(engine.borrow_module_graph_ref());
+        let mut graph = graph_ptr.borrow_mut();
+        let binding = graph
+            .borrow_midi_bindings_mut()
+            .get_mut(self.binding_index)?;
+        let control = binding.get_control()?;
+        let control_ref = control.borrow();
+        modifier(&mut binding.range, control_ref.range);
+        let tttext = format!(
+            "{0}{2} to {1}{2}",
+            format_decimal(binding.range.0, 4),
+            format_decimal(binding.range.1, 4),
+            control_ref.suffix,
+        );
+        drop(control_ref);
+        drop(graph);
+        engine.reload_midi_bindings();
+        Some(Tooltip {
+            text: tttext,
+            interaction: vec![
+                InteractionHint::LeftClickAndDrag,
+                InteractionHint::PrecisionModifier,
+                InteractionHint::SnappingModifier,
+            ],
+        })
+    }
+}
+
+impl MouseBehavior<DropTarget> for ManipulateMidiBinding {
+    fn on_drag(&mut self, delta: Vec2D, mods: &MouseMods) {
+        let delta = range_drag_delta(delta, mods);
+        let (start, end) = (self.start, self.end);
+        let tooltip = self.modify_range(|binding_range, range| {
+            let delta = delta * (range.1 - range.0);
+            if start {
+                binding_range.0 = maybe_snap_value(
+                    (binding_range.0 + delta).clam(range.0, range.1),
+                    range,
+                    mods,
+                );
+            }
+            if end {
+                binding_range.1 = maybe_snap_value(
+                    (binding_range.1 + delta).clam(range.0, range.1),
+                    range,
+                    mods,
+                );
+            }
+        });
+        if let Some(tooltip) = tooltip {
+            self.gui_interface.state.borrow_mut().set_tooltip(tooltip);
+        }
+    }
+
+    fn on_double_click(self: Box<Self>) {
+        let (start, end) = (self.start, self.end);
+        self.modify_range(|binding_range, range| {
+            if start {
+                binding_range.0 = range.0;
+            }
+            if end {
+                binding_range.1 = range.1;
+            }
+        });
+    }
+}
+
 #[make_constructor((widget: &impl GuiInterfaceProvider<GuiState, DropTarget>, .., current_value: i32))]
 pub struct ManipulateIntBox {
     #[value(Rc::clone(&widget.provide_gui_interface().state.borrow().engine))]
@@ -316,3 +403,48 @@ impl MouseBehavior<DropTarget> for ContinuouslyMutateControl {
         }
     }
 }
+
+/// Blends between compare slots A and B, see `engine::morph`. Morphing is turned on as soon as the
+/// mouse is dragged.
+#[make_constructor((widget: &impl GuiInterfaceProvider<GuiState, DropTarget>))]
+pub struct ManipulateMorph {
+    #[value(Rc::clone(&widget.provide_gui_interface().state.borrow().engine))]
+    engine: Rcrc<UiThreadEngine>,
+    #[value(Rc::clone(&widget.provide_gui_interface()))]
+    gui_interface: Rc<GuiInterface<GuiState, DropTarget>>,
+    #[value(widget.provide_gui_interface().state.borrow().engine.borrow().get_morph_amount())]
+    current_amount: f32,
+}
+
+impl MouseBehavior<DropTarget> for ManipulateMorph {
+    fn on_drag(&mut self, delta: Vec2D, mods: &MouseMods) {
+        let mut engine = self.engine.borrow_mut();
+        let mut gui_state = self.gui_interface.state.borrow_mut();
+        if let Err(err) = engine.set_morph_enabled(true) {
+            gui_state.add_error_status(err);
+            return;
+        }
+        let delta = range_drag_delta(delta, mods);
+        self.current_amount = (self.current_amount + delta).clam(0.0, 1.0);
+        let amount = maybe_snap_value(self.current_amount, (0.0, 1.0), mods);
+        engine.set_morph_amount(amount);
+        gui_state.set_tooltip(Tooltip {
+            text: format!(
+                "{}% of the way from A to B",
+                format_decimal(amount * 100.0, 3)
+            ),
+            interaction: vec![
+                InteractionHint::LeftClickAndDrag,
+                InteractionHint::PrecisionModifier,
+                InteractionHint::SnappingModifier,
+            ],
+        });
+    }
+
+    fn on_double_click(self: Box<Self>) {
+        let result = self.engine.borrow_mut().set_morph_enabled(false);
+        debug_assert!(result.is_ok());
+        let mut gui_state = self.gui_interface.state.borrow_mut();
+        gui_state.add_success_status("Morphing turned off.".to_owned());
+    }
+}
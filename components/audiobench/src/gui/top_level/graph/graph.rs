@@ -97,23 +97,23 @@ impl ModuleGraph {
     }
 
     pub fn rebuild(self: &Rc<Self>) {
+        self.rebuild_in_place();
+        self.recenter();
+    }
+
+    /// Recreates the widgets for the modules in the graph without moving the view, for when the
+    /// graph was changed by undoing or redoing an edit.
+    pub fn rebuild_in_place(self: &Rc<Self>) {
+        self.clear_hovered_module();
         let mut children = self.children.borrow_mut();
         children.modules.clear();
         children.detail_menu = None;
         let state = self.state.borrow();
-        let mut top_left = Vec2D::from(std::f32::MAX);
-        let mut bottom_right = Vec2D::from(std::f32::MIN);
         for module_rc in state.graph.borrow().borrow_modules() {
-            let module_widget = Module::new(self, Rc::clone(module_rc));
-            let pos = module_widget.get_pos();
-            let size = module_widget.get_size();
-            top_left = top_left.min(pos);
-            bottom_right = bottom_right.max(pos + size);
-            children.modules.push(module_widget);
+            children
+                .modules
+                .push(Module::new(self, Rc::clone(module_rc)));
         }
-        drop(children);
-        drop(state);
-        self.recenter();
     }
 
     fn recenter(self: &Rc<Self>) {
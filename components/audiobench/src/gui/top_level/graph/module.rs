@@ -1,5 +1,5 @@
 use crate::{
-    engine::parts as ep,
+    engine::{controls::Control, parts as ep},
     gui::{
         constants::*,
         module_widgets::ModuleWidget,
@@ -13,6 +13,7 @@ use shared_util::prelude::*;
 
 struct OutputJack {
     label: String,
+    typ: ep::JackType,
     tooltip: Tooltip,
     icon: usize,
     small_icon: Option<usize>,
@@ -37,6 +38,7 @@ impl OutputJack {
         };
         Self {
             label,
+            typ,
             tooltip: Tooltip {
                 text: tooltip,
                 interaction: vec![
@@ -63,7 +65,7 @@ impl OutputJack {
         if mute {
             g.set_color(&COLOR_FG0);
         } else {
-            g.set_color(&COLOR_FG1);
+            g.set_color(&ThemeColor::Jack(self.typ));
         }
         g.draw_rounded_rect(0, JS, CS);
         g.draw_rect((JS - CS, 0.0), (CS, JS));
@@ -78,6 +80,7 @@ impl OutputJack {
         g.draw_icon(self.icon, JIP, JS - JIP * 2.0);
 
         if show_label && !mute {
+            g.set_color(&COLOR_FG1);
             g.draw_text(
                 FONT_SIZE,
                 (JS + 4.0, 0.0),
@@ -98,8 +101,13 @@ scui::widget! {
         module: Rcrc<ep::Module>,
         size: Vec2D,
         label: String,
+        // Modules from libraries which are not installed are drawn greyed out and show this when
+        // hovered.
+        placeholder_tooltip: Option<String>,
         outputs: Vec<OutputJack>,
-        widgets: Vec<Box<dyn ModuleWidget>>
+        widgets: Vec<Box<dyn ModuleWidget>>,
+        // Drawn on top of controls which are locked so that randomizing skips them.
+        lock_icon: usize,
     }
     Parents {
         graph: Rc<ModuleGraph>
@@ -128,6 +136,11 @@ impl Module {
         let template_ref = module_ref.template.borrow();
         let grid_size = template_ref.size;
         let label = template_ref.label.clone();
+        let placeholder_tooltip = if template_ref.is_placeholder() {
+            Some(template_ref.tooltip.clone())
+        } else {
+            None
+        };
         let module_controls = &module_ref.controls;
 
         let size = Vec2D::new(
@@ -146,12 +159,22 @@ impl Module {
             ));
         }
 
+        let lock_icon = parent
+            .provide_gui_interface()
+            .state
+            .borrow()
+            .registry
+            .borrow()
+            .lookup_icon("Factory:lock")
+            .unwrap();
         let state = ModuleState {
             module: Rc::clone(&module),
             size,
             label,
+            placeholder_tooltip,
             outputs,
             widgets: Vec::new(),
+            lock_icon,
         };
 
         let this = Rc::new(Self::create(parent, state));
@@ -180,9 +203,10 @@ impl Module {
             let input_style = widget.use_input_style_wires();
             if let Some(control) = widget.represented_control() {
                 for source in control.borrow().get_connected_automation() {
-                    let source_coord =
-                        Module::output_position(&*source.module.borrow(), source.output_index);
-                    wire_tracker.add_wire(source_coord, center, input_style);
+                    let module = source.module.borrow();
+                    let source_coord = Module::output_position(&*module, source.output_index);
+                    let typ = module.template.borrow().outputs[source.output_index].get_type();
+                    wire_tracker.add_wire(source_coord, center, input_style, typ);
                 }
             }
         }
@@ -196,6 +220,27 @@ impl Module {
     pub fn is_hovered(self: &Rc<Self>) -> bool {
         self.parents.graph.is_hovered_module(self)
     }
+
+    fn on_toggle_lock(self: &Rc<Self>, control: Rcrc<dyn Control>) -> MaybeMouseBehavior {
+        let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
+        OnClickBehavior::wrap(move || {
+            let mut engine = engine.borrow_mut();
+            let locked = engine.is_control_locked(&control);
+            engine.set_control_locked(&control, !locked);
+        })
+    }
+
+    fn on_randomize(self: &Rc<Self>) -> MaybeMouseBehavior {
+        let this = Rc::clone(self);
+        let module = Rc::clone(&self.state.borrow().module);
+        let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
+        OnClickBehavior::wrap(move || {
+            let seed = engine.borrow_mut().randomize_controls(Some(&module), None);
+            this.with_gui_state_mut(|state| {
+                state.add_success_status(format!("Randomized with seed {}.", seed))
+            });
+        })
+    }
 }
 
 pub struct DragModule {
@@ -256,6 +301,16 @@ impl WidgetImpl<Renderer, DropTarget> for Module {
         mods: &MouseMods,
     ) -> MaybeMouseBehavior {
         let state = self.state.borrow();
+        if mods.right_click && mods.snap {
+            for widget in &state.widgets {
+                if !(mouse_pos - widget.get_pos()).inside(widget.get_size()) {
+                    continue;
+                }
+                if let Some(control) = widget.represented_control() {
+                    return self.on_toggle_lock(control);
+                }
+            }
+        }
         for widget in &state.widgets {
             ris!(widget.get_mouse_behavior(mouse_pos, mods));
         }
@@ -265,7 +320,9 @@ impl WidgetImpl<Renderer, DropTarget> for Module {
                 return Some(g.connect_from_source_behavior(Rc::clone(&state.module), index));
             }
         }
-        if mods.right_click {
+        if mods.right_click && mods.precise {
+            self.on_randomize()
+        } else if mods.right_click {
             let graph = Rc::clone(&self.parents.graph);
             let module = Rc::clone(&state.module);
             OnClickBehavior::wrap(move || graph.remove_module(&module))
@@ -310,10 +367,18 @@ impl WidgetImpl<Renderer, DropTarget> for Module {
         }
 
         let mut tooltip = Tooltip {
-            text: "".to_owned(),
+            text: state.placeholder_tooltip.clone().unwrap_or_else(|| {
+                concat!(
+                    "Right click to remove the module, alt + right click to randomize its ",
+                    "controls, shift + right click a control to lock it"
+                )
+                .to_owned()
+            }),
             interaction: vec![
                 InteractionHint::LeftClickAndDrag,
                 InteractionHint::RightClick,
+                InteractionHint::PrecisionModifier,
+                InteractionHint::SnappingModifier,
             ],
         };
         for output in state.outputs.iter() {
@@ -344,19 +409,24 @@ impl WidgetImpl<Renderer, DropTarget> for Module {
             );
         } else if layer_index == 1 {
             g.translate(pos * -1.0);
-            g.set_color(&COLOR_FG1);
             self.draw_wires(g, pos);
         } else if layer_index == 2 {
             const CS: f32 = CORNER_SIZE;
             const JS: f32 = JACK_SIZE;
             const MIW: f32 = MODULE_IO_WIDTH;
 
+            let missing = state.placeholder_tooltip.is_some();
             g.set_color(&COLOR_BG1);
             g.draw_rounded_rect((JS, 0.0), size - (JS, 0.0), CS);
-            g.set_color(&COLOR_BG2);
-            g.draw_rect((JS + MIW, 0.0), size - (MIW * 2.0 + JS, 0.0));
+            if !missing {
+                g.set_color(&COLOR_BG2);
+                g.draw_rect((JS + MIW, 0.0), size - (MIW * 2.0 + JS, 0.0));
+            }
 
             g.set_color(&COLOR_FG1);
+            if missing {
+                g.set_alpha(0.5);
+            }
             g.draw_text(
                 FONT_SIZE,
                 (MODULE_IO_WIDTH, -20.0),
@@ -365,6 +435,17 @@ impl WidgetImpl<Renderer, DropTarget> for Module {
                 1,
                 &state.label,
             );
+            if missing {
+                g.draw_text(
+                    FONT_SIZE,
+                    (JS + MIW, 0.0),
+                    size - (MIW * 2.0 + JS, 0.0),
+                    (0, 0),
+                    1,
+                    "?",
+                );
+                g.set_alpha(1.0);
+            }
 
             let module_ref = state.module.borrow();
             let template_ref = module_ref.template.borrow();
@@ -379,13 +460,26 @@ impl WidgetImpl<Renderer, DropTarget> for Module {
                 };
                 output.draw(g, hovering, dim);
             }
+            let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
+            let engine = engine.borrow();
             for widget in &state.widgets {
                 widget.draw(g);
+                let control = if let Some(control) = widget.represented_control() {
+                    control
+                } else {
+                    continue;
+                };
+                if engine.is_control_locked(&control) {
+                    const LS: f32 = JACK_SMALL_ICON_SIZE;
+                    let pos = widget.get_pos() + (widget.get_size().x - LS, 0.0);
+                    g.set_color(&COLOR_BG1);
+                    g.draw_rounded_rect(pos - JACK_ICON_PADDING, LS + JACK_ICON_PADDING * 2.0, CS);
+                    g.draw_white_icon(state.lock_icon, pos, LS);
+                }
             }
 
             g.set_color(&COLOR_FG1);
         } else if layer_index == 3 {
-            g.set_color(&COLOR_FG1);
             g.set_alpha(0.2);
             g.translate(pos * -1.0);
             self.draw_wires(g, pos);
@@ -1,4 +1,4 @@
-use crate::{gui::constants::*, scui_config::Renderer};
+use crate::{engine::parts::JackType, gui::constants::*, scui_config::Renderer};
 use scui::Vec2D;
 
 // This code is not intended to be maintainable. It was created by madly scribbling on graph paper
@@ -262,8 +262,8 @@ pub struct WireTracker {
     module_height: f32,
     top_slots: Vec<bool>,
     bottom_slots: Vec<bool>,
-    wires: Vec<(Vec2D, Vec2D, bool)>,
-    input_style_wires: Vec<(Vec2D, Vec2D)>,
+    wires: Vec<(Vec2D, Vec2D, bool, JackType)>,
+    input_style_wires: Vec<(Vec2D, Vec2D, JackType)>,
 }
 
 impl WireTracker {
@@ -277,9 +277,16 @@ impl WireTracker {
             input_style_wires: Vec::new(),
         }
     }
-    pub fn add_wire(&mut self, source_coord: Vec2D, widget_coord: Vec2D, input_style: bool) {
+    pub fn add_wire(
+        &mut self,
+        source_coord: Vec2D,
+        widget_coord: Vec2D,
+        input_style: bool,
+        typ: JackType,
+    ) {
         if input_style {
-            self.input_style_wires.push((source_coord, widget_coord));
+            self.input_style_wires
+                .push((source_coord, widget_coord, typ));
             return;
         }
         let slot_index = ((widget_coord.x - MODULE_IO_WIDTH - JACK_SIZE) / WIRE_SPACING) as usize;
@@ -319,14 +326,17 @@ impl WireTracker {
             empty_slot as f32 * WIRE_SPACING + WIRE_SPACING / 2.0 + MODULE_IO_WIDTH + JACK_SIZE,
             if top { 0.0 } else { self.module_height },
         );
-        self.wires.push((source_coord, (endx, endy).into(), top));
+        self.wires
+            .push((source_coord, (endx, endy).into(), top, typ));
     }
 
     pub fn draw_wires(self, g: &mut Renderer, target_offset: Vec2D) {
-        for (source, target, face_down) in self.wires {
+        for (source, target, face_down, typ) in self.wires {
+            g.set_color(&ThemeColor::Jack(typ));
             draw_automation_wire(g, face_down, source, target + target_offset);
         }
-        for (source, target) in self.input_style_wires {
+        for (source, target, typ) in self.input_style_wires {
+            g.set_color(&ThemeColor::Jack(typ));
             draw_io_wire(g, target + target_offset, source);
         }
     }
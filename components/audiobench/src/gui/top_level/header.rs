@@ -1,22 +1,48 @@
 use crate::{
-    engine::parts::JackType,
-    gui::{constants::*, InteractionHint},
+    engine::{data_transfer::EngineLoad, parts::JackType, NUM_COMPARE_SLOTS},
+    gui::{constants::*, ui_widgets::IconButton, InteractionHint, Tooltip},
     scui_config::{DropTarget, MaybeMouseBehavior, Renderer},
 };
-use scui::{MouseMods, OnClickBehavior, Vec2D, WidgetImpl};
+use scui::{ChildHolder, MouseMods, OnClickBehavior, Vec2D, WidgetImpl};
 use shared_util::prelude::*;
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 
 scui::widget! {
     pub Header
     State {
         hint_icons: HashMap<InteractionHint, Vec<usize>>,
+        load: EngineLoad,
+        // The highest CPU load seen recently and when it was seen.
+        peak_load: (f32, Instant),
+        last_overrun: Option<Instant>,
+    }
+    Children {
+        undo_button: ChildHolder<Rc<IconButton>>,
+        redo_button: ChildHolder<Rc<IconButton>>,
+        copy_slot_button: ChildHolder<Rc<IconButton>>,
     }
 }
 
 const TAB_SIZE: Vec2D = Vec2D::new(grid(4), grid(1));
 const TAB_PADDING: f32 = GRID_P * 0.5;
 const TAB_HEIGHT: f32 = grid(1);
+const VOICES_WIDTH: f32 = grid(3);
+const CPU_METER_WIDTH: f32 = grid(4);
+const CPU_METER_X: f32 = ROOT_WIDTH - GRID_P - CPU_METER_WIDTH;
+const VOICES_X: f32 = CPU_METER_X - GRID_P - VOICES_WIDTH;
+const HISTORY_BUTTON_SIZE: f32 = TAB_HEIGHT - GRID_P;
+const REDO_X: f32 = VOICES_X - GRID_P - HISTORY_BUTTON_SIZE;
+const UNDO_X: f32 = REDO_X - GRID_P / 2.0 - HISTORY_BUTTON_SIZE;
+const COPY_SLOT_X: f32 = UNDO_X - GRID_P - HISTORY_BUTTON_SIZE;
+/// Where the buttons for switching between compare slots start.
+const COMPARE_X: f32 = COPY_SLOT_X - GRID_P / 2.0 - HISTORY_BUTTON_SIZE * NUM_COMPARE_SLOTS as f32;
+/// How long the peak marker on the CPU meter stays in place before it starts following the meter.
+const PEAK_HOLD_TIME: Duration = Duration::from_secs(2);
+/// How long the CPU meter stays red after the audio could not be rendered fast enough.
+const OVERRUN_INDICATOR_TIME: Duration = Duration::from_secs(2);
 
 impl Header {
     pub fn new(parent: &impl HeaderParent) -> Rc<Self> {
@@ -53,10 +79,188 @@ impl Header {
             hint_icons.insert(ProducesOutput(*jt), vec![arrow, ji]);
         }
 
-        let state = HeaderState { hint_icons };
+        let undo_icon = i("Factory:undo");
+        let copy_icon = i("Factory:copy");
+        let redo_icon = i("Factory:redo");
+
+        let state = HeaderState {
+            hint_icons,
+            load: Default::default(),
+            peak_load: (0.0, Instant::now()),
+            last_overrun: None,
+        };
         let this = Rc::new(Self::create(parent, state));
+
+        let this2 = Rc::clone(&this);
+        let undo_button = IconButton::new(
+            &this,
+            (UNDO_X, GRID_P / 2.0),
+            HISTORY_BUTTON_SIZE,
+            undo_icon,
+            move |_| this2.on_undo(false),
+            "Undo the last change to the note graph (Ctrl+Z)",
+        );
+        let this2 = Rc::clone(&this);
+        let redo_button = IconButton::new(
+            &this,
+            (REDO_X, GRID_P / 2.0),
+            HISTORY_BUTTON_SIZE,
+            redo_icon,
+            move |_| this2.on_undo(true),
+            "Redo the last change that was undone (Ctrl+Y)",
+        );
+        let this2 = Rc::clone(&this);
+        let copy_slot_button = IconButton::new(
+            &this,
+            (COPY_SLOT_X, GRID_P / 2.0),
+            HISTORY_BUTTON_SIZE,
+            copy_icon,
+            move |_| this2.on_copy_slot(),
+            "Overwrite the other compare slot with a copy of the patch in this one",
+        );
+        let mut children = this.children.borrow_mut();
+        children.undo_button = undo_button.into();
+        children.redo_button = redo_button.into();
+        children.copy_slot_button = copy_slot_button.into();
+        drop(children);
         this
     }
+
+    fn on_undo(self: &Rc<Self>, redo: bool) -> MaybeMouseBehavior {
+        let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
+        OnClickBehavior::wrap(move || {
+            if redo {
+                engine.borrow_mut().redo();
+            } else {
+                engine.borrow_mut().undo();
+            }
+        })
+    }
+
+    fn on_switch_slot(self: &Rc<Self>, index: usize) -> MaybeMouseBehavior {
+        let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
+        OnClickBehavior::wrap(move || engine.borrow_mut().switch_compare_slot(index))
+    }
+
+    fn on_copy_slot(self: &Rc<Self>) -> MaybeMouseBehavior {
+        let this = Rc::clone(self);
+        let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
+        OnClickBehavior::wrap(move || {
+            let mut engine = engine.borrow_mut();
+            let from = engine.get_compare_slot();
+            let to = (from + 1) % NUM_COMPARE_SLOTS;
+            engine.copy_to_compare_slot(to);
+            drop(engine);
+            this.with_gui_state_mut(|state| {
+                state.add_success_status(format!(
+                    "Copied {} to {}.",
+                    slot_name(from),
+                    slot_name(to)
+                ))
+            });
+        })
+    }
+
+    fn update_history_buttons(self: &Rc<Self>) {
+        let (can_undo, can_redo) = self.with_gui_state(|state| {
+            let engine = state.engine.borrow();
+            (engine.can_undo(), engine.can_redo())
+        });
+        let children = self.children.borrow();
+        children.undo_button.set_enabled(can_undo);
+        children.redo_button.set_enabled(can_redo);
+    }
+
+    fn update_load(self: &Rc<Self>) {
+        let load = self.with_gui_state(|state| state.engine.borrow_mut().take_engine_load());
+        let load = if let Some(load) = load {
+            load
+        } else {
+            return;
+        };
+        let mut state = self.state.borrow_mut();
+        let now = Instant::now();
+        if load.peak_cpu_load >= state.peak_load.0
+            || now.duration_since(state.peak_load.1) > PEAK_HOLD_TIME
+        {
+            state.peak_load = (load.peak_cpu_load, now);
+        }
+        if load.num_overruns > 0 {
+            state.last_overrun = Some(now);
+        }
+        state.load = load;
+    }
+
+    fn is_overrunning(&self) -> bool {
+        self.state
+            .borrow()
+            .last_overrun
+            .map(|time| time.elapsed() < OVERRUN_INDICATOR_TIME)
+            .unwrap_or(false)
+    }
+
+    fn draw_load(self: &Rc<Self>, r: &mut Renderer) {
+        const CS: f32 = CORNER_SIZE;
+        const FS: f32 = FONT_SIZE;
+        let overrunning = self.is_overrunning();
+        let state = self.state.borrow();
+        let height = TAB_HEIGHT - GRID_P;
+        let y = GRID_P / 2.0;
+
+        r.set_color(&COLOR_FG1);
+        let voices = format!(
+            "{} + {} voices",
+            state.load.held_voices, state.load.decaying_voices
+        );
+        r.draw_text(
+            FS,
+            (VOICES_X, 0.0),
+            (VOICES_WIDTH, TAB_HEIGHT),
+            (1, 0),
+            1,
+            &voices,
+        );
+
+        r.set_color(if overrunning {
+            &COLOR_ERROR
+        } else {
+            &COLOR_BG1
+        });
+        r.draw_rounded_rect((CPU_METER_X, y), (CPU_METER_WIDTH, height), CS);
+        let amount = state.load.cpu_load.min(1.0);
+        if amount > 0.0 {
+            r.set_color(&COLOR_EDITABLE);
+            r.set_alpha(0.5);
+            r.draw_rounded_rect((CPU_METER_X, y), (CPU_METER_WIDTH * amount, height), CS);
+            r.set_alpha(1.0);
+        }
+        let peak_x = CPU_METER_X + CPU_METER_WIDTH * state.peak_load.0.min(1.0);
+        r.set_color(&COLOR_FG1);
+        r.draw_line((peak_x, y), (peak_x, y + height), 1.0);
+        let text = format!("CPU {:.0}%", state.load.cpu_load * 100.0);
+        r.draw_text(
+            FS,
+            (CPU_METER_X, y),
+            (CPU_METER_WIDTH, height),
+            (0, 0),
+            1,
+            &text,
+        );
+    }
+
+    fn draw_compare_slots(self: &Rc<Self>, r: &mut Renderer) {
+        let active = self.with_gui_state(|state| state.engine.borrow().get_compare_slot());
+        let size = HISTORY_BUTTON_SIZE;
+        for index in 0..NUM_COMPARE_SLOTS {
+            let pos = (COMPARE_X + size * index as f32, GRID_P / 2.0);
+            if index == active {
+                r.set_color(&COLOR_BG2);
+                r.draw_rounded_rect(pos, size, CORNER_SIZE);
+            }
+            r.set_color(&COLOR_FG1);
+            r.draw_text(FONT_SIZE, pos, size, (0, 0), 1, &slot_name(index));
+        }
+    }
 }
 
 impl WidgetImpl<Renderer, DropTarget> for Header {
@@ -75,6 +279,13 @@ impl WidgetImpl<Renderer, DropTarget> for Header {
     ) -> MaybeMouseBehavior {
         ris!(self.get_mouse_behavior_children(pos, mods));
 
+        if pos.y < TAB_HEIGHT && pos.x >= COMPARE_X {
+            let index = ((pos.x - COMPARE_X) / HISTORY_BUTTON_SIZE) as usize;
+            if index < NUM_COMPARE_SLOTS {
+                return self.on_switch_slot(index);
+            }
+            return None;
+        }
         let tab_index = (pos.x / (TAB_SIZE.x + TAB_PADDING)) as usize;
         let this = Rc::clone(self);
         OnClickBehavior::wrap(move || {
@@ -84,7 +295,44 @@ impl WidgetImpl<Renderer, DropTarget> for Header {
         })
     }
 
+    fn on_hover_impl(self: &Rc<Self>, pos: Vec2D) -> Option<()> {
+        ris!(self.on_hover_children(pos));
+        if pos.y < TAB_HEIGHT && pos.x >= COMPARE_X && pos.x < COPY_SLOT_X {
+            let tooltip = Tooltip {
+                text: concat!(
+                    "Switch between variations of the current patch to compare them, the one ",
+                    "which is active when the patch is saved is the one that gets saved"
+                )
+                .to_owned(),
+                interaction: vec![InteractionHint::LeftClick],
+            };
+            self.with_gui_state_mut(|state| state.set_tooltip(tooltip));
+            return Some(());
+        }
+        if pos.y >= TAB_HEIGHT || pos.x < VOICES_X {
+            return None;
+        }
+        let text = if pos.x < CPU_METER_X {
+            "Notes which are being held down + notes which are fading out after being released"
+        } else if self.is_overrunning() {
+            "Audio is not being rendered fast enough to play it back, causing dropouts"
+        } else {
+            concat!(
+                "Time spent rendering audio compared to how long that audio lasts, the line ",
+                "shows the recent peak"
+            )
+        };
+        let tooltip = Tooltip {
+            text: text.to_owned(),
+            interaction: vec![],
+        };
+        self.with_gui_state_mut(|state| state.set_tooltip(tooltip));
+        Some(())
+    }
+
     fn draw_impl(self: &Rc<Self>, r: &mut Renderer) {
+        self.update_load();
+        self.update_history_buttons();
         let self_state = self.state.borrow();
         const BFS: f32 = BIG_FONT_SIZE;
         const CS: f32 = CORNER_SIZE;
@@ -161,5 +409,14 @@ impl WidgetImpl<Renderer, DropTarget> for Header {
                 index += 1;
             }
         });
+        drop(self_state);
+        self.draw_load(r);
+        self.draw_compare_slots(r);
+        self.draw_children(r);
     }
 }
+
+/// A, B, and so on.
+fn slot_name(index: usize) -> String {
+    ((b'A' + index as u8) as char).to_string()
+}
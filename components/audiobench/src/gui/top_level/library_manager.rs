@@ -0,0 +1,645 @@
+use crate::{
+    gui::{
+        constants::*,
+        ui_widgets::{IconButton, TextBox},
+        GuiTab, InteractionHint, Tooltip,
+    },
+    registry::{PendingLibraryChange, Registry},
+    scui_config::{DropTarget, MaybeMouseBehavior, Renderer},
+};
+use scui::{ChildHolder, MouseMods, OnClickBehavior, Vec2D, WidgetImpl};
+use shared_util::{prelude::*, Version};
+use std::path::PathBuf;
+
+struct LibraryEntry {
+    name: String,
+    pretty_name: String,
+    // None if the library was installed after Audiobench started and is not loaded yet.
+    version: Option<Version>,
+    is_directory: bool,
+    pending_change: Option<PendingLibraryChange>,
+}
+
+impl LibraryEntry {
+    fn can_export(&self) -> bool {
+        self.is_directory && self.pending_change != Some(PendingLibraryChange::Uninstalled)
+    }
+
+    fn can_uninstall(&self) -> bool {
+        self.name != "Factory"
+            && self.name != "User"
+            && self.pending_change != Some(PendingLibraryChange::Uninstalled)
+    }
+
+    fn can_update(&self, registry: &Registry) -> bool {
+        !self.is_directory
+            && !registry.is_downloading_update(&self.name)
+            && registry.borrow_library_update(&self.name).is_some()
+    }
+
+    fn status(&self, registry: &Registry) -> String {
+        match self.pending_change {
+            Some(PendingLibraryChange::Installed) => "Restart to load".to_owned(),
+            Some(PendingLibraryChange::Uninstalled) => "Restart to unload".to_owned(),
+            None if self.name == "Factory" => "Built in".to_owned(),
+            None if registry.is_downloading_update(&self.name) => "Downloading...".to_owned(),
+            None => registry
+                .borrow_library_update(&self.name)
+                .map(|update| format!("{} available", update.version))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+fn collect_entries(registry: &Registry) -> Vec<LibraryEntry> {
+    let pending = registry.borrow_pending_library_changes();
+    let mut entries: Vec<_> = registry
+        .borrow_library_infos()
+        .map(|(name, info)| LibraryEntry {
+            name: name.clone(),
+            pretty_name: info.pretty_name.clone(),
+            version: Some(info.version),
+            is_directory: registry.is_directory_library(name),
+            pending_change: pending.get(name).cloned(),
+        })
+        .collect();
+    let mut new_names: Vec<_> = pending
+        .iter()
+        .filter(|(name, change)| {
+            **change == PendingLibraryChange::Installed
+                && registry.borrow_library_info(name).is_none()
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+    new_names.sort();
+    for name in new_names {
+        entries.push(LibraryEntry {
+            pretty_name: name.clone(),
+            name,
+            version: None,
+            is_directory: false,
+            pending_change: Some(PendingLibraryChange::Installed),
+        });
+    }
+    entries
+}
+
+scui::widget! {
+    pub LibraryManager
+    State {
+        update_icon: usize,
+        export_icon: usize,
+        delete_icon: usize,
+        entries: Vec<LibraryEntry>,
+        num_visible_entries: usize,
+        scroll_offset: usize,
+    }
+    Children {
+        path_box: ChildHolder<Rc<TextBox>>,
+        install_button: ChildHolder<Rc<IconButton>>,
+    }
+}
+
+/// A slightly larger grid size.
+const CG: f32 = grid(1) + GRID_P;
+const ENTRY_HEIGHT: f32 = CG;
+const PATH_BOX_HEIGHT: f32 = CG;
+const LIST_Y: f32 = PATH_BOX_HEIGHT + GRID_P * 2.0;
+const DEV_MODE_WIDTH: f32 = grid(5);
+const DEV_MODE_X: f32 = TAB_BODY_WIDTH - GRID_P - DEV_MODE_WIDTH;
+const UPDATES_X: f32 = DEV_MODE_X - GRID_P - DEV_MODE_WIDTH;
+const TEXT_PATCHES_X: f32 = UPDATES_X - GRID_P - DEV_MODE_WIDTH;
+const THEME_X: f32 = TEXT_PATCHES_X - GRID_P - DEV_MODE_WIDTH;
+const INSTALL_X: f32 = THEME_X - GRID_P - CG;
+const VERSION_WIDTH: f32 = grid(3);
+const STATUS_WIDTH: f32 = grid(5);
+/// Where each column starts, from right to left.
+const DELETE_X: f32 = TAB_BODY_WIDTH - GRID_P * 2.0 - grid(1);
+const EXPORT_X: f32 = DELETE_X - GRID_P - grid(1);
+const UPDATE_X: f32 = EXPORT_X - GRID_P - grid(1);
+const STATUS_X: f32 = UPDATE_X - GRID_P - STATUS_WIDTH;
+const VERSION_X: f32 = STATUS_X - GRID_P - VERSION_WIDTH;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Name,
+    Update,
+    Export,
+    Delete,
+}
+
+impl Column {
+    fn at(x: f32) -> Self {
+        if x >= DELETE_X {
+            Self::Delete
+        } else if x >= EXPORT_X {
+            Self::Export
+        } else if x >= UPDATE_X {
+            Self::Update
+        } else {
+            Self::Name
+        }
+    }
+}
+
+impl LibraryManager {
+    pub fn new(parent: &impl LibraryManagerParent) -> Rc<Self> {
+        let inter = parent.provide_gui_interface();
+        let gui_state = inter.state.borrow();
+        let registry = gui_state.registry.borrow();
+        let list_height = TAB_BODY_HEIGHT - LIST_Y - GRID_P;
+        // The first row is the column headers.
+        let num_visible_entries = (list_height / ENTRY_HEIGHT) as usize - 1;
+        let state = LibraryManagerState {
+            update_icon: registry.lookup_icon("Factory:increase").unwrap(),
+            export_icon: registry.lookup_icon("Factory:save").unwrap(),
+            delete_icon: registry.lookup_icon("Factory:delete").unwrap(),
+            entries: collect_entries(&registry),
+            num_visible_entries,
+            scroll_offset: 0,
+        };
+        let this = Rc::new(Self::create(parent, state));
+
+        let path_box = TextBox::new(
+            &this,
+            (GRID_P, GRID_P),
+            (INSTALL_X - GRID_P * 2.0, PATH_BOX_HEIGHT),
+            "".to_owned(),
+            Box::new(|_| ()),
+        );
+        let this2 = Rc::clone(&this);
+        let install_button = IconButton::new(
+            &this,
+            (INSTALL_X, GRID_P),
+            CG,
+            registry.lookup_icon("Factory:add").unwrap(),
+            move |_| this2.on_install(),
+            "Install the .ablib file typed in the box to the left",
+        );
+
+        let mut children = this.children.borrow_mut();
+        children.path_box = path_box.into();
+        children.install_button = install_button.into();
+        drop(children);
+
+        this
+    }
+
+    fn refresh_entries(self: &Rc<Self>) {
+        let entries = self.with_gui_state(|state| collect_entries(&state.registry.borrow()));
+        let mut state = self.state.borrow_mut();
+        state.entries = entries;
+        state.scroll_offset = 0;
+    }
+
+    fn get_typed_path(self: &Rc<Self>) -> Option<PathBuf> {
+        let text = self.children.borrow().path_box.get_text();
+        let text = text.trim().trim_matches('"');
+        if text.is_empty() {
+            None
+        } else {
+            Some(PathBuf::from(text))
+        }
+    }
+
+    fn on_install(self: &Rc<Self>) -> MaybeMouseBehavior {
+        let this = Rc::clone(self);
+        OnClickBehavior::wrap(move || {
+            let path = if let Some(path) = this.get_typed_path() {
+                path
+            } else {
+                this.with_gui_state_mut(|state| {
+                    state.add_error_status(
+                        "ERROR: Type the path of a .ablib file to install it.".to_owned(),
+                    )
+                });
+                return;
+            };
+            let res =
+                this.with_gui_state(|state| state.registry.borrow_mut().install_library(&path));
+            this.refresh_entries();
+            this.with_gui_state_mut(|state| match res {
+                Ok(name) => state.add_success_status(format!(
+                    "Installed {}, restart Audiobench to start using it.",
+                    name
+                )),
+                Err(err) => state.add_error_status(err),
+            });
+        })
+    }
+
+    fn on_uninstall(self: &Rc<Self>, name: String) -> MaybeMouseBehavior {
+        let this = Rc::clone(self);
+        OnClickBehavior::wrap(move || {
+            let res =
+                this.with_gui_state(|state| state.registry.borrow_mut().uninstall_library(&name));
+            this.refresh_entries();
+            this.with_gui_state_mut(|state| match res {
+                Ok(()) => state.add_success_status(format!(
+                    "Uninstalled {}, it will stay loaded until Audiobench restarts.",
+                    name
+                )),
+                Err(err) => state.add_error_status(err),
+            });
+        })
+    }
+
+    fn on_export(self: &Rc<Self>, name: String) -> MaybeMouseBehavior {
+        let this = Rc::clone(self);
+        OnClickBehavior::wrap(move || {
+            let res = this.with_gui_state(|state| {
+                let registry = state.registry.borrow();
+                // Export to the folder typed in the box, or next to the library folder otherwise.
+                let target_dir = match this.get_typed_path() {
+                    Some(path) if path.is_dir() => path,
+                    _ => registry
+                        .borrow_library_path()
+                        .parent()
+                        .unwrap_or_else(|| registry.borrow_library_path())
+                        .to_owned(),
+                };
+                registry.export_library(&name, &target_dir)
+            });
+            this.with_gui_state_mut(|state| match res {
+                Ok(path) => state.add_success_status(format!(
+                    "Exported {} to {}.",
+                    name,
+                    path.to_string_lossy()
+                )),
+                Err(err) => state.add_error_status(err),
+            });
+        })
+    }
+
+    fn on_update(self: &Rc<Self>, name: String) -> MaybeMouseBehavior {
+        let this = Rc::clone(self);
+        OnClickBehavior::wrap(move || {
+            let res = this
+                .with_gui_state(|state| state.registry.borrow_mut().start_library_update(&name));
+            this.with_gui_state_mut(|state| match res {
+                Ok(()) => state.add_success_status(format!("Downloading the update for {}.", name)),
+                Err(err) => state.add_error_status(err),
+            });
+        })
+    }
+
+    fn is_over_updates_toggle(pos: Vec2D) -> bool {
+        pos.x >= UPDATES_X
+            && pos.x < UPDATES_X + DEV_MODE_WIDTH
+            && pos.y >= GRID_P
+            && pos.y < GRID_P + PATH_BOX_HEIGHT
+    }
+
+    fn on_toggle_updates(self: &Rc<Self>) -> MaybeMouseBehavior {
+        let this = Rc::clone(self);
+        OnClickBehavior::wrap(move || {
+            this.with_gui_state_mut(|state| {
+                let mut registry = state.registry.borrow_mut();
+                let enabled = !registry.is_checking_for_updates();
+                let res = registry.set_checking_for_updates(enabled);
+                drop(registry);
+                match res {
+                    Ok(()) if enabled => state.add_success_status(
+                        "Audiobench and libraries will be checked for updates.".to_owned(),
+                    ),
+                    Ok(()) => state.add_success_status(
+                        "Audiobench will no longer check for updates.".to_owned(),
+                    ),
+                    Err(err) => state.add_error_status(err),
+                }
+            });
+        })
+    }
+
+    fn is_over_text_patches_toggle(pos: Vec2D) -> bool {
+        pos.x >= TEXT_PATCHES_X
+            && pos.x < TEXT_PATCHES_X + DEV_MODE_WIDTH
+            && pos.y >= GRID_P
+            && pos.y < GRID_P + PATH_BOX_HEIGHT
+    }
+
+    fn on_toggle_text_patches(self: &Rc<Self>) -> MaybeMouseBehavior {
+        let this = Rc::clone(self);
+        OnClickBehavior::wrap(move || {
+            this.with_gui_state_mut(|state| {
+                let mut registry = state.registry.borrow_mut();
+                let enabled = !registry.is_saving_text_patches();
+                let res = registry.set_saving_text_patches(enabled);
+                drop(registry);
+                match res {
+                    Ok(()) if enabled => state.add_success_status(
+                        "Patches will be saved as text from now on.".to_owned(),
+                    ),
+                    Ok(()) => state.add_success_status(
+                        "New patches will be saved in the binary format.".to_owned(),
+                    ),
+                    Err(err) => state.add_error_status(err),
+                }
+            });
+        })
+    }
+
+    fn is_over_theme_button(pos: Vec2D) -> bool {
+        pos.x >= THEME_X
+            && pos.x < THEME_X + DEV_MODE_WIDTH
+            && pos.y >= GRID_P
+            && pos.y < GRID_P + PATH_BOX_HEIGHT
+    }
+
+    /// Switches to the theme after the active one, wrapping around to the first one.
+    fn on_next_theme(self: &Rc<Self>) -> MaybeMouseBehavior {
+        let this = Rc::clone(self);
+        OnClickBehavior::wrap(move || {
+            this.with_gui_state_mut(|state| {
+                let mut registry = state.registry.borrow_mut();
+                let active = registry.get_active_theme();
+                let themes = registry.borrow_themes();
+                let index = themes
+                    .iter()
+                    .position(|theme| Rc::ptr_eq(theme, &active))
+                    .map(|index| (index + 1) % themes.len())
+                    .unwrap_or(0);
+                let name = themes[index].name.clone();
+                let res = registry.set_active_theme(&name);
+                drop(registry);
+                match res {
+                    Ok(()) => state.add_success_status(format!("Switched to the {} theme.", name)),
+                    Err(err) => state.add_error_status(err),
+                }
+            });
+        })
+    }
+
+    fn is_over_dev_mode_toggle(pos: Vec2D) -> bool {
+        pos.x >= DEV_MODE_X && pos.y >= GRID_P && pos.y < GRID_P + PATH_BOX_HEIGHT
+    }
+
+    fn on_toggle_dev_mode(self: &Rc<Self>) -> MaybeMouseBehavior {
+        let this = Rc::clone(self);
+        OnClickBehavior::wrap(move || {
+            this.with_gui_state_mut(|state| {
+                let mut registry = state.registry.borrow_mut();
+                let enabled = !registry.is_development_mode();
+                registry.set_development_mode(enabled);
+                drop(registry);
+                state.add_success_status(if enabled {
+                    "Folder libraries will be reloaded whenever their files change.".to_owned()
+                } else {
+                    "Development mode disabled.".to_owned()
+                });
+            });
+        })
+    }
+
+    /// Returns which library and which column the mouse is over.
+    fn entry_at(self: &Rc<Self>, pos: Vec2D) -> Option<(usize, Column)> {
+        let row = (pos.y - LIST_Y) / ENTRY_HEIGHT - 1.0;
+        if row < 0.0 {
+            return None;
+        }
+        let state = self.state.borrow();
+        let index = row as usize + state.scroll_offset;
+        if index < state.entries.len() {
+            Some((index, Column::at(pos.x)))
+        } else {
+            None
+        }
+    }
+}
+
+impl WidgetImpl<Renderer, DropTarget> for LibraryManager {
+    fn get_pos_impl(self: &Rc<Self>) -> Vec2D {
+        (0.0, HEADER_HEIGHT).into()
+    }
+
+    fn get_size_impl(self: &Rc<Self>) -> Vec2D {
+        (TAB_BODY_WIDTH, TAB_BODY_HEIGHT).into()
+    }
+
+    fn get_mouse_behavior_impl(
+        self: &Rc<Self>,
+        mouse_pos: Vec2D,
+        mods: &MouseMods,
+    ) -> MaybeMouseBehavior {
+        ris!(self.get_mouse_behavior_children(mouse_pos, mods));
+        if Self::is_over_dev_mode_toggle(mouse_pos) {
+            return self.on_toggle_dev_mode();
+        }
+        if Self::is_over_updates_toggle(mouse_pos) {
+            return self.on_toggle_updates();
+        }
+        if Self::is_over_text_patches_toggle(mouse_pos) {
+            return self.on_toggle_text_patches();
+        }
+        if Self::is_over_theme_button(mouse_pos) {
+            return self.on_next_theme();
+        }
+        let (index, column) = self.entry_at(mouse_pos)?;
+        let state = self.state.borrow();
+        let entry = &state.entries[index];
+        let can_update = self.with_gui_state(|gui| entry.can_update(&gui.registry.borrow()));
+        match column {
+            Column::Update if can_update => self.on_update(entry.name.clone()),
+            Column::Export if entry.can_export() => self.on_export(entry.name.clone()),
+            Column::Delete if entry.can_uninstall() => self.on_uninstall(entry.name.clone()),
+            _ => None,
+        }
+    }
+
+    fn on_scroll_impl(self: &Rc<Self>, _mouse_pos: Vec2D, delta: f32) -> Option<()> {
+        let mut state = self.state.borrow_mut();
+        if delta > 0.0 {
+            if state.scroll_offset > 0 {
+                state.scroll_offset -= 1;
+            }
+        } else if state.scroll_offset + state.num_visible_entries < state.entries.len() {
+            state.scroll_offset += 1;
+        }
+        Some(())
+    }
+
+    fn on_hover_impl(self: &Rc<Self>, pos: Vec2D) -> Option<()> {
+        ris!(self.on_hover_children(pos));
+        let tooltip = if let Some((index, column)) = self.entry_at(pos) {
+            let state = self.state.borrow();
+            let entry = &state.entries[index];
+            let can_update = self.with_gui_state(|gui| entry.can_update(&gui.registry.borrow()));
+            match column {
+                Column::Update if can_update => Tooltip {
+                    text: concat!(
+                        "Download and install the newest version of this library, it will be ",
+                        "loaded the next time Audiobench starts"
+                    )
+                    .to_owned(),
+                    interaction: vec![InteractionHint::LeftClick],
+                },
+                Column::Export if entry.can_export() => Tooltip {
+                    text: concat!(
+                        "Save this library as a .ablib file in the folder typed above, or in ",
+                        "your Documents folder if no folder is typed"
+                    )
+                    .to_owned(),
+                    interaction: vec![InteractionHint::LeftClick],
+                },
+                Column::Delete if entry.can_uninstall() => Tooltip {
+                    text: "Uninstall this library, deleting its files".to_owned(),
+                    interaction: vec![InteractionHint::LeftClick],
+                },
+                _ => Tooltip {
+                    text: "Installed libraries, folder libraries can be exported".to_owned(),
+                    interaction: vec![InteractionHint::Scroll],
+                },
+            }
+        } else if Self::is_over_updates_toggle(pos) {
+            Tooltip {
+                text: concat!(
+                    "Update checks: while on, Audiobench and libraries which support it are ",
+                    "checked for new versions when Audiobench starts"
+                )
+                .to_owned(),
+                interaction: vec![InteractionHint::LeftClick],
+            }
+        } else if Self::is_over_text_patches_toggle(pos) {
+            Tooltip {
+                text: concat!(
+                    "Text patches: while on, patches are saved as text, which works better with ",
+                    "version control"
+                )
+                .to_owned(),
+                interaction: vec![InteractionHint::LeftClick],
+            }
+        } else if Self::is_over_theme_button(pos) {
+            Tooltip {
+                text: concat!(
+                    "Theme: changes the colors and sizes used by Audiobench, libraries can ",
+                    "include more themes"
+                )
+                .to_owned(),
+                interaction: vec![InteractionHint::LeftClick],
+            }
+        } else if Self::is_over_dev_mode_toggle(pos) {
+            Tooltip {
+                text: concat!(
+                    "Development mode: while on, folder libraries are reloaded as soon as their ",
+                    "files change"
+                )
+                .to_owned(),
+                interaction: vec![InteractionHint::LeftClick],
+            }
+        } else {
+            Tooltip {
+                text: concat!(
+                    "Type the path of a .ablib file above and click the plus button to ",
+                    "install it"
+                )
+                .to_owned(),
+                interaction: vec![],
+            }
+        };
+        self.with_gui_state_mut(|state| state.set_tooltip(tooltip));
+        Some(())
+    }
+
+    fn draw_impl(self: &Rc<Self>, g: &mut Renderer) {
+        const GP: f32 = GRID_P;
+        const CS: f32 = CORNER_SIZE;
+        const FS: f32 = FONT_SIZE;
+        let state = self.state.borrow();
+
+        g.set_color(&COLOR_BG2);
+        g.draw_rect(0, TAB_BODY_SIZE);
+        self.draw_children(g);
+        let offset = state.scroll_offset;
+        let range = offset..(offset + state.num_visible_entries).min(state.entries.len());
+        // Update information can change at any time since it is retrieved in the background.
+        let (toggles, theme_name, updates) = self.with_gui_state(|gui_state| {
+            let registry = gui_state.registry.borrow();
+            let toggles = [
+                (DEV_MODE_X, registry.is_development_mode(), "Dev mode"),
+                (UPDATES_X, registry.is_checking_for_updates(), "Updates"),
+                (TEXT_PATCHES_X, registry.is_saving_text_patches(), "Text"),
+            ];
+            let updates: Vec<_> = state.entries[range.clone()]
+                .iter()
+                .map(|entry| (entry.status(&registry), entry.can_update(&registry)))
+                .collect();
+            (toggles, registry.get_active_theme().name.clone(), updates)
+        });
+        for (x, enabled, label) in toggles.iter() {
+            g.set_color(if *enabled {
+                &COLOR_EDITABLE
+            } else {
+                &COLOR_BG1
+            });
+            let size = (DEV_MODE_WIDTH, PATH_BOX_HEIGHT);
+            g.draw_rounded_rect((*x, GP), size, CS);
+            g.set_color(&COLOR_FG1);
+            let text = format!("{}: {}", label, if *enabled { "on" } else { "off" });
+            g.draw_text(FS, (*x, GP), size, (0, 0), 1, &text);
+        }
+        g.set_color(&COLOR_BG1);
+        let size = (DEV_MODE_WIDTH, PATH_BOX_HEIGHT);
+        g.draw_rounded_rect((THEME_X, GP), size, CS);
+        g.set_color(&COLOR_FG1);
+        let text = format!("Theme: {}", theme_name);
+        g.draw_text(FS, (THEME_X, GP), size, (0, 0), 1, &text);
+        g.set_color(&COLOR_BG0);
+        g.draw_rounded_rect((GP, LIST_Y), TAB_BODY_SIZE - (GP * 2.0, LIST_Y + GP), CS);
+
+        let mut y = LIST_Y;
+        g.set_color(&COLOR_FG0);
+        let headers = [
+            (GP * 2.0, VERSION_X - GP * 3.0, "Library"),
+            (VERSION_X, VERSION_WIDTH, "Version"),
+            (STATUS_X, STATUS_WIDTH, "Status"),
+        ];
+        for (x, width, text) in headers.iter() {
+            g.draw_text(FS, (*x, y), (*width, ENTRY_HEIGHT), (-1, 0), 1, text);
+        }
+        y += ENTRY_HEIGHT;
+
+        for (entry, (status, can_update)) in state.entries[range].iter().zip(updates) {
+            g.set_color(&COLOR_FG1);
+            if entry.pending_change == Some(PendingLibraryChange::Uninstalled) {
+                g.set_alpha(0.5);
+            }
+            let name = if entry.pretty_name == entry.name {
+                entry.name.clone()
+            } else {
+                format!("{} ({})", entry.pretty_name, entry.name)
+            };
+            let size = (VERSION_X - GP * 3.0, ENTRY_HEIGHT);
+            g.draw_text(FS, (GP * 2.0, y), size, (-1, 0), 1, &name);
+            if let Some(version) = entry.version {
+                let size = (VERSION_WIDTH, ENTRY_HEIGHT);
+                g.draw_text(
+                    FS,
+                    (VERSION_X, y),
+                    size,
+                    (-1, 0),
+                    1,
+                    &format!("{}", version),
+                );
+            }
+            let size = (STATUS_WIDTH, ENTRY_HEIGHT);
+            g.draw_text(FS, (STATUS_X, y), size, (-1, 0), 1, &status);
+            g.set_alpha(1.0);
+            if can_update {
+                g.draw_white_icon(state.update_icon, (UPDATE_X, y + GP / 2.0), grid(1));
+            }
+            if entry.can_export() {
+                g.draw_white_icon(state.export_icon, (EXPORT_X, y + GP / 2.0), grid(1));
+            }
+            if entry.can_uninstall() {
+                g.draw_white_icon(state.delete_icon, (DELETE_X, y + GP / 2.0), grid(1));
+            }
+            y += ENTRY_HEIGHT;
+        }
+    }
+}
+
+impl GuiTab for Rc<LibraryManager> {
+    fn get_name(self: &Self) -> String {
+        "Libraries".to_owned()
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{
+    gui::{constants::*, mouse_behaviors::ManipulateMidiBinding, GuiTab, InteractionHint, Tooltip},
+    scui_config::{DropTarget, MaybeMouseBehavior, Renderer},
+};
+use scui::{MouseMods, OnClickBehavior, Vec2D, WidgetImpl};
+use shared_util::prelude::*;
+
+scui::widget! {
+    pub MidiMappings
+    State {
+        delete_icon: usize,
+        num_visible_entries: usize,
+        scroll_offset: usize,
+    }
+}
+
+const ENTRY_HEIGHT: f32 = grid(1) + GRID_P;
+const CONTROLLER_WIDTH: f32 = grid(2);
+const RANGE_WIDTH: f32 = grid(3);
+const CURVE_WIDTH: f32 = grid(4);
+/// Where each column starts, from right to left.
+const DELETE_X: f32 = TAB_BODY_WIDTH - GRID_P * 2.0 - grid(1);
+const CURVE_X: f32 = DELETE_X - GRID_P - CURVE_WIDTH;
+const MAX_X: f32 = CURVE_X - GRID_P - RANGE_WIDTH;
+const MIN_X: f32 = MAX_X - GRID_P - RANGE_WIDTH;
+const TARGET_X: f32 = GRID_P * 2.0 + CONTROLLER_WIDTH;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Controller,
+    Target,
+    Min,
+    Max,
+    Curve,
+    Delete,
+}
+
+impl Column {
+    fn at(x: f32) -> Self {
+        if x >= DELETE_X {
+            Self::Delete
+        } else if x >= CURVE_X {
+            Self::Curve
+        } else if x >= MAX_X {
+            Self::Max
+        } else if x >= MIN_X {
+            Self::Min
+        } else if x >= TARGET_X {
+            Self::Target
+        } else {
+            Self::Controller
+        }
+    }
+}
+
+impl MidiMappings {
+    pub fn new(parent: &impl MidiMappingsParent) -> Rc<Self> {
+        let inter = parent.provide_gui_interface();
+        let state = inter.state.borrow();
+        let registry = state.registry.borrow();
+        let num_visible_entries = ((TAB_BODY_HEIGHT - GRID_P * 2.0) / ENTRY_HEIGHT) as usize - 1;
+        let state = MidiMappingsState {
+            delete_icon: registry.lookup_icon("Factory:delete").unwrap(),
+            num_visible_entries,
+            scroll_offset: 0,
+        };
+        Rc::new(Self::create(parent, state))
+    }
+
+    fn num_bindings(self: &Rc<Self>) -> usize {
+        self.with_gui_state(|state| {
+            let engine = state.engine.borrow();
+            let graph = engine.borrow_module_graph_ref().borrow();
+            graph.borrow_midi_bindings().len()
+        })
+    }
+
+    /// Returns which binding and which column the mouse is over.
+    fn entry_at(self: &Rc<Self>, pos: Vec2D) -> Option<(usize, Column)> {
+        // The first row is the column headers.
+        let row = (pos.y - GRID_P) / ENTRY_HEIGHT - 1.0;
+        if row < 0.0 {
+            return None;
+        }
+        let index = row as usize + self.state.borrow().scroll_offset;
+        if index < self.num_bindings() {
+            Some((index, Column::at(pos.x)))
+        } else {
+            None
+        }
+    }
+}
+
+impl WidgetImpl<Renderer, DropTarget> for MidiMappings {
+    fn get_pos_impl(self: &Rc<Self>) -> Vec2D {
+        (0.0, HEADER_HEIGHT).into()
+    }
+
+    fn get_size_impl(self: &Rc<Self>) -> Vec2D {
+        (TAB_BODY_WIDTH, TAB_BODY_HEIGHT).into()
+    }
+
+    fn get_mouse_behavior_impl(
+        self: &Rc<Self>,
+        mouse_pos: Vec2D,
+        _mods: &MouseMods,
+    ) -> MaybeMouseBehavior {
+        let (index, column) = self.entry_at(mouse_pos)?;
+        let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
+        match column {
+            Column::Min => Some(Box::new(ManipulateMidiBinding::start_only(self, index))),
+            Column::Max => Some(Box::new(ManipulateMidiBinding::end_only(self, index))),
+            Column::Curve => OnClickBehavior::wrap(move || {
+                let mut engine = engine.borrow_mut();
+                let graph_ptr = Rc::clone(engine.borrow_module_graph_ref());
+                let mut graph = graph_ptr.borrow_mut();
+                // The list may have changed since this was clicked.
+                let binding = match graph.borrow_midi_bindings_mut().get_mut(index) {
+                    Some(binding) => binding,
+                    None => return,
+                };
+                binding.curve = binding.curve.next();
+                drop(graph);
+                engine.reload_midi_bindings();
+            }),
+            Column::Delete => OnClickBehavior::wrap(move || {
+                let mut engine = engine.borrow_mut();
+                let num_bindings = engine
+                    .borrow_module_graph_ref()
+                    .borrow()
+                    .borrow_midi_bindings()
+                    .len();
+                if index < num_bindings {
+                    engine.remove_midi_binding(index);
+                }
+            }),
+            Column::Controller | Column::Target => None,
+        }
+    }
+
+    fn on_scroll_impl(self: &Rc<Self>, _mouse_pos: Vec2D, delta: f32) -> Option<()> {
+        let num_bindings = self.num_bindings();
+        let mut state = self.state.borrow_mut();
+        if delta > 0.0 {
+            if state.scroll_offset > 0 {
+                state.scroll_offset -= 1;
+            }
+        } else {
+            if state.scroll_offset + state.num_visible_entries < num_bindings {
+                state.scroll_offset += 1;
+            }
+        }
+        Some(())
+    }
+
+    fn on_hover_impl(self: &Rc<Self>, pos: Vec2D) -> Option<()> {
+        let tooltip = if let Some((_, column)) = self.entry_at(pos) {
+            match column {
+                Column::Controller | Column::Target => Tooltip {
+                    text: "Which MIDI controller is bound to which control".to_owned(),
+                    interaction: vec![InteractionHint::Scroll],
+                },
+                Column::Min | Column::Max => Tooltip {
+                    text: "The value the control has when the MIDI controller is at this end"
+                        .to_owned(),
+                    interaction: vec![
+                        InteractionHint::LeftClickAndDrag,
+                        InteractionHint::DoubleClick,
+                        InteractionHint::PrecisionModifier,
+                        InteractionHint::SnappingModifier,
+                    ],
+                },
+                Column::Curve => Tooltip {
+                    text: "How the MIDI controller is mapped to the range, click to change"
+                        .to_owned(),
+                    interaction: vec![InteractionHint::LeftClick],
+                },
+                Column::Delete => Tooltip {
+                    text: "Remove this binding".to_owned(),
+                    interaction: vec![InteractionHint::LeftClick],
+                },
+            }
+        } else {
+            Tooltip {
+                text: concat!(
+                    "To bind a MIDI controller, right click a knob or slider and click the cc ",
+                    "button in the menu that opens"
+                )
+                .to_owned(),
+                interaction: vec![],
+            }
+        };
+        self.with_gui_state_mut(|state| state.set_tooltip(tooltip));
+        Some(())
+    }
+
+    fn draw_impl(self: &Rc<Self>, g: &mut Renderer) {
+        const GP: f32 = GRID_P;
+        const CS: f32 = CORNER_SIZE;
+        const FS: f32 = FONT_SIZE;
+        let state = self.state.borrow();
+
+        g.set_color(&COLOR_BG2);
+        g.draw_rect(0, TAB_BODY_SIZE);
+        g.set_color(&COLOR_BG0);
+        g.draw_rounded_rect(GP, TAB_BODY_SIZE - GP * 2.0, CS);
+
+        let mut y = GP;
+        g.set_color(&COLOR_FG0);
+        let headers = [
+            (GP * 2.0, CONTROLLER_WIDTH, "CC"),
+            (TARGET_X, MIN_X - TARGET_X - GP, "Control"),
+            (MIN_X, RANGE_WIDTH, "Min"),
+            (MAX_X, RANGE_WIDTH, "Max"),
+            (CURVE_X, CURVE_WIDTH, "Curve"),
+        ];
+        for (x, width, text) in headers.iter() {
+            g.draw_text(FS, (*x, y), (*width, ENTRY_HEIGHT), (-1, 0), 1, text);
+        }
+        y += ENTRY_HEIGHT;
+
+        self.with_gui_state(|gui_state| {
+            let engine = gui_state.engine.borrow();
+            let graph = engine.borrow_module_graph_ref().borrow();
+            let bindings = graph.borrow_midi_bindings();
+            if bindings.len() == 0 {
+                g.set_color(&COLOR_FG0);
+                g.draw_text(
+                    FS,
+                    (GP * 2.0, y),
+                    (TAB_BODY_WIDTH - GP * 4.0, ENTRY_HEIGHT),
+                    (-1, 0),
+                    1,
+                    "There are no MIDI bindings in this patch.",
+                );
+                return;
+            }
+            let offset = state.scroll_offset;
+            let range = offset..(offset + state.num_visible_entries).min(bindings.len());
+            for binding in &bindings[range] {
+                let suffix = binding
+                    .get_control()
+                    .map(|control| control.borrow().suffix.clone())
+                    .unwrap_or_default();
+                g.set_color(&COLOR_FG1);
+                let controller = format!("{}", binding.controller);
+                let target = binding.describe_target();
+                let min = format!("{}{}", format_decimal(binding.range.0, 3), suffix);
+                let max = format!("{}{}", format_decimal(binding.range.1, 3), suffix);
+                let size = (CONTROLLER_WIDTH, ENTRY_HEIGHT);
+                g.draw_text(FS, (GP * 2.0, y), size, (-1, 0), 1, &controller);
+                let size = (MIN_X - TARGET_X - GP, ENTRY_HEIGHT);
+                g.draw_text(FS, (TARGET_X, y), size, (-1, 0), 1, &target);
+
+                for (x, text) in [(MIN_X, min), (MAX_X, max)].iter() {
+                    g.set_color(&COLOR_BG1);
+                    g.draw_rounded_rect((*x, y + GP / 2.0), (RANGE_WIDTH, grid(1)), CS);
+                    g.set_color(&COLOR_FG1);
+                    g.draw_text(FS, (*x, y), (RANGE_WIDTH, ENTRY_HEIGHT), (0, 0), 1, text);
+                }
+                g.set_color(&COLOR_BG1);
+                g.draw_rounded_rect((CURVE_X, y + GP / 2.0), (CURVE_WIDTH, grid(1)), CS);
+                g.set_color(&COLOR_FG1);
+                let size = (CURVE_WIDTH, ENTRY_HEIGHT);
+                g.draw_text(FS, (CURVE_X, y), size, (0, 0), 1, binding.curve.name());
+                g.draw_white_icon(state.delete_icon, (DELETE_X, y + GP / 2.0), grid(1));
+                y += ENTRY_HEIGHT;
+            }
+        });
+    }
+}
+
+impl GuiTab for Rc<MidiMappings> {
+    fn get_name(self: &Self) -> String {
+        "MIDI".to_owned()
+    }
+}
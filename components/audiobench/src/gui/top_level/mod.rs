@@ -2,11 +2,16 @@
 
 pub mod graph;
 mod header;
+mod library_manager;
+mod midi_mappings;
 mod module_browser;
+mod morph_slider;
 mod note_graph;
 mod patch_browser;
 
 pub use header::Header;
+pub use library_manager::*;
+pub use midi_mappings::*;
 pub use module_browser::*;
 pub use note_graph::*;
 pub use patch_browser::*;
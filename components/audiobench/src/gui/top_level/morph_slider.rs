@@ -0,0 +1,188 @@
+use crate::{
+    gui::{constants::*, mouse_behaviors::ManipulateMorph, InteractionHint, Tooltip},
+    scui_config::{DropTarget, MaybeMouseBehavior, Renderer},
+};
+use scui::{MouseMods, OnClickBehavior, Vec2D, WidgetImpl};
+use shared_util::prelude::*;
+
+// Blends between compare slots A and B, with a box at the end for choosing a MIDI controller which
+// also moves it. See `engine::morph`.
+scui::widget! {
+    pub MorphSlider
+    State {
+        pos: Vec2D,
+        size: Vec2D,
+    }
+}
+
+impl MorphSlider {
+    pub fn new(parent: &impl MorphSliderParent, pos: Vec2D, size: Vec2D) -> Rc<Self> {
+        let state = MorphSliderState { pos, size };
+        Rc::new(Self::create(parent, state))
+    }
+
+    /// Where the MIDI learn box starts.
+    fn learn_x(self: &Rc<Self>) -> f32 {
+        let size = self.state.borrow().size;
+        size.x - size.y
+    }
+
+    fn on_learn_click(self: &Rc<Self>, mods: &MouseMods) -> MaybeMouseBehavior {
+        let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
+        let this = Rc::clone(self);
+        let right_click = mods.right_click;
+        OnClickBehavior::wrap(move || {
+            let mut engine = engine.borrow_mut();
+            let message = if right_click {
+                if engine.get_morph_controller().is_none() {
+                    return;
+                }
+                engine.remove_morph_controller();
+                "MIDI binding removed."
+            } else if engine.is_learning_morph_controller() {
+                engine.cancel_midi_learn();
+                return;
+            } else {
+                engine.start_morph_midi_learn();
+                "Move a controller on your MIDI device to bind it."
+            };
+            drop(engine);
+            this.with_gui_state_mut(|state| state.add_success_status(message.to_owned()));
+        })
+    }
+}
+
+impl WidgetImpl<Renderer, DropTarget> for MorphSlider {
+    fn get_pos_impl(self: &Rc<Self>) -> Vec2D {
+        self.state.borrow().pos
+    }
+
+    fn get_size_impl(self: &Rc<Self>) -> Vec2D {
+        self.state.borrow().size
+    }
+
+    fn get_mouse_behavior_impl(
+        self: &Rc<Self>,
+        pos: Vec2D,
+        mods: &MouseMods,
+    ) -> MaybeMouseBehavior {
+        if pos.x >= self.learn_x() {
+            self.on_learn_click(mods)
+        } else if mods.right_click {
+            None
+        } else {
+            Some(Box::new(ManipulateMorph::new(self)))
+        }
+    }
+
+    fn on_hover_impl(self: &Rc<Self>, pos: Vec2D) -> Option<()> {
+        let (enabled, controller) = self.with_gui_state(|state| {
+            let engine = state.engine.borrow();
+            (engine.is_morph_enabled(), engine.get_morph_controller())
+        });
+        let tooltip = if pos.x >= self.learn_x() {
+            if let Some(controller) = controller {
+                Tooltip {
+                    text: format!(
+                        concat!(
+                            "The morph is bound to MIDI controller #{}. Click to bind a ",
+                            "different controller or right click to remove the binding"
+                        ),
+                        controller
+                    ),
+                    interaction: vec![InteractionHint::LeftClick, InteractionHint::RightClick],
+                }
+            } else {
+                Tooltip {
+                    text: "MIDI learn: click, then move a controller on your MIDI device"
+                        .to_owned(),
+                    interaction: vec![InteractionHint::LeftClick],
+                }
+            }
+        } else if enabled {
+            Tooltip {
+                text: concat!(
+                    "Drag to blend between compare slots A and B, double click to stop ",
+                    "morphing"
+                )
+                .to_owned(),
+                interaction: vec![
+                    InteractionHint::LeftClickAndDrag,
+                    InteractionHint::DoubleClick,
+                ],
+            }
+        } else {
+            Tooltip {
+                text: concat!(
+                    "Drag to blend between compare slots A and B, which only works when both ",
+                    "contain the same modules and connections"
+                )
+                .to_owned(),
+                interaction: vec![InteractionHint::LeftClickAndDrag],
+            }
+        };
+        self.with_gui_state_mut(|state| state.set_tooltip(tooltip));
+        Some(())
+    }
+
+    fn draw_impl(self: &Rc<Self>, g: &mut Renderer) {
+        const GP: f32 = GRID_P;
+        let size = self.state.borrow().size;
+        let (enabled, amount, learning, controller) = self.with_gui_state(|state| {
+            let engine = state.engine.borrow();
+            (
+                engine.is_morph_enabled(),
+                engine.get_morph_amount(),
+                engine.is_learning_morph_controller(),
+                engine.get_morph_controller(),
+            )
+        });
+
+        let track_width = self.learn_x() - GP;
+        g.set_color(&COLOR_BG0);
+        g.draw_rounded_rect(0, (track_width, size.y), CORNER_SIZE);
+        if enabled {
+            g.set_color(&COLOR_EDITABLE);
+            g.set_alpha(0.5);
+            g.draw_rounded_rect(0, (track_width * amount, size.y), CORNER_SIZE);
+            g.set_alpha(1.0);
+            let x = track_width * amount;
+            g.set_color(&COLOR_FG1);
+            g.draw_line((x, 0.0), (x, size.y), 1.0);
+        }
+        g.set_color(&COLOR_FG1);
+        if !enabled {
+            g.set_alpha(0.5);
+        }
+        let text_size = (track_width - GP * 2.0, size.y);
+        g.draw_text(FONT_SIZE, (GP, 0.0), text_size, (-1, 0), 1, "A");
+        g.draw_text(FONT_SIZE, (GP, 0.0), text_size, (1, 0), 1, "B");
+        let status = if enabled {
+            format!("{:.0}%", amount * 100.0)
+        } else {
+            "Off".to_owned()
+        };
+        g.draw_text(FONT_SIZE, (GP, 0.0), text_size, (0, 0), 1, &status);
+        g.set_alpha(1.0);
+
+        let learn_pos = (self.learn_x(), 0.0);
+        let learn_size = size.y;
+        if learning {
+            g.set_color(&COLOR_WARNING);
+            g.draw_rounded_rect(learn_pos, learn_size, CORNER_SIZE);
+            g.set_color(&COLOR_FG1);
+            g.draw_text(FONT_SIZE, learn_pos, learn_size, (0, 0), 1, "...");
+        } else {
+            g.set_color(&COLOR_BG0);
+            g.draw_rounded_rect(learn_pos, learn_size, CORNER_SIZE);
+            if let Some(controller) = controller {
+                g.set_color(&COLOR_FG1);
+                let text = format!("{}", controller);
+                g.draw_text(FONT_SIZE, learn_pos, learn_size, (0, 0), 1, &text);
+            } else {
+                g.set_color(&COLOR_FG0);
+                g.draw_text(FONT_SIZE, learn_pos, learn_size, (0, 0), 1, "cc");
+            }
+        }
+    }
+}
@@ -1,22 +1,35 @@
 use crate::{
     gui::{
         constants::*,
+        top_level::morph_slider::MorphSlider,
         ui_widgets::{IconButton, TextBox},
         GuiTab, InteractionHint, Tooltip,
     },
-    registry::save_data::Patch,
+    registry::{
+        patch_metadata::{self, PatchMetadata},
+        save_data::Patch,
+    },
     scui_config::{DropTarget, MaybeMouseBehavior, Renderer},
 };
 use clipboard::ClipboardProvider;
-use scui::{ChildHolder, MouseMods, OnClickBehavior, Vec2D, Widget, WidgetImpl};
+use scui::{ChildHolder, MouseMods, OnClickBehavior, Vec2D, WidgetImpl};
 use shared_util::prelude::*;
+use std::collections::HashMap;
 
 scui::widget! {
     pub PatchBrowser
     State {
         delete_icon: usize,
+        up_icon: usize,
+        folder_icon: usize,
         entries: Vec<Rcrc<Patch>>,
-        alphabetical_order: Vec<usize>,
+        // The rows currently shown in the patch list, see `refresh`.
+        rows: Vec<Row>,
+        // The library followed by the folders inside it which are being shown, empty to show a
+        // list of libraries.
+        folder: Vec<String>,
+        search: String,
+        sort_by_date: bool,
         current_entry_index: Option<usize>,
         num_visible_entries: usize,
         scroll_offset: usize,
@@ -27,6 +40,21 @@ scui::widget! {
         new_button: ChildHolder<Rc<IconButton>>,
         copy_button: ChildHolder<Rc<IconButton>>,
         paste_button: ChildHolder<Rc<IconButton>>,
+        search_box: ChildHolder<Rc<TextBox>>,
+        sort_button: ChildHolder<Rc<IconButton>>,
+        author_box: ChildHolder<Rc<TextBox>>,
+        category_box: ChildHolder<Rc<TextBox>>,
+        tags_box: ChildHolder<Rc<TextBox>>,
+        description_box: ChildHolder<Rc<TextBox>>,
+        morph_slider: ChildHolder<Rc<MorphSlider>>,
+        randomize_button: ChildHolder<Rc<IconButton>>,
+        repeat_randomize_button: ChildHolder<Rc<IconButton>>,
+        seed_box: ChildHolder<Rc<TextBox>>,
+        variation_box: ChildHolder<Rc<TextBox>>,
+        patch_seed_box: ChildHolder<Rc<TextBox>>,
+        // Only present while there is a recovered patch to restore or discard.
+        restore_button: Option<Rc<IconButton>>,
+        discard_recovery_button: Option<Rc<IconButton>>,
     }
 }
 
@@ -34,8 +62,45 @@ scui::widget! {
 const CG: f32 = grid(1) + GRID_P;
 const ENTRY_HEIGHT: f32 = CG;
 const NAME_BOX_HEIGHT: f32 = CG;
+/// Where the patch list starts, below the name and search boxes.
+const LIST_Y: f32 = (NAME_BOX_HEIGHT + GRID_P) * 2.0;
 /// How large each half of the GUI takes.
 const HW: f32 = (TAB_BODY_WIDTH - GRID_P * 3.0) / 2.0;
+/// Where the metadata of the current patch is shown.
+const META_X: f32 = GRID_P * 2.0 + HW;
+const LABEL_WIDTH: f32 = grid(4);
+const NUM_META_BOXES: f32 = 4.0;
+/// The morph slider and the randomizer go below the metadata boxes, followed by the rest of the
+/// metadata.
+const MORPH_Y: f32 = (CG + GRID_P) * NUM_META_BOXES;
+const RANDOMIZE_Y: f32 = MORPH_Y + CG + GRID_P;
+const VARIATION_Y: f32 = RANDOMIZE_Y + CG + GRID_P;
+/// The toggle for deterministic mode and the seed of the patch.
+const NOTES_Y: f32 = VARIATION_Y + CG + GRID_P;
+const DETERMINISTIC_WIDTH: f32 = grid(5);
+const INFO_Y: f32 = NOTES_Y + CG + GRID_P;
+/// Height of the notice about a recovered patch at the bottom of the metadata area.
+const RECOVERY_HEIGHT: f32 = ENTRY_HEIGHT * 2.0 + GRID_P * 2.0;
+const RECOVERY_Y: f32 = TAB_BODY_HEIGHT - GRID_P - RECOVERY_HEIGHT;
+const SORT_BY_DATE_TOOLTIP: &str = "Sort patches by when they were last modified";
+const SORT_BY_NAME_TOOLTIP: &str = "Sort patches by name";
+
+/// What a row of the patch list shows.
+#[derive(Clone, Debug, PartialEq)]
+enum Row {
+    /// Goes back to the folder containing the current one.
+    Up,
+    Folder(String),
+    /// Index into `entries`.
+    Patch(usize),
+}
+
+fn describe_upgrade(changes: &[String]) -> String {
+    format!(
+        "Patch loaded and upgraded to newer library versions: {}.",
+        changes.join("; ")
+    )
+}
 
 impl PatchBrowser {
     pub fn new(parent: &impl PatchBrowserParent) -> Rc<Self> {
@@ -63,20 +128,28 @@ impl PatchBrowser {
             save_enabled = false;
         }
 
-        let alphabetical_order = (0..entries.len()).collect();
-        let entries = entries;
+        // Start out in the folder of the current patch.
+        let folder = if let Some(index) = current_entry_index {
+            folder_of(&entries[index].borrow())
+        } else {
+            Vec::new()
+        };
 
         let state = PatchBrowserState {
             delete_icon: registry.lookup_icon("Factory:delete").unwrap(),
+            up_icon: registry.lookup_icon("Factory:arrow_left").unwrap(),
+            folder_icon: registry.lookup_icon("Factory:arrow_right").unwrap(),
             entries,
-            alphabetical_order,
+            rows: Vec::new(),
+            folder,
+            search: String::new(),
+            sort_by_date: false,
             current_entry_index,
             num_visible_entries: 0,
             scroll_offset: 0,
         };
 
         let this = Rc::new(Self::create(parent, state));
-        this.sort();
 
         let patch_name = current_patch.borrow().borrow_name().to_owned();
         let this2 = Rc::clone(&this);
@@ -117,6 +190,32 @@ impl PatchBrowser {
             "Paste and load a patch from your clipboard",
         );
 
+        let (restore_button, discard_recovery_button) = if engine.borrow_recovered_patch().is_some()
+        {
+            let button_y = RECOVERY_Y + (RECOVERY_HEIGHT - CG) / 2.0;
+            let this2 = Rc::clone(&this);
+            let restore_button = IconButton::new(
+                &this,
+                (META_X + HW - CG * 2.0 - GRID_P * 2.0, button_y),
+                CG,
+                registry.lookup_icon("Factory:undo").unwrap(),
+                move |_| this2.on_restore_recovered_patch(),
+                "Load the recovered changes as a new patch",
+            );
+            let this2 = Rc::clone(&this);
+            let discard_button = IconButton::new(
+                &this,
+                (META_X + HW - CG - GRID_P, button_y),
+                CG,
+                registry.lookup_icon("Factory:delete").unwrap(),
+                move |_| this2.on_discard_recovered_patch(),
+                "Throw away the recovered changes",
+            );
+            (Some(restore_button), Some(discard_button))
+        } else {
+            (None, None)
+        };
+
         let this2 = Rc::clone(&this);
         let name_box = TextBox::new(
             &this,
@@ -127,9 +226,111 @@ impl PatchBrowser {
         );
         name_box.set_enabled(current_patch.borrow().is_writable());
 
-        // Extra +GRID_P because the padding under the last patch in the list shouldn't be
-        // rendered.
-        let patch_list_height = TAB_BODY_HEIGHT - GRID_P * 3.0 - name_box.get_size().y + GRID_P;
+        let this2 = Rc::clone(&this);
+        let search_box = TextBox::new(
+            &this,
+            (GRID_P, CG + GRID_P),
+            (HW - CG - GRID_P, NAME_BOX_HEIGHT),
+            String::new(),
+            Box::new(move |text| this2.on_search(text)),
+        );
+        let this2 = Rc::clone(&this);
+        search_box.set_change_action(Box::new(move |text| this2.on_search(text)));
+        let this2 = Rc::clone(&this);
+        let sort_button = IconButton::new(
+            &this,
+            (GRID_P + HW - CG, CG + GRID_P),
+            CG,
+            registry.lookup_icon("Factory:time").unwrap(),
+            move |_| this2.on_toggle_sort(),
+            SORT_BY_DATE_TOOLTIP,
+        );
+
+        let metadata = current_patch.borrow().borrow_metadata().clone();
+        let meta_box = |row: f32, text: String, edit: fn(&mut PatchMetadata, &str)| {
+            let this2 = Rc::clone(&this);
+            let text_box = TextBox::new(
+                &this,
+                (META_X + LABEL_WIDTH, (CG + GRID_P) * row),
+                (HW - LABEL_WIDTH, NAME_BOX_HEIGHT),
+                text,
+                Box::new(move |text| this2.on_edit_metadata(|metadata| edit(metadata, text))),
+            );
+            text_box.set_enabled(current_patch.borrow().is_writable());
+            text_box
+        };
+        let author_box = meta_box(0.0, metadata.author, |metadata, text| {
+            metadata.author = text.to_owned()
+        });
+        let category_box = meta_box(1.0, metadata.category, |metadata, text| {
+            metadata.category = text.to_owned()
+        });
+        let tags_box = meta_box(2.0, metadata.tags.join(", "), |metadata, text| {
+            metadata.set_tags_from_text(text)
+        });
+        let description_box = meta_box(3.0, metadata.description, |metadata, text| {
+            metadata.description = text.to_owned()
+        });
+        let morph_slider = MorphSlider::new(
+            &this,
+            (META_X + LABEL_WIDTH, MORPH_Y).into(),
+            (HW - LABEL_WIDTH, NAME_BOX_HEIGHT).into(),
+        );
+
+        let this2 = Rc::clone(&this);
+        let randomize_button = IconButton::new(
+            &this,
+            (META_X + LABEL_WIDTH, RANDOMIZE_Y),
+            CG,
+            registry.lookup_icon("Factory:random").unwrap(),
+            move |_| this2.on_randomize(false),
+            "Randomize every control in the note graph which is not locked, using a new seed",
+        );
+        let this2 = Rc::clone(&this);
+        let repeat_randomize_button = IconButton::new(
+            &this,
+            (META_X + LABEL_WIDTH + CG + GRID_P, RANDOMIZE_Y),
+            CG,
+            registry.lookup_icon("Factory:reset").unwrap(),
+            move |_| this2.on_randomize(true),
+            concat!(
+                "Randomize using the seed in the box, which gives the same result as before when ",
+                "the controls have the same values (undo first to get back to them)"
+            ),
+        );
+        let seed = engine
+            .get_last_randomize_seed()
+            .map(|seed| format!("{}", seed))
+            .unwrap_or_default();
+        let seed_x = LABEL_WIDTH + (CG + GRID_P) * 2.0;
+        let seed_box = TextBox::new(
+            &this,
+            (META_X + seed_x, RANDOMIZE_Y),
+            (HW - seed_x, NAME_BOX_HEIGHT),
+            seed,
+            Box::new(|_| ()),
+        );
+        let this2 = Rc::clone(&this);
+        let variation_box = TextBox::new(
+            &this,
+            (META_X + LABEL_WIDTH, VARIATION_Y),
+            (HW - LABEL_WIDTH, NAME_BOX_HEIGHT),
+            format_variation(engine.get_randomize_variation()),
+            Box::new(move |text| this2.on_set_variation(text)),
+        );
+        let this2 = Rc::clone(&this);
+        let patch_seed_x = LABEL_WIDTH + DETERMINISTIC_WIDTH + GRID_P;
+        let patch_seed_box = TextBox::new(
+            &this,
+            (META_X + patch_seed_x, NOTES_Y),
+            (HW - patch_seed_x, NAME_BOX_HEIGHT),
+            format!("{}", engine.get_patch_seed()),
+            Box::new(move |text| this2.on_set_patch_seed(text)),
+        );
+
+        // The list panel stops GRID_P above the bottom, but the padding under the last patch in
+        // the list doesn't need to fit inside it.
+        let patch_list_height = TAB_BODY_HEIGHT - LIST_Y;
         let num_visible_entries = (patch_list_height / ENTRY_HEIGHT) as usize;
 
         this.state.borrow_mut().num_visible_entries = num_visible_entries;
@@ -140,7 +341,22 @@ impl PatchBrowser {
         children.new_button = new_button.into();
         children.copy_button = copy_button.into();
         children.paste_button = paste_button.into();
+        children.search_box = search_box.into();
+        children.sort_button = sort_button.into();
+        children.author_box = author_box.into();
+        children.category_box = category_box.into();
+        children.tags_box = tags_box.into();
+        children.description_box = description_box.into();
+        children.morph_slider = morph_slider.into();
+        children.randomize_button = randomize_button.into();
+        children.repeat_randomize_button = repeat_randomize_button.into();
+        children.seed_box = seed_box.into();
+        children.variation_box = variation_box.into();
+        children.patch_seed_box = patch_seed_box.into();
+        children.restore_button = restore_button;
+        children.discard_recovery_button = discard_recovery_button;
         drop(children);
+        this.refresh();
 
         this
     }
@@ -149,11 +365,11 @@ impl PatchBrowser {
         let mut state = self.state.borrow_mut();
         let next_entry_index = state.entries.len();
         if new_patch.borrow().exists_on_disk() {
-            state.alphabetical_order.push(next_entry_index);
             state.entries.push(Rc::clone(new_patch));
             state.current_entry_index = Some(next_entry_index);
+            state.folder = folder_of(&new_patch.borrow());
             drop(state);
-            self.sort();
+            self.refresh();
         } else {
             state.current_entry_index = None;
             drop(state);
@@ -223,13 +439,44 @@ impl PatchBrowser {
                 .collect();
             let mut engine = engine.borrow_mut();
             let res = engine.new_patch_from_clipboard(data.as_bytes());
-            if let Ok(patch) = res {
+            if let Ok((patch, changes)) = res {
+                this.after_new_patch(patch);
+                this.show_patch_seed(engine.get_patch_seed());
+                this.with_gui_state_mut(|state| {
+                    if !changes.is_empty() {
+                        state.add_warning_status(describe_upgrade(&changes));
+                    } else {
+                        state.add_success_status(
+                            concat!(
+                                "Patch data loaded from clipboard. (Click the save button if you ",
+                                "want to keep it.)"
+                            )
+                            .to_owned(),
+                        );
+                    }
+                });
+            } else if let Err(err) = res {
+                this.with_gui_state_mut(|state| {
+                    state.add_error_status(err);
+                });
+            }
+        })
+    }
+
+    fn on_restore_recovered_patch(self: &Rc<Self>) -> MaybeMouseBehavior {
+        let this = Rc::clone(self);
+        let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
+        OnClickBehavior::wrap(move || {
+            let mut engine = engine.borrow_mut();
+            let res = engine.restore_recovered_patch();
+            if let Ok((patch, _)) = res {
                 this.after_new_patch(patch);
+                this.show_patch_seed(engine.get_patch_seed());
                 this.with_gui_state_mut(|state| {
                     state.add_success_status(
                         concat!(
-                            "Patch data loaded from clipboard. (Click the save button if you want",
-                            "to keep it.)"
+                            "Recovered changes loaded. (Click the save button if you want to ",
+                            "keep them.)"
                         )
                         .to_owned(),
                     );
@@ -239,9 +486,26 @@ impl PatchBrowser {
                     state.add_error_status(err);
                 });
             }
+            drop(engine);
+            this.remove_recovery_buttons();
         })
     }
 
+    fn on_discard_recovered_patch(self: &Rc<Self>) -> MaybeMouseBehavior {
+        let this = Rc::clone(self);
+        let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
+        OnClickBehavior::wrap(move || {
+            engine.borrow_mut().discard_recovered_patch();
+            this.remove_recovery_buttons();
+        })
+    }
+
+    fn remove_recovery_buttons(&self) {
+        let mut children = self.children.borrow_mut();
+        children.restore_button = None;
+        children.discard_recovery_button = None;
+    }
+
     fn on_load_patch(self: &Rc<Self>, patch: Rcrc<Patch>, index: usize) -> MaybeMouseBehavior {
         let this = Rc::clone(self);
         let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
@@ -250,13 +514,15 @@ impl PatchBrowser {
             state.current_entry_index = Some(index);
             drop(state);
             this.update_on_patch_change(&patch);
-            if let Err(..) = engine.borrow_mut().load_patch(patch) {
-                this.with_gui_state_mut(|state| {
-                    state.add_error_status(format!("ERROR: Patch data is corrupt."))
-                });
-            } else {
-                this.with_gui_state_mut(|state| state.add_success_status(format!("Patch loaded.")));
-            }
+            let res = engine.borrow_mut().load_patch(patch);
+            this.show_patch_seed(engine.borrow().get_patch_seed());
+            this.with_gui_state_mut(|state| match res {
+                Err(message) => state.add_error_status(message),
+                Ok(changes) if !changes.is_empty() => {
+                    state.add_warning_status(describe_upgrade(&changes))
+                }
+                Ok(..) => state.add_success_status("Patch loaded.".to_owned()),
+            });
         })
     }
 
@@ -267,36 +533,234 @@ impl PatchBrowser {
                 .borrow_mut()
                 .rename_current_patch(new_name.to_owned());
         });
-        self.sort();
+        self.refresh();
     }
 
-    fn sort(&self) {
-        let mut state = self.state.borrow_mut();
-        let PatchBrowserState {
-            alphabetical_order,
-            entries,
-            ..
-        } = &mut *state;
-        alphabetical_order.sort_by(|a, b| {
-            entries[*a]
+    fn on_edit_metadata(self: &Rc<Self>, edit: impl FnOnce(&mut PatchMetadata)) {
+        self.with_gui_state_mut(|state| {
+            let mut engine = state.engine.borrow_mut();
+            let old = engine
+                .borrow_current_patch()
                 .borrow()
-                .borrow_name()
-                .cmp(&entries[*b].borrow().borrow_name())
+                .borrow_metadata()
+                .clone();
+            let mut metadata = old.clone();
+            edit(&mut metadata);
+            if metadata != old {
+                if let Err(err) = engine.set_current_patch_metadata(metadata) {
+                    drop(engine);
+                    state.add_error_status(err);
+                }
+            }
         });
     }
 
+    /// With `repeat`, the seed in the seed box is used instead of a new one.
+    fn on_randomize(self: &Rc<Self>, repeat: bool) -> MaybeMouseBehavior {
+        let this = Rc::clone(self);
+        let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
+        OnClickBehavior::wrap(move || {
+            let seed = if repeat {
+                let text = this.children.borrow().seed_box.get_text();
+                if let Ok(seed) = text.trim().parse() {
+                    Some(seed)
+                } else {
+                    this.with_gui_state_mut(|state| {
+                        state.add_error_status(format!(
+                            "ERROR: \"{}\" is not a valid seed, it must be a whole number.",
+                            text
+                        ))
+                    });
+                    return;
+                }
+            } else {
+                None
+            };
+            let seed = engine.borrow_mut().randomize_controls(None, seed);
+            this.children
+                .borrow()
+                .seed_box
+                .set_text(format!("{}", seed));
+        })
+    }
+
+    fn on_set_variation(self: &Rc<Self>, text: &str) {
+        let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
+        let mut engine = engine.borrow_mut();
+        let text = text.trim().trim_end_matches('%').trim();
+        let percent: f32 = text.parse().unwrap_or(f32::NAN);
+        if percent.is_finite() {
+            engine.set_randomize_variation(percent / 100.0);
+        }
+        // Also puts back the old value if the text could not be parsed.
+        let text = format_variation(engine.get_randomize_variation());
+        self.children.borrow().variation_box.set_text(text);
+    }
+
+    fn on_set_patch_seed(self: &Rc<Self>, text: &str) {
+        let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
+        let mut engine = engine.borrow_mut();
+        if let Ok(seed) = text.trim().parse() {
+            engine.set_patch_seed(seed);
+        } else {
+            let seed = engine.get_patch_seed();
+            drop(engine);
+            self.with_gui_state_mut(|state| {
+                state.add_error_status(format!(
+                    "ERROR: \"{}\" is not a valid seed, it must be a whole number.",
+                    text
+                ))
+            });
+            self.show_patch_seed(seed);
+        }
+    }
+
+    fn show_patch_seed(&self, seed: u32) {
+        self.children
+            .borrow()
+            .patch_seed_box
+            .set_text(format!("{}", seed));
+    }
+
+    fn is_over_deterministic_toggle(pos: Vec2D) -> bool {
+        pos.x >= META_X + LABEL_WIDTH
+            && pos.x < META_X + LABEL_WIDTH + DETERMINISTIC_WIDTH
+            && pos.y >= NOTES_Y
+            && pos.y < NOTES_Y + NAME_BOX_HEIGHT
+    }
+
+    fn on_toggle_deterministic(self: &Rc<Self>) -> MaybeMouseBehavior {
+        let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
+        OnClickBehavior::wrap(move || {
+            let mut engine = engine.borrow_mut();
+            let enabled = !engine.is_deterministic_mode();
+            engine.set_deterministic_mode(enabled);
+        })
+    }
+
+    fn on_search(self: &Rc<Self>, text: &str) {
+        let mut state = self.state.borrow_mut();
+        if state.search == text {
+            return;
+        }
+        state.search = text.to_owned();
+        state.scroll_offset = 0;
+        drop(state);
+        self.refresh();
+    }
+
+    fn on_toggle_sort(self: &Rc<Self>) -> MaybeMouseBehavior {
+        let this = Rc::clone(self);
+        OnClickBehavior::wrap(move || {
+            let mut state = this.state.borrow_mut();
+            state.sort_by_date = !state.sort_by_date;
+            let tooltip = if state.sort_by_date {
+                SORT_BY_NAME_TOOLTIP
+            } else {
+                SORT_BY_DATE_TOOLTIP
+            };
+            drop(state);
+            this.children.borrow().sort_button.set_tooltip(tooltip);
+            this.refresh();
+        })
+    }
+
+    fn on_open_folder(self: &Rc<Self>, row: Row) -> MaybeMouseBehavior {
+        let this = Rc::clone(self);
+        OnClickBehavior::wrap(move || {
+            let mut state = this.state.borrow_mut();
+            match row {
+                Row::Up => {
+                    state.folder.pop();
+                }
+                Row::Folder(name) => state.folder.push(name),
+                Row::Patch(..) => unreachable!(),
+            }
+            state.scroll_offset = 0;
+            drop(state);
+            this.refresh();
+        })
+    }
+
+    /// Rebuilds the rows of the patch list. While searching, every matching patch inside the
+    /// current folder is listed without its sub-folders.
+    fn refresh(&self) {
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+        let searching = !state.search.trim().is_empty();
+        let mut folders: Vec<String> = Vec::new();
+        let mut patches = Vec::new();
+        for (index, entry) in state.entries.iter().enumerate() {
+            let entry = entry.borrow();
+            let path = folder_of(&entry);
+            if path.len() < state.folder.len() || path[..state.folder.len()] != state.folder[..] {
+                continue;
+            }
+            if searching {
+                if matches_search(&entry, &state.search) {
+                    patches.push(index);
+                }
+            } else if path.len() == state.folder.len() {
+                patches.push(index);
+            } else if !folders.contains(&path[state.folder.len()]) {
+                folders.push(path[state.folder.len()].clone());
+            }
+        }
+        folders.sort();
+        let entries = &state.entries;
+        let by_name = |a: &usize, b: &usize| {
+            let a = entries[*a].borrow().borrow_name().to_lowercase();
+            let b = entries[*b].borrow().borrow_name().to_lowercase();
+            a.cmp(&b)
+        };
+        if state.sort_by_date {
+            // Newest first. Looking up the time can involve reading the file system, so it is only
+            // done once per patch.
+            let modified: HashMap<usize, u64> = patches
+                .iter()
+                .map(|index| (*index, entries[*index].borrow().get_modified_time()))
+                .collect();
+            patches.sort_by(|a, b| modified[b].cmp(&modified[a]).then_with(|| by_name(a, b)));
+        } else {
+            patches.sort_by(by_name);
+        }
+        state.rows.clear();
+        if !state.folder.is_empty() {
+            state.rows.push(Row::Up);
+        }
+        state.rows.extend(folders.into_iter().map(Row::Folder));
+        state.rows.extend(patches.into_iter().map(Row::Patch));
+        let max_offset = state.rows.len().saturating_sub(state.num_visible_entries);
+        state.scroll_offset = state.scroll_offset.min(max_offset);
+    }
+
     fn update_on_patch_change(&self, new_patch: &Rcrc<Patch>) {
         let new_patch_ref = new_patch.borrow();
         let children = self.children.borrow_mut();
         children
             .name_box
             .set_text(new_patch_ref.borrow_name().to_owned());
+        let metadata = new_patch_ref.borrow_metadata();
+        children.author_box.set_text(metadata.author.clone());
+        children.category_box.set_text(metadata.category.clone());
+        children.tags_box.set_text(metadata.tags.join(", "));
+        children
+            .description_box
+            .set_text(metadata.description.clone());
         let enable = new_patch_ref.is_writable();
         children.name_box.set_enabled(enable);
         children.save_button.set_enabled(enable);
+        for text_box in &[
+            &children.author_box,
+            &children.category_box,
+            &children.tags_box,
+            &children.description_box,
+        ] {
+            text_box.set_enabled(enable);
+        }
     }
 
-    fn on_delete_patch(self: &Rc<Self>, order_index: usize, index: usize) -> MaybeMouseBehavior {
+    fn on_delete_patch(self: &Rc<Self>, index: usize) -> MaybeMouseBehavior {
         let this = Rc::clone(self);
         OnClickBehavior::wrap(move || {
             let mut state = this.state.borrow_mut();
@@ -305,12 +769,6 @@ impl PatchBrowser {
                 eprintln!("TODO: Nice error, failed to delete patch: {}", err);
             } else {
                 state.entries.remove(index);
-                state.alphabetical_order.remove(order_index);
-                for other_index in &mut state.alphabetical_order {
-                    if *other_index > index {
-                        *other_index -= 1;
-                    }
-                }
                 if let Some(current_entry_index) = state.current_entry_index {
                     if current_entry_index == index {
                         state.current_entry_index = None;
@@ -320,6 +778,8 @@ impl PatchBrowser {
                         state.current_entry_index = Some(current_entry_index - 1);
                     }
                 }
+                drop(state);
+                this.refresh();
             }
         })
     }
@@ -340,36 +800,39 @@ impl WidgetImpl<Renderer, DropTarget> for PatchBrowser {
         mods: &MouseMods,
     ) -> MaybeMouseBehavior {
         ris!(self.get_mouse_behavior_children(mouse_pos, mods));
+        if Self::is_over_deterministic_toggle(mouse_pos) {
+            return self.on_toggle_deterministic();
+        }
         let state = self.state.borrow();
 
-        if mouse_pos.x <= HW && mouse_pos.y > NAME_BOX_HEIGHT + GRID_P {
-            let entry_index = (mouse_pos.y - NAME_BOX_HEIGHT - GRID_P) / ENTRY_HEIGHT;
-            if entry_index >= 0.0 && entry_index < state.entries.len() as f32 {
-                let order_index = entry_index as usize + state.scroll_offset;
-                let entry_index = state.alphabetical_order[order_index];
-                let patch = Rc::clone(&state.entries[entry_index]);
-                // Delete the patch. The threshold is deliberately shorter than the actual area the
-                // icon technically occupies to hopefully make misclicks less likely.
-                if mouse_pos.x > HW - grid(1) && patch.borrow().is_writable() {
-                    return self.on_delete_patch(order_index, entry_index);
-                } else {
-                    return self.on_load_patch(patch, entry_index);
-                }
+        if mouse_pos.x <= HW && mouse_pos.y > LIST_Y {
+            let row_index = ((mouse_pos.y - LIST_Y) / ENTRY_HEIGHT) as usize + state.scroll_offset;
+            let entry_index = match state.rows.get(row_index) {
+                Some(Row::Patch(index)) => *index,
+                Some(row) => return self.on_open_folder(row.clone()),
+                None => return None,
+            };
+            let patch = Rc::clone(&state.entries[entry_index]);
+            // Delete the patch. The threshold is deliberately shorter than the actual area the
+            // icon technically occupies to hopefully make misclicks less likely.
+            if mouse_pos.x > HW - grid(1) && patch.borrow().is_writable() {
+                return self.on_delete_patch(entry_index);
+            } else {
+                return self.on_load_patch(patch, entry_index);
             }
         }
         None
     }
 
     fn on_scroll_impl(self: &Rc<Self>, mouse_pos: Vec2D, delta: f32) -> Option<()> {
-        if mouse_pos.x <= HW && mouse_pos.y > NAME_BOX_HEIGHT + GRID_P {
+        if mouse_pos.x <= HW && mouse_pos.y > LIST_Y {
             let mut state = self.state.borrow_mut();
             if delta > 0.0 {
                 if state.scroll_offset > 0 {
                     state.scroll_offset -= 1;
                 }
             } else {
-                if state.scroll_offset + state.num_visible_entries < state.alphabetical_order.len()
-                {
+                if state.scroll_offset + state.num_visible_entries < state.rows.len() {
                     state.scroll_offset += 1;
                 }
             }
@@ -381,14 +844,29 @@ impl WidgetImpl<Renderer, DropTarget> for PatchBrowser {
 
     fn on_hover_impl(self: &Rc<Self>, pos: Vec2D) -> Option<()> {
         ris!(self.on_hover_children(pos));
-        if pos.x <= HW && pos.y > NAME_BOX_HEIGHT + GRID_P {
+        if pos.x <= HW && pos.y > LIST_Y {
             self.with_gui_state_mut(|state| {
                 state.set_tooltip(Tooltip {
-                    text: "Click a patch to load it or click the trash icon to delete it"
-                        .to_owned(),
+                    text: concat!(
+                        "Click a patch to load it, click a folder to open it or click the trash ",
+                        "icon to delete a patch"
+                    )
+                    .to_owned(),
                     interaction: vec![InteractionHint::LeftClick, InteractionHint::Scroll],
                 });
             })
+        } else if Self::is_over_deterministic_toggle(pos) {
+            self.with_gui_state_mut(|state| {
+                state.set_tooltip(Tooltip {
+                    text: concat!(
+                        "Repeatable notes: while on, the randomness in each note only depends on ",
+                        "the seed next to this and how many notes were played since the patch ",
+                        "was loaded"
+                    )
+                    .to_owned(),
+                    interaction: vec![InteractionHint::LeftClick],
+                });
+            })
         }
         Some(())
     }
@@ -399,55 +877,144 @@ impl WidgetImpl<Renderer, DropTarget> for PatchBrowser {
 
         g.set_color(&COLOR_BG2);
         g.draw_rect(0, TAB_BODY_SIZE);
+        // Drawn before the children since the recovery buttons are on top of it.
+        g.set_color(&COLOR_FG1);
+        let labels = [
+            "Author",
+            "Category",
+            "Tags",
+            "Description",
+            "Morph",
+            "Randomize",
+            "Variation",
+            "Notes",
+        ];
+        for (row, label) in labels.iter().enumerate() {
+            let y = (CG + GP) * row as f32;
+            g.draw_text(FONT_SIZE, (META_X, y), (LABEL_WIDTH, CG), (-1, 0), 1, label);
+        }
+        let y = INFO_Y;
+        g.set_color(&COLOR_BG0);
+        g.draw_rounded_rect((META_X, y), (HW, TAB_BODY_HEIGHT - y - GP), CORNER_SIZE);
+        let (info, recovered_name, deterministic) = self.with_gui_state(|state| {
+            let engine = state.engine.borrow();
+            let patch = engine.borrow_current_patch().borrow();
+            let recovered_name = engine
+                .borrow_recovered_patch()
+                .map(|patch| patch.borrow_name().to_owned());
+            (
+                describe_metadata(patch.borrow_metadata()),
+                recovered_name,
+                engine.is_deterministic_mode(),
+            )
+        });
+        g.set_color(if deterministic {
+            &COLOR_EDITABLE
+        } else {
+            &COLOR_BG1
+        });
+        let pos = (META_X + LABEL_WIDTH, NOTES_Y);
+        let size = (DETERMINISTIC_WIDTH, NAME_BOX_HEIGHT);
+        g.draw_rounded_rect(pos, size, CORNER_SIZE);
+        g.set_color(&COLOR_FG1);
+        let text = format!("Repeatable: {}", if deterministic { "on" } else { "off" });
+        g.draw_text(FONT_SIZE, pos, size, (0, 0), 1, &text);
+        if let Some(name) = recovered_name {
+            g.set_color(&COLOR_WARNING);
+            g.draw_rounded_rect((META_X, RECOVERY_Y), (HW, RECOVERY_HEIGHT), CORNER_SIZE);
+            // Make room for the buttons.
+            let text_width = HW - (CG + GP) * 2.0 - GP * 2.0;
+            let lines = [
+                format!("Unsaved changes to \"{}\"", name),
+                "were recovered after a crash.".to_owned(),
+            ];
+            g.set_color(&COLOR_BG0);
+            for (index, line) in lines.iter().enumerate() {
+                let pos = (META_X + GP, RECOVERY_Y + GP + ENTRY_HEIGHT * index as f32);
+                g.draw_text(FONT_SIZE, pos, (text_width, ENTRY_HEIGHT), (-1, 0), 1, line);
+            }
+        }
+        g.set_color(&COLOR_FG1);
+        for (index, line) in info.iter().enumerate() {
+            let pos = (META_X + GP, y + ENTRY_HEIGHT * index as f32);
+            g.draw_text(
+                FONT_SIZE,
+                pos,
+                (HW - GP * 2.0, ENTRY_HEIGHT),
+                (-1, 0),
+                1,
+                line,
+            );
+        }
         self.draw_children(g);
 
-        let y = CG + GP;
+        if state.search.is_empty() {
+            g.set_color(&COLOR_FG1);
+            g.set_alpha(0.5);
+            let hint = "Search (try tag:bass or lib:factory)";
+            let pos = (GP * 2.0, CG + GP);
+            g.draw_text(FONT_SIZE, pos, (HW, CG), (-1, 0), 1, hint);
+            g.set_alpha(1.0);
+        }
+        if self.children.borrow().seed_box.get_text().is_empty() {
+            g.set_color(&COLOR_FG1);
+            g.set_alpha(0.5);
+            let pos = (META_X + LABEL_WIDTH + (CG + GP) * 2.0 + GP, RANDOMIZE_Y);
+            g.draw_text(FONT_SIZE, pos, (HW, CG), (-1, 0), 1, "Seed");
+            g.set_alpha(1.0);
+        }
+
+        let y = LIST_Y;
         g.set_color(&COLOR_BG0);
         let panel_height = TAB_BODY_HEIGHT - y - GP;
         g.draw_rounded_rect((GP, y), (HW, panel_height), CORNER_SIZE);
         g.set_color(&COLOR_FG1);
         let offset = state.scroll_offset;
-        let num_entries = state.alphabetical_order.len();
+        let num_entries = state.rows.len();
         let range = offset..(offset + state.num_visible_entries).min(num_entries);
+        const ICON_SIZE: f32 = grid(1);
+        const ICON_PADDING: f32 = (ENTRY_HEIGHT - ICON_SIZE) / 2.0;
+        let width = if num_entries > state.num_visible_entries {
+            HW - GP * 3.0 // Make room for scrollbar.
+        } else {
+            HW - GP * 2.0
+        };
         for index in range {
-            let entry_index = state.alphabetical_order[index];
-            let entry = &state.entries[entry_index];
             let x = GP;
             let y = y + ENTRY_HEIGHT * (index - offset) as f32;
-            let item_index = state.alphabetical_order[index];
-            if Some(item_index) == state.current_entry_index {
+            let text_pos = (x + GP, y);
+            let text_size = (width, ENTRY_HEIGHT);
+            // Don't ask me why it just works
+            let icon_pos = (x + width - ICON_SIZE * 0.5, y + ICON_PADDING);
+            let entry_index = match &state.rows[index] {
+                Row::Up => {
+                    g.draw_white_icon(state.up_icon, (x + GP, y + ICON_PADDING), ICON_SIZE);
+                    let text_pos = (x + GP * 2.0 + ICON_SIZE, y);
+                    let path = state.folder.join("/");
+                    g.draw_text(FONT_SIZE, text_pos, text_size, (-1, 0), 1, &path);
+                    continue;
+                }
+                Row::Folder(name) => {
+                    g.draw_text(FONT_SIZE, text_pos, text_size, (-1, 0), 1, name);
+                    g.draw_white_icon(state.folder_icon, icon_pos, ICON_SIZE);
+                    continue;
+                }
+                Row::Patch(entry_index) => *entry_index,
+            };
+            if Some(entry_index) == state.current_entry_index {
                 g.set_color(&COLOR_BG1);
                 g.draw_rounded_rect((x, y), (HW, ENTRY_HEIGHT), CORNER_SIZE);
                 g.set_color(&COLOR_FG1);
             }
-            let entry = entry.borrow();
+            let entry = state.entries[entry_index].borrow();
             let name = entry.borrow_name();
-            let width = if num_entries > state.num_visible_entries {
-                HW - GP * 3.0 // Make room for scrollbar.
-            } else {
-                HW - GP * 2.0
-            };
-            g.draw_text(
-                FONT_SIZE,
-                (x + GP, y),
-                (width, ENTRY_HEIGHT),
-                (-1, 0),
-                1,
-                name,
-            );
+            g.draw_text(FONT_SIZE, text_pos, text_size, (-1, 0), 1, name);
             if entry.is_writable() {
-                const ICON_SIZE: f32 = grid(1);
-                const ICON_PADDING: f32 = (ENTRY_HEIGHT - ICON_SIZE) / 2.0;
-                g.draw_white_icon(
-                    state.delete_icon,
-                    // Don't ask me why it just works
-                    (x + width - ICON_SIZE * 0.5, y + ICON_PADDING),
-                    ICON_SIZE,
-                );
+                g.draw_white_icon(state.delete_icon, icon_pos, ICON_SIZE);
             } else {
                 g.set_alpha(0.5);
-                let t = "[Factory]";
-                g.draw_text(FONT_SIZE, (x + GP, y), (width, ENTRY_HEIGHT), (1, 0), 1, t);
+                let t = format!("[{}]", entry.borrow_lib_name());
+                g.draw_text(FONT_SIZE, text_pos, text_size, (1, 0), 1, &t);
                 g.set_alpha(1.0);
             }
         }
@@ -465,4 +1032,72 @@ impl WidgetImpl<Renderer, DropTarget> for PatchBrowser {
     }
 }
 
+/// The library a patch is from followed by the folders it is in.
+fn folder_of(patch: &Patch) -> Vec<String> {
+    let mut folder = vec![patch.borrow_lib_name().to_owned()];
+    folder.extend(patch.borrow_folders().iter().cloned());
+    folder
+}
+
+/// Every word of `search` has to match the patch. Words like `tag:bass`, `category:bass` and
+/// `lib:factory` only match that part of the patch, other words can appear anywhere in its name,
+/// author, category, description or tags. Case is ignored.
+fn matches_search(patch: &Patch, search: &str) -> bool {
+    let metadata = patch.borrow_metadata();
+    let search = search.to_lowercase();
+    search.split_whitespace().all(|word| {
+        if let Some(tag) = word.strip_prefix("tag:") {
+            metadata
+                .tags
+                .iter()
+                .any(|other| other.to_lowercase() == tag)
+        } else if let Some(category) = word.strip_prefix("category:") {
+            metadata.category.to_lowercase() == category
+        } else if let Some(lib_name) = word.strip_prefix("lib:") {
+            patch.borrow_lib_name().to_lowercase().starts_with(lib_name)
+        } else {
+            let fields = [
+                patch.borrow_name(),
+                &metadata.author,
+                &metadata.category,
+                &metadata.description,
+            ];
+            fields
+                .iter()
+                .copied()
+                .chain(metadata.tags.iter().map(String::as_str))
+                .any(|field| field.to_lowercase().contains(word))
+        }
+    })
+}
+
+/// Shows how far randomizing can move values as a percentage of the range of each control.
+fn format_variation(variation: f32) -> String {
+    format!("{:.0}%", variation * 100.0)
+}
+
+/// Lines describing the parts of `metadata` which cannot be edited.
+fn describe_metadata(metadata: &PatchMetadata) -> Vec<String> {
+    let mut lines = Vec::new();
+    if metadata.created != 0 {
+        lines.push(format!(
+            "Created {}",
+            patch_metadata::format_date(metadata.created)
+        ));
+    }
+    if metadata.modified != 0 {
+        lines.push(format!(
+            "Modified {}",
+            patch_metadata::format_date(metadata.modified)
+        ));
+    }
+    if let Some(version) = metadata.engine_version {
+        lines.push(format!("Saved with Audiobench {}", version));
+    }
+    for (lib_name, version) in &metadata.library_versions {
+        lines.push(format!("Uses {} {}", lib_name, version));
+    }
+    lines
+}
+
 impl GuiTab for Rc<PatchBrowser> {}
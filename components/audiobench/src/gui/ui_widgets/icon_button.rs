@@ -44,6 +44,10 @@ impl IconButton {
     pub fn set_enabled(self: &Rc<Self>, enabled: bool) {
         self.state.borrow_mut().enabled = enabled;
     }
+
+    pub fn set_tooltip(self: &Rc<Self>, tooltip: impl ToString) {
+        self.state.borrow_mut().tooltip = tooltip.to_string();
+    }
 }
 
 impl WidgetImpl<Renderer, DropTarget> for IconButton {
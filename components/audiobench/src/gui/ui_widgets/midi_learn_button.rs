@@ -0,0 +1,138 @@
+use crate::{
+    engine::controls::Control,
+    gui::{constants::*, InteractionHint, Tooltip},
+    scui_config::{DropTarget, MaybeMouseBehavior, Renderer},
+};
+use scui::{MouseMods, OnClickBehavior, Vec2D, WidgetImpl};
+use shared_util::prelude::*;
+
+// Shows which MIDI controller a control is bound to. Clicking it makes the next MIDI controller
+// that is moved become bound to the control.
+scui::widget! {
+    pub MidiLearnButton
+    State {
+        pos: Vec2D,
+        control: Rcrc<dyn Control>,
+    }
+}
+
+impl MidiLearnButton {
+    pub fn new(
+        parent: &impl MidiLearnButtonParent,
+        pos: impl Into<Vec2D>,
+        control: Rcrc<dyn Control>,
+    ) -> Rc<Self> {
+        let state = MidiLearnButtonState {
+            pos: pos.into(),
+            control,
+        };
+        Rc::new(Self::create(parent, state))
+    }
+
+    /// Returns the number of the controller the control is bound to, if any.
+    fn get_bound_controller(self: &Rc<Self>) -> Option<usize> {
+        let control = &self.state.borrow().control;
+        self.with_gui_state(|state| {
+            let engine = state.engine.borrow();
+            let index = engine.find_midi_binding_for(control)?;
+            let graph = engine.borrow_module_graph_ref().borrow();
+            Some(graph.borrow_midi_bindings()[index].controller)
+        })
+    }
+
+    fn is_learning(self: &Rc<Self>) -> bool {
+        let control = &self.state.borrow().control;
+        self.with_gui_state(|state| state.engine.borrow().is_learning_midi_for(control))
+    }
+}
+
+impl WidgetImpl<Renderer, DropTarget> for MidiLearnButton {
+    fn get_pos_impl(self: &Rc<Self>) -> Vec2D {
+        self.state.borrow().pos
+    }
+
+    fn get_size_impl(self: &Rc<Self>) -> Vec2D {
+        grid(1).into()
+    }
+
+    fn get_mouse_behavior_impl(
+        self: &Rc<Self>,
+        _pos: Vec2D,
+        mods: &MouseMods,
+    ) -> MaybeMouseBehavior {
+        let control = Rc::clone(&self.state.borrow().control);
+        let engine = self.with_gui_state(|state| Rc::clone(&state.engine));
+        let this = Rc::clone(self);
+        if mods.right_click {
+            OnClickBehavior::wrap(move || {
+                let mut engine = engine.borrow_mut();
+                engine.cancel_midi_learn();
+                if let Some(index) = engine.find_midi_binding_for(&control) {
+                    engine.remove_midi_binding(index);
+                    drop(engine);
+                    this.with_gui_state_mut(|state| {
+                        state.add_success_status("MIDI binding removed.".to_owned())
+                    });
+                }
+            })
+        } else {
+            let learning = self.is_learning();
+            OnClickBehavior::wrap(move || {
+                let mut engine = engine.borrow_mut();
+                if learning {
+                    engine.cancel_midi_learn();
+                } else {
+                    engine.start_midi_learn(&control);
+                    drop(engine);
+                    this.with_gui_state_mut(|state| {
+                        state.add_success_status(
+                            "Move a controller on your MIDI device to bind it.".to_owned(),
+                        )
+                    });
+                }
+            })
+        }
+    }
+
+    fn on_hover_impl(self: &Rc<Self>, _pos: Vec2D) -> Option<()> {
+        let tooltip = if let Some(controller) = self.get_bound_controller() {
+            Tooltip {
+                text: format!(
+                    concat!(
+                        "Bound to MIDI controller #{}. Click to bind a different controller or ",
+                        "right click to remove the binding"
+                    ),
+                    controller
+                ),
+                interaction: vec![InteractionHint::LeftClick, InteractionHint::RightClick],
+            }
+        } else {
+            Tooltip {
+                text: "MIDI learn: click, then move a controller on your MIDI device".to_owned(),
+                interaction: vec![InteractionHint::LeftClick],
+            }
+        };
+        self.with_gui_state_mut(|state| state.set_tooltip(tooltip));
+        Some(())
+    }
+
+    fn draw_impl(self: &Rc<Self>, g: &mut Renderer) {
+        const S: f32 = grid(1);
+        if self.is_learning() {
+            g.set_color(&COLOR_WARNING);
+            g.draw_rounded_rect(0, S, CORNER_SIZE);
+            g.set_color(&COLOR_FG1);
+            g.draw_text(FONT_SIZE, 0, S, (0, 0), 1, "...");
+        } else if let Some(controller) = self.get_bound_controller() {
+            g.set_color(&COLOR_BG0);
+            g.draw_rounded_rect(0, S, CORNER_SIZE);
+            g.set_color(&COLOR_FG1);
+            g.draw_text(FONT_SIZE, 0, S, (0, 0), 1, &format!("{}", controller));
+        } else {
+            g.set_color(&COLOR_BG0);
+            g.draw_rounded_rect(0, S, CORNER_SIZE);
+            g.set_color(&COLOR_FG0);
+            g.draw_text(FONT_SIZE, 0, S, (0, 0), 1, "cc");
+        }
+    }
+}
@@ -1,5 +1,7 @@
 mod icon_button;
+mod midi_learn_button;
 mod text_box;
 
 pub use icon_button::*;
+pub use midi_learn_button::*;
 pub use text_box::*;
@@ -45,6 +45,11 @@ impl TextBox {
         self.state.borrow().field.borrow().text.clone()
     }
 
+    /// Unlike the defocus action given to `new`, `action` is called as the text is being typed.
+    pub fn set_change_action(&self, action: Box<dyn Fn(&str)>) {
+        self.state.borrow().field.borrow_mut().set_on_change(action);
+    }
+
     pub fn set_enabled(&self, enabled: bool) {
         self.state.borrow_mut().enabled = enabled;
     }
@@ -8,9 +8,18 @@ use engine::{AudioThreadEngine, UiThreadEngine};
 use gui::graphics::GrahpicsWrapper;
 pub use gui::graphics::GraphicsFunctions;
 use gui::Gui;
-use registry::Registry;
+pub use registry::{
+    lint::lint_library,
+    scaffold::{create_module, ModuleSpec},
+};
+use registry::{theme::ThemeColor, Registry};
 use shared_util::prelude::*;
 
+/// ASCII SUB, sent by the frontend for Ctrl+Z or Cmd+Z.
+const UNDO_KEY: char = '\x1A';
+/// ASCII EM, sent by the frontend for Ctrl+Y or Ctrl+Shift+Z.
+const REDO_KEY: char = '\x19';
+
 pub struct ErrorDrawer {
     error: String,
     pub graphics_fns: Rc<GraphicsFunctions>,
@@ -34,10 +43,15 @@ impl ErrorDrawer {
     }
 
     pub fn draw(&self, data: *mut i8, icon_store: *mut i8) {
-        let mut g = GrahpicsWrapper::new(Rc::clone(&self.graphics_fns), data, icon_store);
-        g.set_color(&(0, 0, 0));
+        let mut g = GrahpicsWrapper::new(
+            Rc::clone(&self.graphics_fns),
+            data,
+            icon_store,
+            Default::default(),
+        );
+        g.set_color(&ThemeColor::Fixed((0, 0, 0)));
         g.draw_rect((0, 0), (640, 480));
-        g.set_color(&(255, 255, 255));
+        g.set_color(&ThemeColor::Fixed((255, 255, 255)));
         g.draw_console_text((640, 480), &self.error);
     }
 }
@@ -67,6 +81,11 @@ impl Instance {
     }
 
     pub fn ui_deserialize_patch(&mut self, serialized: &[u8]) -> Result<(), String> {
+        let (recovery_id, serialized) = registry::recovery::split_state(serialized);
+        // Done first so that a crash is still recovered from if the patch fails to load.
+        if let Some(id) = recovery_id {
+            self.ui_engine.borrow_mut().restore_recovery_id(id);
+        }
         let registry = self.registry.borrow();
         let patch = match registry::save_data::Patch::load_readable(
             "External Preset".to_owned(),
@@ -84,9 +103,10 @@ impl Instance {
         };
         drop(registry);
         let patch = rcrc(patch);
-        if let Err(..) = self.ui_engine.borrow_mut().load_patch(patch) {
+        if let Err(message) = self.ui_engine.borrow_mut().load_patch(patch) {
             return Err(format!(
-                "ERROR: Failed to load the patch you were working on.",
+                "ERROR: Failed to load the patch you were working on, caused by:\n{}",
+                message
             ));
         }
         if let Some(gui) = &mut self.gui {
@@ -109,6 +129,30 @@ impl Instance {
         }
     }
 
+    pub fn ui_mouse_up(&mut self) {
+        self.ui_with_gui_mut(|gui| gui.on_mouse_up());
+        // Drags are only recorded once they are finished so that they can be undone in one step.
+        self.ui_engine.borrow_mut().record_edit();
+    }
+
+    pub fn ui_scroll(&mut self, delta: f32) {
+        self.ui_with_gui_mut(|gui| gui.on_scroll(delta));
+        self.ui_engine.borrow_mut().record_edit();
+    }
+
+    /// The frontend sends the undo and redo shortcuts as the characters Ctrl+Z and Ctrl+Y produce
+    /// in a terminal. They work even while a text field is being edited.
+    pub fn ui_key_press(&mut self, key: char) {
+        match key {
+            UNDO_KEY => self.ui_engine.borrow_mut().undo(),
+            REDO_KEY => self.ui_engine.borrow_mut().redo(),
+            _ => {
+                self.ui_with_gui_mut(|gui| gui.on_key_press(key));
+                self.ui_engine.borrow_mut().record_edit();
+            }
+        }
+    }
+
     pub fn ui_create_ui(&mut self) {
         if self.gui.is_some() {
             // This is an indicator of a bug in the frontend, but is not in itself a critical error,
@@ -125,7 +169,9 @@ impl Instance {
 
     pub fn ui_draw_ui(&mut self, data: *mut i8, icon_store: *mut i8) {
         if let Some(gui) = &mut self.gui {
-            let mut g = GrahpicsWrapper::new(Rc::clone(&self.graphics_fns), data, icon_store);
+            let theme = self.registry.borrow().get_active_theme();
+            let mut g =
+                GrahpicsWrapper::new(Rc::clone(&self.graphics_fns), data, icon_store, theme);
             let mut ui_engine = self.ui_engine.borrow_mut();
             ui_engine.display_new_feedback_data();
             drop(ui_engine);
@@ -2,20 +2,25 @@ use crate::{
     config::*,
     registry::{
         library_preload::{self, PreloadedLibrary, ZippedLibraryContentProvider},
+        library_watcher::LibraryWatcher,
+        migration::{self, LibraryMigrations, OldControl},
         module_template::ModuleTemplate,
+        recovery,
         save_data::Patch,
+        settings::Settings,
+        theme::Theme,
         update_check::{self, UpdateInfo},
         yaml,
     },
 };
 use julia_helper::FileClip;
 use rand::RngCore;
-use shared_util::prelude::*;
+use shared_util::{prelude::*, Version};
 use std::{
     collections::{HashMap, HashSet},
     fs,
-    path::PathBuf,
-    sync::mpsc::{self, Receiver, TryRecvError},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
 };
 
 pub use super::library_preload::LibraryInfo;
@@ -27,18 +32,51 @@ pub struct Registry {
 
     general_scripts_by_library: HashMap<String, Vec<FileClip>>,
     module_scripts_by_library: HashMap<String, Vec<(String, FileClip)>>,
+    migrations_by_library: HashMap<String, LibraryMigrations>,
 
     icon_indexes: HashMap<String, usize>,
     icons: Vec<Vec<u8>>,
 
+    // The default theme always comes first.
+    themes: Vec<Rc<Theme>>,
+    active_theme: Rc<Theme>,
+
     unloaded_patches: Vec<(String, Option<PathBuf>, Vec<u8>)>,
     patches: Vec<Rcrc<Patch>>,
     patch_paths: HashMap<String, usize>,
 
     library_path: PathBuf,
     library_info: HashMap<String, LibraryInfo>,
+    // Libraries in the order they were loaded in, dependencies always come before their dependents.
+    library_order: Vec<String>,
+    // Libraries installed or uninstalled since Audiobench was started. These changes only take
+    // effect after restarting.
+    pending_library_changes: HashMap<String, PendingLibraryChange>,
+    // Only exists in development mode.
+    library_watcher: Option<LibraryWatcher>,
+    settings: Settings,
     checked_updates: HashMap<String, Option<UpdateInfo>>,
     update_check_stream: Receiver<(String, Option<UpdateInfo>)>,
+    // Names of libraries whose updates are being downloaded.
+    downloading_updates: HashSet<String>,
+    download_sender: Sender<(String, Result<Vec<u8>, String>)>,
+    download_stream: Receiver<(String, Result<Vec<u8>, String>)>,
+}
+
+/// A change made to the installed libraries which will take effect after Audiobench restarts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PendingLibraryChange {
+    Installed,
+    Uninstalled,
+}
+
+/// Puts back an entry that was removed from a map, or removes the entry if there was none.
+fn restore_entry<T>(map: &mut HashMap<String, T>, key: &str, old: Option<T>) {
+    if let Some(old) = old {
+        map.insert(key.to_owned(), old);
+    } else {
+        map.remove(key);
+    }
 }
 
 enum DelayedError {
@@ -135,13 +173,75 @@ impl Registry {
         Ok(())
     }
 
+    fn load_migrations_resource(
+        &mut self,
+        library_name: &str,
+        file_name: &str,
+        buffer: Vec<u8>,
+    ) -> Result<(), String> {
+        let buffer_as_text = String::from_utf8(buffer).map_err(|e| {
+            format!(
+                "ERROR: The file {} is not a valid UTF-8 text document, caused by:\nERROR: {}",
+                file_name, e
+            )
+        })?;
+        let yaml = yaml::parse_yaml(&buffer_as_text, file_name)?;
+        let migrations = migration::parse_migrations(&yaml).map_err(|err| {
+            format!(
+                "ERROR: Failed to load migrations from {}, caused by:\n{}",
+                file_name, err
+            )
+        })?;
+        self.migrations_by_library
+            .insert(library_name.to_owned(), migrations);
+        Ok(())
+    }
+
+    fn load_theme_resource(
+        &mut self,
+        lib_name: &str,
+        file_name: &str,
+        full_name: &str,
+        buffer: Vec<u8>,
+    ) -> Result<(), String> {
+        let buffer_as_text = String::from_utf8(buffer).map_err(|e| {
+            format!(
+                "ERROR: The file {} is not a valid UTF-8 text document, caused by:\nERROR: {}",
+                full_name, e
+            )
+        })?;
+        let yaml = yaml::parse_yaml(&buffer_as_text, full_name)?;
+        // Themes without a name are named after the library, or after the file if the library
+        // contains more than one.
+        let default_name = if file_name == "theme.yaml" {
+            lib_name.to_owned()
+        } else {
+            let file_name = file_name.rsplit('/').next().unwrap_or(file_name);
+            file_name.trim_end_matches(".theme.yaml").to_owned()
+        };
+        let theme = Theme::from_yaml(&yaml, lib_name, &default_name).map_err(|err| {
+            format!(
+                "ERROR: Failed to load theme from {}, caused by:\n{}",
+                full_name, err
+            )
+        })?;
+        if self.themes.iter().any(|other| other.name == theme.name) {
+            return Err(format!(
+                "ERROR: {} defines a theme named {}, but a theme with that name already exists.",
+                full_name, theme.name
+            ));
+        }
+        self.themes.push(Rc::new(theme));
+        Ok(())
+    }
+
     fn load_patch(
         &mut self,
         name: &str,
         full_path: Option<PathBuf>,
         buffer: Vec<u8>,
     ) -> Result<(), String> {
-        let patch = if let Some(full_path) = full_path {
+        let mut patch = if let Some(full_path) = full_path {
             Patch::load_writable(full_path, &buffer[..], &self)
         } else {
             Patch::load_readable(name.to_owned(), &buffer[..], &self)
@@ -152,6 +252,17 @@ impl Registry {
                 name, err
             )
         })?;
+        let (lib_name, file_name) = name.split_at(name.find(':').unwrap_or(0));
+        let mut folders: Vec<String> = file_name[1..]
+            .split(&['/', '\\'][..])
+            .map(str::to_owned)
+            .collect();
+        // The last part is the name of the file itself.
+        folders.pop();
+        if folders.first().map(|folder| folder == "patches") == Some(true) {
+            folders.remove(0);
+        }
+        patch.set_location(lib_name.to_owned(), folders);
         self.patch_paths.insert(name.to_owned(), self.patches.len());
         self.patches.push(rcrc(patch));
         Ok(())
@@ -174,8 +285,14 @@ impl Registry {
         if file_name.ends_with(".icon.svg") {
             let file_name = Self::strip_path_and_extension(file_name, ".icon.svg");
             let icon_id = format!("{}:{}", lib_name, file_name);
-            self.icon_indexes.insert(icon_id, self.icons.len());
-            self.icons.push(buffer);
+            // Icons that already exist are being reloaded, they keep their index so that
+            // everything referring to them stays valid.
+            if let Some(&index) = self.icon_indexes.get(&icon_id) {
+                self.icons[index] = buffer;
+            } else {
+                self.icon_indexes.insert(icon_id, self.icons.len());
+                self.icons.push(buffer);
+            }
         } else if file_name.ends_with(".module.yaml") {
             let module_id = Self::strip_path_and_extension(file_name, ".module.yaml");
             return self.load_module_resource(
@@ -190,8 +307,14 @@ impl Registry {
             self.load_module_script_resource(lib_name, &full_name, buffer)?;
         } else if file_name.ends_with(".abpatch") {
             self.unloaded_patches.push((full_name, full_path, buffer));
+        } else if file_name == "theme.yaml" || file_name.ends_with(".theme.yaml") {
+            self.load_theme_resource(lib_name, file_name, &full_name, buffer)?;
+        } else if file_name.ends_with(recovery::RECOVERY_EXTENSION) {
+            // Autosaves of unsaved patches, see `recovery`.
         } else if file_name.ends_with(".md") {
             // Ignore, probably just readme / license type stuff.
+        } else if file_name == "migrations.yaml" {
+            self.load_migrations_resource(lib_name, &full_name, buffer)?;
         } else if file_name == "library_info.yaml" {
             // Handled in library preload phase.
         } else {
@@ -203,7 +326,13 @@ impl Registry {
         Ok(None)
     }
 
-    fn load_library(&mut self, mut library: PreloadedLibrary) -> Result<LibraryInfo, String> {
+    /// Loads every file in the library, continuing past files which fail to load so that all
+    /// problems can be reported at once.
+    fn load_library_collecting_errors(
+        &mut self,
+        mut library: PreloadedLibrary,
+    ) -> (LibraryInfo, Vec<String>) {
+        let mut errors = Vec::new();
         // Add entries to script hash table.
         self.general_scripts_by_library
             .insert(library.internal_name.clone(), Vec::new());
@@ -214,30 +343,44 @@ impl Registry {
             let file_name = library.content.get_file_name(index);
             if file_name.ends_with(".icon.svg") {
                 let full_path = library.content.get_full_path(index);
-                let contents = library.content.read_file_contents(index)?;
-                let delayed_error =
-                    self.load_resource(&library.internal_name, &file_name, full_path, contents)?;
-                assert!(
-                    delayed_error.is_none(),
-                    "Icons should not cause delayed errors."
-                );
+                let result = library
+                    .content
+                    .read_file_contents(index)
+                    .and_then(|contents| {
+                        self.load_resource(&library.internal_name, &file_name, full_path, contents)
+                    });
+                match result {
+                    Ok(delayed_error) => assert!(
+                        delayed_error.is_none(),
+                        "Icons should not cause delayed errors."
+                    ),
+                    Err(err) => errors.push(err),
+                }
             }
         }
-        let mut delayed_error = None;
+        let mut duplicate_save_ids = Vec::new();
         for index in 0..library.content.get_num_files() {
             let file_name = library.content.get_file_name(index);
             if !file_name.ends_with(".icon.svg") {
                 let full_path = library.content.get_full_path(index);
-                let contents = library.content.read_file_contents(index)?;
-                delayed_error = delayed_error.or(self.load_resource(
-                    &library.internal_name,
-                    &file_name,
-                    full_path,
-                    contents,
-                )?);
+                let result = library
+                    .content
+                    .read_file_contents(index)
+                    .and_then(|contents| {
+                        self.load_resource(&library.internal_name, &file_name, full_path, contents)
+                    });
+                match result {
+                    Ok(Some(DelayedError::DuplicateSaveId(dupl_id))) => {
+                        if !duplicate_save_ids.contains(&dupl_id) {
+                            duplicate_save_ids.push(dupl_id);
+                        }
+                    }
+                    Ok(None) => (),
+                    Err(err) => errors.push(err),
+                }
             }
         }
-        if let Some(DelayedError::DuplicateSaveId(dupl_id)) = delayed_error {
+        if !duplicate_save_ids.is_empty() {
             let mut save_ids = HashSet::new();
             for (this_lib_name, save_id) in self.modules_by_serialized_id.keys() {
                 if this_lib_name == &library.internal_name {
@@ -248,12 +391,27 @@ impl Registry {
             while save_ids.contains(&next_available_id) {
                 next_available_id += 1;
             }
-            return Err(format!(
-                "ERROR: Multiple modules have {} as their save id. The lowest available ID is {}.",
-                dupl_id, next_available_id
-            ));
+            duplicate_save_ids.sort_unstable();
+            for dupl_id in duplicate_save_ids {
+                errors.push(format!(
+                    concat!(
+                        "ERROR: Multiple modules have {} as their save id. The lowest available ",
+                        "ID is {}."
+                    ),
+                    dupl_id, next_available_id
+                ));
+            }
+        }
+        (library.info, errors)
+    }
+
+    fn load_library(&mut self, library: PreloadedLibrary) -> Result<LibraryInfo, String> {
+        let (info, errors) = self.load_library_collecting_errors(library);
+        if errors.is_empty() {
+            Ok(info)
+        } else {
+            Err(errors.join("\n"))
         }
-        Ok(library.info)
     }
 
     fn create_and_update_user_library(&self) -> Result<(), String> {
@@ -279,32 +437,25 @@ impl Registry {
         Ok(())
     }
 
-    fn initialize(&mut self) -> Result<(), String> {
-        let factory_library = {
-            let raw = std::include_bytes!(concat!(env!("OUT_DIR"), "/Factory.ablib"));
-            let reader = std::io::Cursor::new(raw as &[u8]);
-            let content = ZippedLibraryContentProvider::new(reader).map_err(|err| {
-                format!("ERROR: Failed to open Factory library, caused by:\n{}", err)
-            })?;
-            library_preload::preload_library("Factory".to_owned(), Box::new(content)).map_err(
-                |err| {
-                    format!(
-                        "ERROR: Failed to preload Factory library, caused by:\n{}",
-                        err
-                    )
-                },
-            )?
-        };
-        let factory_lib_info = self
-            .load_library(factory_library)
-            .map_err(|e| format!("ERROR: Failed to load Factory library, caused by:\n{}", e))?;
-        self.library_info
-            .insert("Factory".to_owned(), factory_lib_info);
+    fn preload_factory_library() -> Result<PreloadedLibrary, String> {
+        let raw = std::include_bytes!(concat!(env!("OUT_DIR"), "/Factory.ablib"));
+        let reader = std::io::Cursor::new(raw as &[u8]);
+        let content = ZippedLibraryContentProvider::new(reader)
+            .map_err(|err| format!("ERROR: Failed to open Factory library, caused by:\n{}", err))?;
+        library_preload::preload_library("Factory".to_owned(), Box::new(content)).map_err(|err| {
+            format!(
+                "ERROR: Failed to preload Factory library, caused by:\n{}",
+                err
+            )
+        })
+    }
 
+    fn initialize(&mut self) -> Result<(), String> {
+        let factory_library = Self::preload_factory_library()?;
         self.create_and_update_user_library()?;
 
-        let mut loaded_libraries = HashSet::new();
-        loaded_libraries.insert("Factory".to_owned());
+        // Factory always comes first so that it is loaded first when nothing depends on anything.
+        let mut libraries = vec![factory_library];
         for entry in fs::read_dir(&self.library_path).map_err(|err| {
             format!(
                 "ERROR: Failed to read libraries from {}, caused by:\n{}",
@@ -325,6 +476,13 @@ impl Registry {
                         err
                     )
                 })?;
+            libraries.push(library);
+        }
+        // Sort the rest by name so that the load order does not depend on the file system.
+        libraries[1..].sort_by(|a, b| a.internal_name.cmp(&b.internal_name));
+        let libraries = library_preload::resolve_load_order(libraries)?;
+
+        for library in libraries {
             let internal_name = library.internal_name.clone();
             let info = self.load_library(library).map_err(|err| {
                 format!(
@@ -332,7 +490,8 @@ impl Registry {
                     internal_name, err
                 )
             })?;
-            self.library_info.insert(internal_name, info);
+            self.library_info.insert(internal_name.clone(), info);
+            self.library_order.push(internal_name);
         }
 
         // We wait to load patches in case patches depend on libraries that aren't loaded yet when
@@ -345,41 +504,134 @@ impl Registry {
         Ok(())
     }
 
-    pub fn new() -> Result<Self, String> {
-        let library_path = {
-            let user_dirs = directories::UserDirs::new().unwrap();
-            let document_dir = user_dirs.document_dir().unwrap();
-            document_dir.join("Audiobench")
-        };
-
-        let (sender, receiver) = mpsc::channel();
-        let update_urls = vec![ENGINE_UPDATE_URL.to_owned()];
-        update_check::spawn_update_checker(update_urls, sender);
-
-        let mut registry = Self {
+    fn empty(
+        library_path: PathBuf,
+        update_check_stream: Receiver<(String, Option<UpdateInfo>)>,
+    ) -> Self {
+        let (download_sender, download_stream) = mpsc::channel();
+        Self {
             module_templates: Vec::new(),
             modules_by_resource_id: HashMap::new(),
             modules_by_serialized_id: HashMap::new(),
 
             general_scripts_by_library: HashMap::new(),
             module_scripts_by_library: HashMap::new(),
+            migrations_by_library: HashMap::new(),
 
             icon_indexes: HashMap::new(),
             icons: Vec::new(),
 
+            themes: vec![Rc::new(Theme::default())],
+            active_theme: Rc::new(Theme::default()),
+
             unloaded_patches: Vec::new(),
             patches: Vec::new(),
             patch_paths: HashMap::new(),
 
             library_path,
             library_info: HashMap::new(),
+            library_order: Vec::new(),
+            pending_library_changes: HashMap::new(),
+            library_watcher: None,
+            settings: Default::default(),
             checked_updates: HashMap::new(),
-            update_check_stream: receiver,
+            update_check_stream,
+            downloading_updates: HashSet::new(),
+            download_sender,
+            download_stream,
+        }
+    }
+
+    /// Creates a registry where the given libraries are installed but nothing from them is loaded.
+    #[cfg(test)]
+    pub(super) fn new_for_testing(libraries: Vec<(String, LibraryInfo)>) -> Self {
+        let (_, receiver) = mpsc::channel();
+        let mut registry = Self::empty(PathBuf::new(), receiver);
+        for (name, info) in libraries {
+            registry.library_order.push(name.clone());
+            registry.library_info.insert(name, info);
+        }
+        registry
+    }
+
+    pub fn new() -> Result<Self, String> {
+        let library_path = {
+            let user_dirs = directories::UserDirs::new().unwrap();
+            let document_dir = user_dirs.document_dir().unwrap();
+            document_dir.join("Audiobench")
         };
+
+        // Replaced with a working channel once the update checker is started.
+        let (_, receiver) = mpsc::channel();
+        let mut registry = Self::empty(library_path, receiver);
+        registry.settings = Settings::load();
         registry.initialize()?;
+        registry.apply_theme_setting();
+        if registry.settings.check_for_updates {
+            registry.start_update_checker();
+        }
         Ok(registry)
     }
 
+    /// Creates a registry containing the Factory library and `dependencies`, then loads `library`
+    /// without stopping at the first problem. Returns everything that went wrong while loading
+    /// `library`, including problems with its patches. The returned error is for problems which
+    /// prevent `library` from being loaded at all, like missing dependencies. Nothing is read from
+    /// or written to the user's library folder.
+    pub(super) fn new_for_linting(
+        dependencies: Vec<PreloadedLibrary>,
+        library: PreloadedLibrary,
+    ) -> Result<(Self, Vec<String>), String> {
+        // Nothing is ever sent through this since no update checker is started.
+        let (_, receiver) = mpsc::channel();
+        let mut registry = Self::empty(PathBuf::new(), receiver);
+
+        let linted_name = library.internal_name.clone();
+        let mut libraries = Vec::new();
+        if linted_name != "Factory"
+            && dependencies
+                .iter()
+                .all(|dep| dep.internal_name != "Factory")
+        {
+            libraries.push(Self::preload_factory_library()?);
+        }
+        libraries.extend(dependencies);
+        libraries.push(library);
+        let libraries = library_preload::resolve_load_order(libraries)?;
+
+        let mut errors = Vec::new();
+        for library in libraries {
+            let internal_name = library.internal_name.clone();
+            let info = if internal_name == linted_name {
+                let (info, load_errors) = registry.load_library_collecting_errors(library);
+                errors = load_errors;
+                info
+            } else {
+                registry.load_library(library).map_err(|err| {
+                    format!(
+                        "ERROR: Failed to load dependency {}, caused by:\n{}",
+                        internal_name, err
+                    )
+                })?
+            };
+            registry.library_info.insert(internal_name.clone(), info);
+            registry.library_order.push(internal_name);
+        }
+
+        // Patches from dependencies are not being checked.
+        let patch_prefix = format!("{}:", linted_name);
+        let unloaded_patches = std::mem::take(&mut registry.unloaded_patches);
+        for (name, path, data) in unloaded_patches.into_iter() {
+            if name.starts_with(&patch_prefix) {
+                if let Err(err) = registry.load_patch(&name, path, data) {
+                    errors.push(err);
+                }
+            }
+        }
+
+        Ok((registry, errors))
+    }
+
     pub fn borrow_templates(&self) -> &[Rcrc<ModuleTemplate>] {
         &self.module_templates
     }
@@ -393,6 +645,45 @@ impl Registry {
             .map(|idx| &self.module_templates[*idx])
     }
 
+    /// Like `borrow_template_by_serialized_id` but for patches saved with an older version of the
+    /// library, where a module might have had a different save id.
+    pub fn borrow_template_by_old_serialized_id(
+        &self,
+        id: &(String, usize),
+        version: Version,
+    ) -> Option<&Rcrc<ModuleTemplate>> {
+        let migrations = if let Some(migrations) = self.migrations_by_library.get(&id.0) {
+            migrations
+        } else {
+            return self.borrow_template_by_serialized_id(id);
+        };
+        self.module_templates.iter().find(|template_ptr| {
+            let template = template_ptr.borrow();
+            template.lib_name == id.0 && migrations.save_id_at(&template, version) == id.1
+        })
+    }
+
+    /// Returns the controls the module had in the specified version of its library, see
+    /// `LibraryMigrations::old_control_layout`.
+    pub fn old_control_layout(
+        &self,
+        template: &ModuleTemplate,
+        version: Version,
+        changes: &mut Vec<String>,
+    ) -> Vec<(String, OldControl)> {
+        if let Some(migrations) = self.migrations_by_library.get(&template.lib_name) {
+            migrations.old_control_layout(template, version, changes)
+        } else {
+            let controls = template.default_controls.iter().enumerate();
+            controls
+                .map(|(index, (name, _))| {
+                    let old_range = None;
+                    (name.clone(), OldControl::Current { index, old_range })
+                })
+                .collect()
+        }
+    }
+
     pub fn borrow_general_scripts_from_library(&self, lib_name: &str) -> &[FileClip] {
         &self.general_scripts_by_library.get(lib_name).unwrap()[..]
     }
@@ -417,7 +708,8 @@ impl Registry {
         let filename = format!("{:016X}.abpatch", rand::thread_rng().next_u64());
         self.patch_paths
             .insert(format!("User:{}", filename), self.patches.len());
-        let patch = Patch::new(self.library_path.join("User").join(filename));
+        let mut patch = Patch::new(self.library_path.join("User").join(filename));
+        patch.set_location("User".to_owned(), Vec::new());
         let prc = rcrc(patch);
         self.patches.push(prc);
         self.patches.last().unwrap()
@@ -435,8 +727,393 @@ impl Registry {
         self.library_info.get(name)
     }
 
+    /// Iterates over all loaded libraries. Libraries always come after the libraries they depend
+    /// on.
     pub fn borrow_library_infos(&self) -> impl Iterator<Item = (&String, &LibraryInfo)> {
-        self.library_info.iter()
+        self.library_order
+            .iter()
+            .map(move |name| (name, &self.library_info[name]))
+    }
+
+    /// Returns where the files for a library are stored, if it is stored in the library folder.
+    fn find_library_files(&self, name: &str) -> Option<PathBuf> {
+        let dir_path = self.library_path.join(name);
+        let zip_path = self.library_path.join(format!("{}.ablib", name));
+        if dir_path.is_dir() {
+            Some(dir_path)
+        } else if zip_path.is_file() {
+            Some(zip_path)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the library is a plain directory in the library folder, as opposed to a
+    /// `.ablib` file or a library built into Audiobench.
+    pub fn is_directory_library(&self, name: &str) -> bool {
+        self.library_path.join(name).is_dir()
+    }
+
+    pub fn borrow_library_path(&self) -> &Path {
+        &self.library_path
+    }
+
+    pub fn borrow_pending_library_changes(&self) -> &HashMap<String, PendingLibraryChange> {
+        &self.pending_library_changes
+    }
+
+    /// Copies a `.ablib` file into the library folder so that it is loaded the next time
+    /// Audiobench starts. Zipped libraries that are already installed are replaced, which is how
+    /// libraries are updated. Returns the name of the installed library.
+    pub fn install_library(&mut self, path: &Path) -> Result<String, String> {
+        if path.is_dir() {
+            return Err(format!(
+                "ERROR: {} is a folder, only .ablib files can be installed.",
+                path.to_string_lossy()
+            ));
+        }
+        let library = library_preload::preload_library_from_path(path).map_err(|err| {
+            format!(
+                "ERROR: Failed to read library from {}, caused by:\n{}",
+                path.to_string_lossy(),
+                err
+            )
+        })?;
+        let name = library.internal_name;
+        if name == "Factory" || name == "User" {
+            return Err(format!(
+                "ERROR: Libraries cannot be named {}, rename the file and try again.",
+                name
+            ));
+        }
+        if self.is_directory_library(&name) {
+            return Err(format!(
+                concat!(
+                    "ERROR: A library named {} is already installed as a folder, remove it ",
+                    "before installing this file."
+                ),
+                name
+            ));
+        }
+        let mut problems = Vec::new();
+        for dependency in &library.info.dependencies {
+            let installed = if self.pending_library_changes.get(&dependency.name)
+                == Some(&PendingLibraryChange::Uninstalled)
+            {
+                None
+            } else {
+                self.library_info.get(&dependency.name)
+            };
+            if let Some(installed) = installed {
+                if !dependency.versions.contains(installed.version) {
+                    problems.push(format!(
+                        "ERROR: Requires {} of library {}, but version {} is installed.",
+                        dependency.versions, dependency.name, installed.version
+                    ));
+                }
+            } else {
+                problems.push(format!(
+                    "ERROR: Requires library {} ({}), which is not installed.",
+                    dependency.name, dependency.versions
+                ));
+            }
+        }
+        if !problems.is_empty() {
+            return Err(format!(
+                "ERROR: Cannot install {}, caused by:\n{}",
+                name,
+                problems.join("\n")
+            ));
+        }
+        let target = self.library_path.join(format!("{}.ablib", name));
+        // Copying a file onto itself would empty it.
+        if let (Ok(source), Ok(existing)) = (path.canonicalize(), target.canonicalize()) {
+            if source == existing {
+                return Err(format!("ERROR: {} is already installed.", name));
+            }
+        }
+        // The library is copied next to the target first so that a failed copy cannot destroy the
+        // version which is already installed.
+        let partial = self.library_path.join(format!("{}.ablib.partial", name));
+        if let Err(err) = fs::copy(path, &partial).and_then(|_| fs::rename(&partial, &target)) {
+            let _ = fs::remove_file(&partial);
+            return Err(format!(
+                "ERROR: Failed to copy library to {}, caused by:\nERROR: {}",
+                target.to_string_lossy(),
+                err
+            ));
+        }
+        self.pending_library_changes
+            .insert(name.clone(), PendingLibraryChange::Installed);
+        Ok(name)
+    }
+
+    /// Deletes a library from the library folder. It stays loaded until Audiobench restarts.
+    pub fn uninstall_library(&mut self, name: &str) -> Result<(), String> {
+        if name == "Factory" || name == "User" {
+            return Err(format!(
+                "ERROR: The {} library cannot be uninstalled.",
+                name
+            ));
+        }
+        let dependents: Vec<_> = self
+            .borrow_library_infos()
+            .filter(|(other, info)| {
+                self.pending_library_changes.get(*other) != Some(&PendingLibraryChange::Uninstalled)
+                    && info.dependencies.iter().any(|dep| dep.name == name)
+            })
+            .map(|(other, _)| &other[..])
+            .collect();
+        if !dependents.is_empty() {
+            return Err(format!(
+                "ERROR: {} cannot be uninstalled because these libraries depend on it: {}",
+                name,
+                dependents.join(", ")
+            ));
+        }
+        let path = self
+            .find_library_files(name)
+            .ok_or_else(|| format!("ERROR: Could not find the files for library {}.", name))?;
+        if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        }
+        .map_err(|err| {
+            format!(
+                "ERROR: Failed to delete {}, caused by:\nERROR: {}",
+                path.to_string_lossy(),
+                err
+            )
+        })?;
+        self.pending_library_changes
+            .insert(name.to_owned(), PendingLibraryChange::Uninstalled);
+        Ok(())
+    }
+
+    /// Packs a directory library into a `.ablib` file inside `target_dir` so that it can be shared
+    /// with other people. The User library is exported as `UserPatches` because libraries named
+    /// User cannot be installed. Returns the path of the created file.
+    pub fn export_library(&self, name: &str, target_dir: &Path) -> Result<PathBuf, String> {
+        if !self.is_directory_library(name) {
+            return Err(format!(
+                "ERROR: {} is not stored as a folder, only folder libraries can be exported.",
+                name
+            ));
+        }
+        // The name of a zipped library comes from its file name.
+        let export_name = if name == "User" { "UserPatches" } else { name };
+        let target = target_dir.join(format!("{}.ablib", export_name));
+        library_preload::export_library_to_zip(&self.library_path.join(name), &target).map_err(
+            |err| {
+                format!(
+                    "ERROR: Failed to export library {}, caused by:\n{}",
+                    name, err
+                )
+            },
+        )?;
+        Ok(target)
+    }
+
+    /// In development mode, directory libraries are watched for changes so that they can be
+    /// reloaded with `reload_library` while Audiobench is running.
+    pub fn set_development_mode(&mut self, enabled: bool) {
+        self.library_watcher = if enabled {
+            let libraries = self
+                .library_order
+                .iter()
+                // The User library changes whenever a patch is saved and has no code anyway.
+                .filter(|name| *name != "User" && self.is_directory_library(name))
+                .map(|name| (name.clone(), self.library_path.join(name)))
+                .collect();
+            Some(LibraryWatcher::new(libraries))
+        } else {
+            None
+        };
+    }
+
+    pub fn is_development_mode(&self) -> bool {
+        self.library_watcher.is_some()
+    }
+
+    /// Returns the names of watched libraries whose files changed since the last call. Always
+    /// returns nothing outside of development mode.
+    pub fn poll_changed_libraries(&mut self) -> Vec<String> {
+        if let Some(watcher) = &mut self.library_watcher {
+            watcher.poll()
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn reindex_templates(&mut self) {
+        self.modules_by_resource_id.clear();
+        self.modules_by_serialized_id.clear();
+        for (index, template_ptr) in self.module_templates.iter().enumerate() {
+            let template = template_ptr.borrow();
+            let resource_id = format!("{}:{}", template.lib_name, template.module_name);
+            self.modules_by_resource_id.insert(resource_id, index);
+            let ser_id = (template.lib_name.clone(), template.save_id);
+            self.modules_by_serialized_id.insert(ser_id, index);
+        }
+    }
+
+    /// Loads a directory library again after its files have changed. Templates of modules that
+    /// still exist are updated in place, so modules using them see the new version, but the
+    /// caller is responsible for making those modules consistent with their new templates. If
+    /// the library fails to load, everything is left the way it was. Patches in the library are
+    /// not reloaded and new icons only show up after restarting.
+    pub fn reload_library(&mut self, name: &str) -> Result<(), String> {
+        let library = library_preload::preload_library_from_path(&self.library_path.join(name))
+            .map_err(|err| {
+                format!(
+                    "ERROR: Failed to preload library {}, caused by:\n{}",
+                    name, err
+                )
+            })?;
+        // Take out everything that belongs to the library so that it can be loaded from scratch.
+        let (old_templates, other_templates) = std::mem::take(&mut self.module_templates)
+            .into_iter()
+            .partition(|template| template.borrow().lib_name == name);
+        let old_templates: Vec<Rcrc<ModuleTemplate>> = old_templates;
+        self.module_templates = other_templates;
+        self.reindex_templates();
+        let old_general_scripts = self.general_scripts_by_library.remove(name);
+        let old_module_scripts = self.module_scripts_by_library.remove(name);
+        let old_migrations = self.migrations_by_library.remove(name);
+        let (old_themes, other_themes) = std::mem::take(&mut self.themes)
+            .into_iter()
+            .partition(|theme| theme.lib_name == name);
+        let old_themes: Vec<Rc<Theme>> = old_themes;
+        self.themes = other_themes;
+
+        let result = self.load_library(library);
+        // Patches were already loaded when Audiobench started.
+        self.unloaded_patches.clear();
+        match result {
+            Ok(info) => {
+                for template_ptr in &mut self.module_templates {
+                    let old = old_templates.iter().find(|old| {
+                        let (old, new) = (old.borrow(), template_ptr.borrow());
+                        old.lib_name == new.lib_name && old.module_name == new.module_name
+                    });
+                    if let Some(old) = old {
+                        std::mem::swap(&mut *old.borrow_mut(), &mut *template_ptr.borrow_mut());
+                        *template_ptr = Rc::clone(old);
+                    }
+                }
+                self.library_info.insert(name.to_owned(), info);
+                // Lets people making a theme see their changes right away.
+                self.apply_theme_setting();
+                Ok(())
+            }
+            Err(err) => {
+                self.module_templates
+                    .retain(|template| template.borrow().lib_name != name);
+                self.module_templates.extend(old_templates);
+                self.reindex_templates();
+                restore_entry(
+                    &mut self.general_scripts_by_library,
+                    name,
+                    old_general_scripts,
+                );
+                restore_entry(
+                    &mut self.module_scripts_by_library,
+                    name,
+                    old_module_scripts,
+                );
+                restore_entry(&mut self.migrations_by_library, name, old_migrations);
+                self.themes.retain(|theme| theme.lib_name != name);
+                self.themes.extend(old_themes);
+                Err(format!(
+                    "ERROR: Failed to reload library {}, caused by:\n{}",
+                    name, err
+                ))
+            }
+        }
+    }
+
+    pub fn borrow_themes(&self) -> &[Rc<Theme>] {
+        &self.themes
+    }
+
+    pub fn get_active_theme(&self) -> Rc<Theme> {
+        Rc::clone(&self.active_theme)
+    }
+
+    /// Makes the theme chosen in the settings active, falling back to the default theme if it
+    /// does not exist anymore.
+    fn apply_theme_setting(&mut self) {
+        let chosen = self.settings.theme.as_ref().and_then(|name| {
+            let theme = self.themes.iter().find(|theme| &theme.name == name);
+            if theme.is_none() {
+                eprintln!(
+                    "WARNING: The theme {} could not be found, using the default theme instead.",
+                    name
+                );
+            }
+            theme
+        });
+        self.active_theme = Rc::clone(chosen.unwrap_or(&self.themes[0]));
+    }
+
+    /// Changes the theme used by the GUI and saves the choice.
+    pub fn set_active_theme(&mut self, name: &str) -> Result<(), String> {
+        let theme = self
+            .themes
+            .iter()
+            .find(|theme| theme.name == name)
+            .ok_or_else(|| format!("ERROR: There is no theme named {}.", name))?;
+        self.active_theme = Rc::clone(theme);
+        self.settings.theme = Some(name.to_owned());
+        self.settings.save()
+    }
+
+    fn engine_update_url(&self) -> &str {
+        self.settings
+            .engine_update_url
+            .as_ref()
+            .map(|url| &url[..])
+            .unwrap_or(ENGINE_UPDATE_URL)
+    }
+
+    /// Checks Audiobench and every library which has an `update_url` for new versions in the
+    /// background, see `poll_update_checker`.
+    fn start_update_checker(&mut self) {
+        let mut update_urls = vec![self.engine_update_url().to_owned()];
+        for (_, info) in self.borrow_library_infos() {
+            if let Some(url) = &info.update_url {
+                update_urls.push(url.clone());
+            }
+        }
+        let (sender, receiver) = mpsc::channel();
+        self.update_check_stream = receiver;
+        update_check::spawn_update_checker(update_urls, sender);
+    }
+
+    pub fn is_saving_text_patches(&self) -> bool {
+        self.settings.text_patches
+    }
+
+    /// Changes whether patches are saved in the text format and saves the setting. Patches which
+    /// are already text stay text either way.
+    pub fn set_saving_text_patches(&mut self, enabled: bool) -> Result<(), String> {
+        self.settings.text_patches = enabled;
+        self.settings.save()
+    }
+
+    pub fn is_checking_for_updates(&self) -> bool {
+        self.settings.check_for_updates
+    }
+
+    /// Changes whether updates are checked for when Audiobench starts and saves the setting.
+    /// Turning it on also checks for updates right away if that has not been done yet.
+    pub fn set_checking_for_updates(&mut self, enabled: bool) -> Result<(), String> {
+        self.settings.check_for_updates = enabled;
+        if enabled && self.checked_updates.is_empty() {
+            self.start_update_checker();
+        }
+        self.settings.save()
     }
 
     // Returns true if the update checker is still running.
@@ -457,13 +1134,86 @@ impl Registry {
         self.checked_updates.get(url)
     }
 
+    /// Returns information about a newer version of the library if the update checker found one.
+    /// Returns None once the update has been installed, even before Audiobench restarts.
+    pub fn borrow_library_update(&self, name: &str) -> Option<&UpdateInfo> {
+        let info = self.library_info.get(name)?;
+        let update = self
+            .checked_updates
+            .get(info.update_url.as_ref()?)?
+            .as_ref()?;
+        if update.version > info.version && !self.pending_library_changes.contains_key(name) {
+            Some(update)
+        } else {
+            None
+        }
+    }
+
     pub fn any_updates_available(&self) -> bool {
-        if let Some(Some(info)) = self.checked_updates.get(ENGINE_UPDATE_URL) {
-            // TODO: Update check. unimplemented!()
-            // if info.version > ENGINE_VERSION {
-            //     return true;
-            // }
+        if let Some(Some(info)) = self.checked_updates.get(self.engine_update_url()) {
+            if info.version > ENGINE_VERSION {
+                return true;
+            }
+        }
+        self.library_order
+            .iter()
+            .any(|name| self.borrow_library_update(name).is_some())
+    }
+
+    /// Starts downloading the newest version of a library in the background. Once the download
+    /// finishes, `poll_library_updates` installs it.
+    pub fn start_library_update(&mut self, name: &str) -> Result<(), String> {
+        if self.is_directory_library(name) {
+            return Err(format!(
+                "ERROR: {} is installed as a folder and cannot be updated automatically.",
+                name
+            ));
+        }
+        if self.downloading_updates.contains(name) {
+            return Ok(());
+        }
+        let update = self
+            .borrow_library_update(name)
+            .ok_or_else(|| format!("ERROR: There is no update available for {}.", name))?;
+        update_check::spawn_download(
+            name.to_owned(),
+            update.download_url.clone(),
+            self.download_sender.clone(),
+        );
+        self.downloading_updates.insert(name.to_owned());
+        Ok(())
+    }
+
+    pub fn is_downloading_update(&self, name: &str) -> bool {
+        self.downloading_updates.contains(name)
+    }
+
+    /// Installs updates which have finished downloading. Returns the name of each library that was
+    /// updated, or what went wrong.
+    pub fn poll_library_updates(&mut self) -> Vec<Result<String, String>> {
+        let mut results = Vec::new();
+        while let Ok((name, download)) = self.download_stream.try_recv() {
+            self.downloading_updates.remove(&name);
+            let result = download.and_then(|data| {
+                // install_library takes the name of the library from the name of the file.
+                let path = std::env::temp_dir().join(format!("{}.ablib", name));
+                fs::write(&path, data).map_err(|err| {
+                    format!(
+                        "ERROR: Failed to write {}, caused by:\nERROR: {}",
+                        path.to_string_lossy(),
+                        err
+                    )
+                })?;
+                let result = self.install_library(&path);
+                let _ = fs::remove_file(&path);
+                result
+            });
+            results.push(
+                result.map_err(|err| {
+                    format!("ERROR: Failed to update {}, caused by:\n{}", name, err)
+                }),
+            );
         }
-        false
+        results
     }
 }
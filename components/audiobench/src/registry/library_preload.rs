@@ -1,8 +1,13 @@
-use crate::{config::*, registry::yaml};
+use crate::{
+    config::*,
+    registry::{recovery, yaml},
+};
 use shared_util::Version;
 use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
     fs::{self, File},
-    io::{Read, Seek},
+    io::{Read, Seek, Write},
     path::{Path, PathBuf},
 };
 
@@ -10,9 +15,90 @@ pub struct LibraryInfo {
     pub pretty_name: String,
     pub description: String,
     pub version: Version,
+    pub dependencies: Vec<LibraryDependency>,
+    /// Where to check for new versions of the library, see `update_check::UpdateInfo`.
+    pub update_url: Option<String>,
     // min_engine_version check is handled by parse_library_info.
 }
 
+/// A range of library versions. Both bounds are optional, the lower bound is inclusive and the
+/// upper bound is exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VersionRange {
+    pub min: Option<Version>,
+    pub max: Option<Version>,
+}
+
+impl VersionRange {
+    pub fn contains(&self, version: Version) -> bool {
+        self.min.map(|min| version >= min).unwrap_or(true)
+            && self.max.map(|max| version < max).unwrap_or(true)
+    }
+
+    /// Parses something like ">=1.2.0, <2.0.0". A bare version like "1.2.0" is treated the same as
+    /// ">=1.2.0" and "*" accepts any version. The requirement may be surrounded by quotes.
+    fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim().trim_matches('"');
+        let mut result = Self {
+            min: None,
+            max: None,
+        };
+        if text == "*" {
+            return Ok(result);
+        }
+        for part in text.split(',') {
+            let part = part.trim();
+            let (is_max, version_text) = if let Some(rest) = part.strip_prefix(">=") {
+                (false, rest)
+            } else if let Some(rest) = part.strip_prefix('<') {
+                (true, rest)
+            } else {
+                (false, part)
+            };
+            let version = version_text.trim().parse().map_err(|_| {
+                format!(
+                    concat!(
+                        "ERROR: \"{}\" is not a valid version requirement. Expected something ",
+                        "like \">=1.2.0\" or \"<2.0.0\"."
+                    ),
+                    part
+                )
+            })?;
+            let bound = if is_max {
+                &mut result.max
+            } else {
+                &mut result.min
+            };
+            if bound.is_some() {
+                return Err(format!(
+                    "ERROR: \"{}\" specifies the same bound more than once.",
+                    text
+                ));
+            }
+            *bound = Some(version);
+        }
+        Ok(result)
+    }
+}
+
+impl Display for VersionRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (self.min, self.max) {
+            (None, None) => write!(f, "any version"),
+            (Some(min), None) => write!(f, "version {} or later", min),
+            (None, Some(max)) => write!(f, "a version before {}", max),
+            (Some(min), Some(max)) => write!(f, "at least version {} but before {}", min, max),
+        }
+    }
+}
+
+/// Another library which must be installed for a library to work.
+pub struct LibraryDependency {
+    /// The internal name of the library, which is its file or folder name without the extension.
+    pub name: String,
+    pub versions: VersionRange,
+}
+
 pub(super) struct PreloadedLibrary {
     pub internal_name: String,
     pub content: Box<dyn LibraryContentProvider>,
@@ -159,23 +245,38 @@ fn parse_library_info(name: &str, buffer: Vec<u8>) -> Result<LibraryInfo, String
     let pretty_name = yaml.unique_child("pretty_name")?.value.clone();
     let description = yaml.unique_child("description")?.value.clone();
     let version = yaml.unique_child("version")?.parse()?;
-    // TODO: Min engine version check. unimplemented!()
-    // let min_engine_version = yaml
-    //     .unique_child("min_engine_version")?
-    //     .parse_ranged(Some(0), Some(0xFFFF))?;
-    // if min_engine_version > ENGINE_VERSION as i32 {
-    //     return Err(format!(
-    //         concat!(
-    //             "ERROR: This library requires at least version {} of Audiobench.\n",
-    //             "You are currently running version {}.",
-    //         ),
-    //         min_engine_version, ENGINE_VERSION
-    //     ));
-    // }
+    // Older libraries do not specify a minimum engine version.
+    if let Ok(child) = yaml.unique_child("min_engine_version") {
+        let min_engine_version: Version = child.parse()?;
+        if min_engine_version > ENGINE_VERSION {
+            return Err(format!(
+                concat!(
+                    "ERROR: This library requires at least version {} of Audiobench.\n",
+                    "You are currently running version {}."
+                ),
+                min_engine_version, ENGINE_VERSION
+            ));
+        }
+    }
+    let mut dependencies = Vec::new();
+    if let Ok(child) = yaml.unique_child("dependencies") {
+        for dependency in &child.children {
+            dependencies.push(LibraryDependency {
+                name: dependency.name.clone(),
+                versions: dependency.parse_custom(VersionRange::parse)?,
+            });
+        }
+    }
+    let update_url = yaml
+        .unique_child("update_url")
+        .ok()
+        .map(|child| child.value.trim().to_owned());
     Ok(LibraryInfo {
         pretty_name,
         description,
         version,
+        dependencies,
+        update_url,
     })
 }
 
@@ -235,3 +336,294 @@ pub(super) fn preload_library_from_path(path: &Path) -> Result<PreloadedLibrary,
         preload_library(lib_name, Box::new(content))
     }
 }
+
+/// Packs a directory library into a zip file at `output_path` which can be installed as a `.ablib`
+/// file. Recovery files are left out since they are only useful on this computer.
+pub(super) fn export_library_to_zip(library_path: &Path, output_path: &Path) -> Result<(), String> {
+    let mut content = DirectoryLibraryContentProvider::new(library_path.to_owned())?;
+    let output_file = File::create(output_path).map_err(|err| {
+        format!(
+            "ERROR: Failed to create {}, caused by:\nERROR: {}",
+            output_path.to_string_lossy(),
+            err
+        )
+    })?;
+    let mut zip_writer = zip::ZipWriter::new(output_file);
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let write_err = |err: &dyn Display| {
+        format!(
+            "ERROR: Failed to write {}, caused by:\nERROR: {}",
+            output_path.to_string_lossy(),
+            err
+        )
+    };
+    for index in 0..content.get_num_files() {
+        if content
+            .get_file_name(index)
+            .ends_with(recovery::RECOVERY_EXTENSION)
+        {
+            continue;
+        }
+        // Zip files always use forward slashes, no matter what the platform uses.
+        let zip_key = content.file_paths[index]
+            .iter()
+            .map(|part| part.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let contents = content.read_file_contents(index)?;
+        zip_writer
+            .start_file(&zip_key, options)
+            .map_err(|err| write_err(&err))?;
+        zip_writer
+            .write_all(&contents)
+            .map_err(|err| write_err(&err))?;
+    }
+    zip_writer.finish().map_err(|err| write_err(&err))?;
+    Ok(())
+}
+
+/// Checks that every library has all its dependencies available in compatible versions and returns
+/// the libraries in an order where each library comes after all the libraries it depends on.
+/// Libraries which do not depend on each other keep the order they were given in.
+pub(super) fn resolve_load_order(
+    libraries: Vec<PreloadedLibrary>,
+) -> Result<Vec<PreloadedLibrary>, String> {
+    let mut indexes = HashMap::new();
+    for (index, library) in libraries.iter().enumerate() {
+        if indexes
+            .insert(library.internal_name.clone(), index)
+            .is_some()
+        {
+            return Err(format!(
+                "ERROR: There is more than one library named {}.",
+                library.internal_name
+            ));
+        }
+    }
+
+    let mut problems = Vec::new();
+    for library in &libraries {
+        for dependency in &library.info.dependencies {
+            if let Some(&index) = indexes.get(&dependency.name) {
+                let installed = libraries[index].info.version;
+                if !dependency.versions.contains(installed) {
+                    problems.push(format!(
+                        concat!(
+                            "ERROR: Library {} requires {} of library {}, but version {} is ",
+                            "installed."
+                        ),
+                        library.internal_name, dependency.versions, dependency.name, installed
+                    ));
+                }
+            } else {
+                problems.push(format!(
+                    "ERROR: Library {} requires library {} ({}), which is not installed.",
+                    library.internal_name, dependency.name, dependency.versions
+                ));
+            }
+        }
+    }
+    if !problems.is_empty() {
+        return Err(format!(
+            "ERROR: Some libraries are missing dependencies, caused by:\n{}",
+            problems.join("\n")
+        ));
+    }
+
+    // Depth-first topological sort. `visiting` contains the libraries on the current path so that
+    // circular dependencies can be reported instead of recursing forever.
+    fn visit(
+        index: usize,
+        libraries: &[PreloadedLibrary],
+        indexes: &HashMap<String, usize>,
+        visiting: &mut Vec<usize>,
+        visited: &mut HashSet<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), String> {
+        if visited.contains(&index) {
+            return Ok(());
+        }
+        if let Some(start) = visiting.iter().position(|other| *other == index) {
+            let mut cycle: Vec<_> = visiting[start..]
+                .iter()
+                .map(|index| &libraries[*index].internal_name[..])
+                .collect();
+            cycle.push(&libraries[index].internal_name);
+            return Err(format!(
+                "ERROR: Libraries have a circular dependency: {}",
+                cycle.join(" -> ")
+            ));
+        }
+        visiting.push(index);
+        for dependency in &libraries[index].info.dependencies {
+            let dep_index = indexes[&dependency.name];
+            visit(dep_index, libraries, indexes, visiting, visited, order)?;
+        }
+        visiting.pop();
+        visited.insert(index);
+        order.push(index);
+        Ok(())
+    }
+
+    let mut visiting = Vec::new();
+    let mut visited = HashSet::new();
+    let mut order = Vec::new();
+    for index in 0..libraries.len() {
+        visit(
+            index,
+            &libraries,
+            &indexes,
+            &mut visiting,
+            &mut visited,
+            &mut order,
+        )?;
+    }
+
+    let mut libraries: Vec<_> = libraries.into_iter().map(Some).collect();
+    Ok(order
+        .into_iter()
+        .map(|index| libraries[index].take().unwrap())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(major: u8, minor: u8, patch: u8) -> Version {
+        Version::new(major, minor, patch)
+    }
+
+    struct NoFiles;
+
+    impl LibraryContentProvider for NoFiles {
+        fn get_num_files(&self) -> usize {
+            0
+        }
+
+        fn get_file_name(&mut self, _index: usize) -> String {
+            unreachable!()
+        }
+
+        fn get_full_path(&mut self, _index: usize) -> Option<PathBuf> {
+            None
+        }
+
+        fn read_file_contents(&mut self, _index: usize) -> Result<Vec<u8>, String> {
+            unreachable!()
+        }
+    }
+
+    /// A library at version 1.0.0 which depends on any version of each of `dependencies`.
+    fn library(name: &str, dependencies: &[&str]) -> PreloadedLibrary {
+        let dependencies = dependencies
+            .iter()
+            .map(|name| LibraryDependency {
+                name: (*name).to_owned(),
+                versions: VersionRange::parse("*").unwrap(),
+            })
+            .collect();
+        PreloadedLibrary {
+            internal_name: name.to_owned(),
+            content: Box::new(NoFiles),
+            info: LibraryInfo {
+                pretty_name: name.to_owned(),
+                description: String::new(),
+                version: v(1, 0, 0),
+                dependencies,
+                update_url: None,
+            },
+        }
+    }
+
+    fn names(libraries: &[PreloadedLibrary]) -> Vec<&str> {
+        libraries
+            .iter()
+            .map(|library| &library.internal_name[..])
+            .collect()
+    }
+
+    #[test]
+    fn parse_version_ranges() {
+        let range = |min, max| VersionRange { min, max };
+        assert_eq!(VersionRange::parse("*"), Ok(range(None, None)));
+        assert_eq!(
+            VersionRange::parse("1.2.0"),
+            Ok(range(Some(v(1, 2, 0)), None))
+        );
+        assert_eq!(
+            VersionRange::parse("\">=1.2.0, <2.0.0\""),
+            Ok(range(Some(v(1, 2, 0)), Some(v(2, 0, 0))))
+        );
+        assert_eq!(
+            VersionRange::parse("<2.0.0"),
+            Ok(range(None, Some(v(2, 0, 0))))
+        );
+    }
+
+    #[test]
+    fn malformed_version_ranges_are_errors() {
+        for text in &[
+            "",
+            "1.2",
+            ">1.2.0",
+            "<=2.0.0",
+            ">=1.0.0, >=1.1.0",
+            "<2.0.0,<3.0.0",
+        ] {
+            assert!(VersionRange::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn lower_bound_is_inclusive_and_upper_bound_is_exclusive() {
+        let range = VersionRange::parse(">=1.2.0, <2.0.0").unwrap();
+        assert!(!range.contains(v(1, 1, 9)));
+        assert!(range.contains(v(1, 2, 0)));
+        assert!(range.contains(v(1, 9, 9)));
+        assert!(!range.contains(v(2, 0, 0)));
+        assert!(VersionRange::parse("*").unwrap().contains(v(0, 0, 0)));
+    }
+
+    #[test]
+    fn missing_dependency_is_an_error() {
+        let libraries = vec![library("A", &["B"])];
+        let err = resolve_load_order(libraries).err().unwrap();
+        assert!(err.contains("requires library B"), "{}", err);
+    }
+
+    #[test]
+    fn incompatible_dependency_is_an_error() {
+        let mut a = library("A", &["B"]);
+        a.info.dependencies[0].versions = VersionRange::parse(">=2.0.0").unwrap();
+        let err = resolve_load_order(vec![a, library("B", &[])])
+            .err()
+            .unwrap();
+        assert!(err.contains("version 1.0.0 is installed"), "{}", err);
+    }
+
+    #[test]
+    fn dependency_cycle_is_an_error() {
+        let libraries = vec![
+            library("A", &["B"]),
+            library("B", &["C"]),
+            library("C", &["A"]),
+        ];
+        let err = resolve_load_order(libraries).err().unwrap();
+        assert!(err.contains("A -> B -> C -> A"), "{}", err);
+    }
+
+    #[test]
+    fn dependencies_load_first_and_the_rest_keep_their_order() {
+        let libraries = vec![
+            library("D", &[]),
+            library("A", &["C", "B"]),
+            library("B", &["C"]),
+            library("C", &[]),
+            library("E", &[]),
+        ];
+        let order = resolve_load_order(libraries).unwrap();
+        assert_eq!(names(&order), vec!["D", "C", "B", "A", "E"]);
+    }
+}
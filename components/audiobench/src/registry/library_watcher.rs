@@ -0,0 +1,83 @@
+//! Used in development mode to notice when the files of a directory library change so that the
+//! library can be reloaded without restarting Audiobench.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often the library folders are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// When each file in a library was last modified.
+type Snapshot = HashMap<PathBuf, SystemTime>;
+
+fn take_snapshot(root_path: &Path) -> Snapshot {
+    let mut snapshot = HashMap::new();
+    let mut unvisited_paths = vec![root_path.to_owned()];
+    while let Some(visiting) = unvisited_paths.pop() {
+        let reader = if let Ok(reader) = fs::read_dir(&visiting) {
+            reader
+        } else {
+            continue;
+        };
+        for entry in reader {
+            let entry = if let Ok(entry) = entry {
+                entry
+            } else {
+                continue;
+            };
+            let path = entry.path();
+            if path.is_dir() {
+                unvisited_paths.push(path);
+            } else if let Ok(modified) = entry.metadata().and_then(|meta| meta.modified()) {
+                snapshot.insert(path, modified);
+            }
+        }
+    }
+    snapshot
+}
+
+pub(super) struct LibraryWatcher {
+    libraries: Vec<(String, PathBuf, Snapshot)>,
+    last_poll: Instant,
+}
+
+impl LibraryWatcher {
+    /// Watches the libraries stored in the given folders, which are specified along with their
+    /// names.
+    pub fn new(libraries: Vec<(String, PathBuf)>) -> Self {
+        let libraries = libraries
+            .into_iter()
+            .map(|(name, path)| {
+                let snapshot = take_snapshot(&path);
+                (name, path, snapshot)
+            })
+            .collect();
+        Self {
+            libraries,
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Returns the names of all libraries which had files added, removed or modified since the
+    /// last time this was called. The folders are only checked every `POLL_INTERVAL`, calling it
+    /// more often than that returns nothing.
+    pub fn poll(&mut self) -> Vec<String> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+        let mut changed = Vec::new();
+        for (name, path, snapshot) in &mut self.libraries {
+            let new_snapshot = take_snapshot(path);
+            if &new_snapshot != snapshot {
+                *snapshot = new_snapshot;
+                changed.push(name.clone());
+            }
+        }
+        changed
+    }
+}
@@ -0,0 +1,248 @@
+//! Checks a library for problems without starting Audiobench. The library is loaded the same way
+//! Audiobench would load it, except that loading continues after the first error so that every
+//! problem can be reported in one go. Some extra checks are done afterwards for mistakes that
+//! would otherwise only show up once a module is used.
+
+use crate::registry::{
+    library_preload::{self, PreloadedLibrary},
+    Registry,
+};
+use std::path::Path;
+
+/// Returns a description of every problem found in the library stored at `path`, which can be
+/// either a folder or a `.ablib` file. Libraries it depends on are looked for in the same folder
+/// the library is in. An empty list means no problems were found.
+pub fn lint_library(path: &Path) -> Vec<String> {
+    match lint_library_impl(path) {
+        Ok(problems) => problems,
+        Err(err) => vec![err],
+    }
+}
+
+fn lint_library_impl(path: &Path) -> Result<Vec<String>, String> {
+    let mut library = library_preload::preload_library_from_path(path)?;
+    let lib_name = library.internal_name.clone();
+    // The registry does not keep the contents of every file, so the ones needed for the checks
+    // below are read before loading the library.
+    let mut file_names = Vec::new();
+    let mut text_files = Vec::new();
+    for index in 0..library.content.get_num_files() {
+        let file_name = library.content.get_file_name(index);
+        if file_name.ends_with(".yaml") || file_name.ends_with(".jl") {
+            // Files which can't be read are reported when the library is loaded.
+            if let Ok(Ok(text)) = library
+                .content
+                .read_file_contents(index)
+                .map(String::from_utf8)
+            {
+                text_files.push((file_name.clone(), text));
+            }
+        }
+        file_names.push(file_name);
+    }
+
+    let dependencies = find_dependencies(path, &library);
+    let (registry, mut problems) = Registry::new_for_linting(dependencies, library)?;
+    check_module_scripts(&registry, &lib_name, &mut problems);
+    check_unused_icons(&lib_name, &file_names, &text_files, &mut problems);
+    check_unused_general_scripts(&text_files, &mut problems);
+    Ok(problems)
+}
+
+/// Finds the libraries `library` depends on, directly or indirectly, in the folder containing
+/// `path`. Missing dependencies are reported later when the load order is resolved.
+fn find_dependencies(path: &Path, library: &PreloadedLibrary) -> Vec<PreloadedLibrary> {
+    let folder = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    let mut unvisited: Vec<String> = library
+        .info
+        .dependencies
+        .iter()
+        .map(|dependency| dependency.name.clone())
+        .collect();
+    let mut found: Vec<PreloadedLibrary> = Vec::new();
+    while let Some(name) = unvisited.pop() {
+        if name == library.internal_name || found.iter().any(|lib| lib.internal_name == name) {
+            continue;
+        }
+        for candidate in &[folder.join(&name), folder.join(format!("{}.ablib", name))] {
+            if !candidate.exists() {
+                continue;
+            }
+            if let Ok(dependency) = library_preload::preload_library_from_path(candidate) {
+                for other in &dependency.info.dependencies {
+                    unvisited.push(other.name.clone());
+                }
+                found.push(dependency);
+                break;
+            }
+        }
+    }
+    found
+}
+
+/// Checks that every module has code, that the code defines `exec()` and that `exec()` assigns
+/// every output.
+fn check_module_scripts(registry: &Registry, lib_name: &str, problems: &mut Vec<String>) {
+    let scripts = registry.borrow_module_scripts_from_library(lib_name);
+    let templates: Vec<_> = registry
+        .borrow_templates()
+        .iter()
+        .map(|template_ptr| template_ptr.borrow())
+        .filter(|template| template.lib_name == lib_name)
+        .collect();
+    for template in &templates {
+        let script = scripts
+            .iter()
+            .find(|(module_name, _)| module_name == &template.module_name);
+        let script = if let Some((_, script)) = script {
+            script
+        } else {
+            problems.push(format!(
+                "ERROR: Module {} does not have a corresponding {}.module.jl file.",
+                template.module_name, template.module_name
+            ));
+            continue;
+        };
+        let exec_code = if let Some((exec_code, _)) = script.clip_section("function exec()", "end")
+        {
+            exec_code
+        } else {
+            problems.push(format!(
+                "ERROR: The code for module {} does not define a function called exec().",
+                template.module_name
+            ));
+            continue;
+        };
+        for output in &template.outputs {
+            if !assigns_variable(exec_code.as_str(), output.borrow_code_name()) {
+                problems.push(format!(
+                    "ERROR: The exec() function of module {} never assigns its output {}.",
+                    template.module_name,
+                    output.borrow_code_name()
+                ));
+            }
+        }
+    }
+    for (module_name, _) in scripts {
+        if !templates
+            .iter()
+            .any(|template| &template.module_name == module_name)
+        {
+            problems.push(format!(
+                "ERROR: {}.module.jl does not have a corresponding {}.module.yaml file.",
+                module_name, module_name
+            ));
+        }
+    }
+}
+
+/// Reports icons which are not mentioned in any of the library's YAML or Julia files. The factory
+/// library is skipped since the GUI and other libraries use its icons by name.
+fn check_unused_icons(
+    lib_name: &str,
+    file_names: &[String],
+    text_files: &[(String, String)],
+    problems: &mut Vec<String>,
+) {
+    if lib_name == "Factory" {
+        return;
+    }
+    for file_name in file_names {
+        if let Some(icon_path) = file_name.strip_suffix(".icon.svg") {
+            let icon_name = icon_path.rsplit('/').next().unwrap_or(icon_path);
+            let icon_id = format!("{}:{}", lib_name, icon_name);
+            if !text_files.iter().any(|(_, text)| text.contains(&icon_id)) {
+                problems.push(format!(
+                    "WARNING: The icon {} is not used anywhere in the library.",
+                    icon_id
+                ));
+            }
+        }
+    }
+}
+
+/// Reports `.lib.jl` files where nothing they define is used by any other Julia file in the
+/// library. Libraries depending on this one might still use them, so this is only a warning.
+fn check_unused_general_scripts(text_files: &[(String, String)], problems: &mut Vec<String>) {
+    for (file_name, text) in text_files {
+        if !file_name.ends_with(".lib.jl") {
+            continue;
+        }
+        let names = defined_names(text);
+        let used = text_files
+            .iter()
+            .filter(|(other_name, _)| other_name != file_name && other_name.ends_with(".jl"))
+            .any(|(_, other)| names.iter().any(|name| mentions_name(other, name)));
+        if !names.is_empty() && !used {
+            problems.push(format!(
+                concat!(
+                    "WARNING: Nothing defined in {} is used by any other code in the library. ",
+                    "Remove it if it is left over from something else."
+                ),
+                file_name
+            ));
+        }
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '!'
+}
+
+/// Returns the names of the functions, constants, types and macros defined in `code`.
+fn defined_names(code: &str) -> Vec<&str> {
+    const KEYWORDS: &[&str] = &[
+        "function ",
+        "const ",
+        "struct ",
+        "mutable struct ",
+        "abstract type ",
+        "macro ",
+    ];
+    let mut names = Vec::new();
+    for line in code.lines() {
+        let line = line.trim_start();
+        for keyword in KEYWORDS {
+            if let Some(rest) = line.strip_prefix(keyword) {
+                let rest = rest.trim_start();
+                let end = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+                if end > 0 && !names.contains(&&rest[..end]) {
+                    names.push(&rest[..end]);
+                }
+            }
+        }
+    }
+    names
+}
+
+/// Returns true if `name` appears in `code` on its own rather than as part of a longer name.
+fn mentions_name(code: &str, name: &str) -> bool {
+    code.match_indices(name).any(|(start, _)| {
+        let before = code[..start].chars().next_back();
+        let after = code[start + name.len()..].chars().next();
+        !before.map(is_name_char).unwrap_or(false) && !after.map(is_name_char).unwrap_or(false)
+    })
+}
+
+/// Returns true if `code` contains something like `name = ...` or `name .= ...`.
+fn assigns_variable(code: &str, name: &str) -> bool {
+    for (start, _) in code.match_indices(name) {
+        let before = code[..start].chars().next_back();
+        // Skip matches which are part of a longer name or a field of something else.
+        if before.map(|c| is_name_char(c) || c == '.').unwrap_or(false) {
+            continue;
+        }
+        let after = &code[start + name.len()..];
+        if after.starts_with(is_name_char) {
+            continue;
+        }
+        let after = after.trim_start_matches(|c| c == ' ' || c == '\t');
+        if after.starts_with(".=") || (after.starts_with('=') && !after.starts_with("==")) {
+            return true;
+        }
+    }
+    false
+}
@@ -0,0 +1,238 @@
+//! Libraries can ship a `migrations.yaml` file describing how their modules changed between
+//! versions so that patches saved with older versions of the library can still be loaded. The file
+//! looks like this:
+//!
+//! ```yaml
+//! 0.4.0:
+//!   Envelope:
+//!     old_save_id: 3
+//!     renamed_controls:
+//!       attack: attack_time
+//!     added_controls:
+//!       curve:
+//!     removed_controls:
+//!       legacy_mode: OptionChoice
+//!         index: 2
+//!         options:
+//!           Off:
+//!           On:
+//!     changed_ranges:
+//!       attack_time:
+//!         min: 0
+//!         max: 10
+//! ```
+//!
+//! Each top level entry is a version of the library and describes the changes made in that
+//! version, so it is applied to patches saved with any earlier version. Module names are the names
+//! the modules have in the current version. Control names in `changed_ranges` and `added_controls`
+//! and the second half of `renamed_controls` are names as of the version the entry is for. The
+//! `index` of a removed control is the position it had in the list of controls before it was
+//! removed and the rest of its entry is the definition it had, in the same format as in the
+//! `.module.yaml` file. The range given in `changed_ranges` is the range the control had before.
+
+use crate::{
+    engine::controls::{self, AnyControl},
+    registry::{module_template::ModuleTemplate, yaml::YamlNode},
+};
+use shared_util::Version;
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+struct ModuleMigration {
+    old_save_id: Option<usize>,
+    /// First field is the old name, second field is the new name.
+    renamed_controls: Vec<(String, String)>,
+    added_controls: Vec<String>,
+    /// First field is the index the control had, second field is its old definition.
+    removed_controls: Vec<(usize, (String, AnyControl))>,
+    changed_ranges: Vec<(String, (f32, f32))>,
+}
+
+#[derive(Debug)]
+struct MigrationStep {
+    version: Version,
+    modules: HashMap<String, ModuleMigration>,
+}
+
+/// All the migrations a library provides.
+#[derive(Debug, Default)]
+pub struct LibraryMigrations {
+    /// Sorted from oldest to newest.
+    steps: Vec<MigrationStep>,
+}
+
+/// Describes a control the way it was stored in a patch saved with an older version of a module.
+pub enum OldControl {
+    /// The control still exists, `index` is its position in the current version of the module.
+    Current {
+        index: usize,
+        /// The range the control had when the patch was saved, if it has changed since then.
+        old_range: Option<(f32, f32)>,
+    },
+    /// The control has since been removed. Its data should be read using this definition and then
+    /// discarded.
+    Removed(AnyControl),
+}
+
+fn parse_module_migration(yaml: &YamlNode) -> Result<ModuleMigration, String> {
+    let mut migration = ModuleMigration::default();
+    for child in &yaml.children {
+        match &child.name[..] {
+            "old_save_id" => {
+                migration.old_save_id = Some(child.parse_ranged(Some(0), Some(0xFFFF))?);
+            }
+            "renamed_controls" => {
+                for rename in &child.children {
+                    let new_name = rename.value.trim().to_owned();
+                    migration
+                        .renamed_controls
+                        .push((rename.name.clone(), new_name));
+                }
+            }
+            "added_controls" => {
+                for added in &child.children {
+                    migration.added_controls.push(added.name.clone());
+                }
+            }
+            "removed_controls" => {
+                for removed in &child.children {
+                    let index = removed.unique_child("index")?.parse()?;
+                    let control = controls::from_yaml(removed)?;
+                    migration.removed_controls.push((index, control));
+                }
+                migration.removed_controls.sort_by_key(|(index, _)| *index);
+            }
+            "changed_ranges" => {
+                for changed in &child.children {
+                    let min = changed.unique_child("min")?.parse()?;
+                    let max = changed.unique_child("max")?.parse_ranged(Some(min), None)?;
+                    migration
+                        .changed_ranges
+                        .push((changed.name.clone(), (min, max)));
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "ERROR: {} is not a valid kind of migration.",
+                    child.full_name
+                ))
+            }
+        }
+    }
+    Ok(migration)
+}
+
+pub(super) fn parse_migrations(yaml: &YamlNode) -> Result<LibraryMigrations, String> {
+    let mut steps = Vec::new();
+    for step in &yaml.children {
+        let version = step
+            .name
+            .parse()
+            .map_err(|_| format!("ERROR: {} is not a valid version.", step.full_name))?;
+        let mut modules = HashMap::new();
+        for module in &step.children {
+            modules.insert(module.name.clone(), parse_module_migration(module)?);
+        }
+        steps.push(MigrationStep { version, modules });
+    }
+    steps.sort_by_key(|step| step.version);
+    Ok(LibraryMigrations { steps })
+}
+
+impl LibraryMigrations {
+    /// Returns the migrations for a particular module that need to be undone to get to how it was
+    /// in `version`, newest first.
+    fn steps_since<'a>(
+        &'a self,
+        module_name: &'a str,
+        version: Version,
+    ) -> impl Iterator<Item = (Version, &'a ModuleMigration)> + 'a {
+        self.steps
+            .iter()
+            .rev()
+            .take_while(move |step| step.version > version)
+            .filter_map(move |step| {
+                step.modules
+                    .get(module_name)
+                    .map(|migration| (step.version, migration))
+            })
+    }
+
+    /// Returns every save id modules used to have in older versions of the library. New modules
+    /// should not use these, or patches saved with those versions would load the wrong module.
+    pub fn old_save_ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.steps
+            .iter()
+            .flat_map(|step| step.modules.values())
+            .filter_map(|migration| migration.old_save_id)
+    }
+
+    /// Returns the save id the module had in the specified version of the library.
+    pub fn save_id_at(&self, template: &ModuleTemplate, version: Version) -> usize {
+        let mut save_id = template.save_id;
+        for (_, migration) in self.steps_since(&template.module_name, version) {
+            save_id = migration.old_save_id.unwrap_or(save_id);
+        }
+        save_id
+    }
+
+    /// Returns the controls the module had in the specified version of the library, in the order
+    /// they were saved and along with the names they had back then. A human readable description
+    /// of each change is added to `changes`, without adding the same description twice.
+    pub fn old_control_layout(
+        &self,
+        template: &ModuleTemplate,
+        version: Version,
+        changes: &mut Vec<String>,
+    ) -> Vec<(String, OldControl)> {
+        let mut layout: Vec<(String, OldControl)> = template
+            .default_controls
+            .iter()
+            .enumerate()
+            .map(|(index, (name, _))| {
+                let old_range = None;
+                (name.clone(), OldControl::Current { index, old_range })
+            })
+            .collect();
+        let mut note = |change: String| {
+            let change = format!("{}: {}", template.label, change);
+            if !changes.contains(&change) {
+                changes.push(change);
+            }
+        };
+        for (step_version, migration) in self.steps_since(&template.module_name, version) {
+            for (name, range) in &migration.changed_ranges {
+                for (other_name, control) in &mut layout {
+                    if let OldControl::Current { old_range, .. } = control {
+                        if other_name == name {
+                            *old_range = Some(*range);
+                        }
+                    }
+                }
+                note(format!("the range of {} changed in {}", name, step_version));
+            }
+            for name in &migration.added_controls {
+                layout.retain(|(other_name, _)| other_name != name);
+                note(format!(
+                    "{} was added in {} and has its default value",
+                    name, step_version
+                ));
+            }
+            for (old_name, new_name) in &migration.renamed_controls {
+                for (other_name, _) in &mut layout {
+                    if other_name == new_name {
+                        *other_name = old_name.clone();
+                    }
+                }
+                note(format!("{} was renamed to {}", old_name, new_name));
+            }
+            for (index, (name, control)) in &migration.removed_controls {
+                let index = (*index).min(layout.len());
+                let old_control = OldControl::Removed(control.deep_clone());
+                layout.insert(index, (name.clone(), old_control));
+                note(format!("{} was removed in {}", name, step_version));
+            }
+        }
+        layout
+    }
+}
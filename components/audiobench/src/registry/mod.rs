@@ -1,7 +1,16 @@
 mod base;
 pub mod library_preload;
+mod library_watcher;
+pub mod lint;
+pub mod migration;
 pub mod module_template;
+pub mod patch_metadata;
+pub mod recovery;
 pub mod save_data;
+pub mod scaffold;
+pub mod settings;
+pub mod text_patch;
+pub mod theme;
 pub mod update_check;
 pub mod yaml;
 
@@ -6,6 +6,7 @@ use crate::{
     gui::module_widgets::WidgetOutline,
     registry::yaml::YamlNode,
 };
+use shared_util::Version;
 use std::collections::HashMap;
 
 pub(super) fn create_module_template_from_yaml(
@@ -97,9 +98,19 @@ pub(super) fn create_module_template_from_yaml(
 
         default_controls: controls,
         outputs,
+        placeholder: None,
     })
 }
 
+/// Describes a module that was used in a patch but could not be found when loading it.
+#[derive(Debug, Clone)]
+pub struct PlaceholderInfo {
+    /// The version of the library the patch was saved with.
+    pub lib_version: Version,
+    /// False if the whole library is missing, true if only the module is.
+    pub lib_installed: bool,
+}
+
 #[derive(Debug)]
 pub struct ModuleTemplate {
     pub lib_name: String,
@@ -115,4 +126,48 @@ pub struct ModuleTemplate {
     /// First field is code name, second field is control.
     pub default_controls: Vec<(String, AnyControl)>,
     pub outputs: Vec<ep::IOJack>,
+    /// Set for templates which stand in for modules that could not be found when loading a patch.
+    /// Modules using them do nothing, but are saved again unchanged.
+    pub placeholder: Option<PlaceholderInfo>,
+}
+
+impl ModuleTemplate {
+    pub fn new_placeholder(lib_name: String, save_id: usize, info: PlaceholderInfo) -> Self {
+        let tooltip = if info.lib_installed {
+            format!(
+                concat!(
+                    "This module is from version {} of {}, the installed version does not ",
+                    "have it. It does nothing but will be kept when the patch is saved"
+                ),
+                info.lib_version, lib_name
+            )
+        } else {
+            format!(
+                concat!(
+                    "This module is from the library {}, which is not installed. It does ",
+                    "nothing but will be kept when the patch is saved"
+                ),
+                lib_name
+            )
+        };
+        Self {
+            label: format!("Missing ({})", lib_name),
+            lib_name,
+            module_name: "Placeholder".to_owned(),
+            save_id,
+
+            category: "Missing".to_owned(),
+            tooltip,
+            size: (3, 2),
+            widget_outlines: Vec::new(),
+
+            default_controls: Vec::new(),
+            outputs: Vec::new(),
+            placeholder: Some(info),
+        }
+    }
+
+    pub fn is_placeholder(&self) -> bool {
+        self.placeholder.is_some()
+    }
 }
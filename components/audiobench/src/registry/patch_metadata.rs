@@ -0,0 +1,276 @@
+//! Information about a patch which is not needed to play it, like who made it and when. It is
+//! stored separately from the note graph so that it can be edited without saving the graph again.
+//! In text patches it is written as top level keys next to the name:
+//!
+//! ```yaml
+//! # Audiobench patch
+//! name: Bass
+//! author: Someone
+//! description: "A warm bass, try it with the filter wide open."
+//! category: Bass
+//! tags:
+//!   - warm
+//!   - analog
+//! created: 1600000000
+//! modified: 1600000000
+//! engine_version: 0.2.0
+//! ```
+//!
+//! Library versions are not written there since the `libraries` entry of the graph already
+//! contains them.
+
+use crate::{
+    config::ENGINE_VERSION,
+    engine::parts as ep,
+    registry::{text_patch::quoted, yaml::YamlNode, Registry},
+};
+use shared_util::{
+    mini_serde::{MiniDes, MiniSer},
+    Version,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Top level keys of text patches which belong to the metadata rather than the graph.
+pub const TEXT_KEYS: &[&str] = &[
+    "author",
+    "description",
+    "category",
+    "tags",
+    "created",
+    "modified",
+    "engine_version",
+];
+
+/// `MiniSer::str` can only store strings shorter than this many bytes.
+const MAX_TEXT_LEN: usize = 0xFFFF;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PatchMetadata {
+    pub author: String,
+    pub description: String,
+    pub category: String,
+    pub tags: Vec<String>,
+    /// Seconds since the Unix epoch, 0 if unknown.
+    pub created: u64,
+    /// Seconds since the Unix epoch, 0 if unknown.
+    pub modified: u64,
+    /// Version of Audiobench the patch was last saved with, None if it was saved before this was
+    /// recorded.
+    pub engine_version: Option<Version>,
+    /// Versions of the libraries whose modules the patch uses.
+    pub library_versions: Vec<(String, Version)>,
+}
+
+impl PatchMetadata {
+    /// Updates the timestamps and versions after the graph of the patch has been saved.
+    pub fn update_on_save(&mut self, graph: &ep::ModuleGraph, registry: &Registry) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        if self.created == 0 {
+            self.created = now;
+        }
+        self.modified = now;
+        self.engine_version = Some(ENGINE_VERSION);
+        self.library_versions = used_libraries(graph, registry);
+    }
+
+    /// Returns an error if any of the text is too long to be saved.
+    pub fn check_text_lengths(&self) -> Result<(), String> {
+        let fields = [
+            ("author", &self.author),
+            ("description", &self.description),
+            ("category", &self.category),
+        ];
+        let tags = self.tags.iter().map(|tag| ("tag", tag));
+        for (field, value) in fields.iter().cloned().chain(tags) {
+            if value.len() >= MAX_TEXT_LEN {
+                return Err(format!(
+                    "ERROR: The {} is too long, it must be shorter than {} bytes.",
+                    field, MAX_TEXT_LEN
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Splits `text` on commas, which is how tags are entered in the patch browser.
+    pub fn set_tags_from_text(&mut self, text: &str) {
+        self.tags = text
+            .split(',')
+            .map(|tag| tag.trim().to_owned())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+    }
+
+    pub fn serialize(&self, ser: &mut MiniSer) {
+        ser.str(&self.author);
+        ser.str(&self.description);
+        ser.str(&self.category);
+        ser.varint(self.tags.len());
+        for tag in &self.tags {
+            ser.str(tag);
+        }
+        ser.varint(self.created as usize);
+        ser.varint(self.modified as usize);
+        ser.bool(self.engine_version.is_some());
+        if let Some(version) = self.engine_version {
+            ser.version(version);
+        }
+        ser.varint(self.library_versions.len());
+        for (name, version) in &self.library_versions {
+            ser.str(name);
+            ser.version(*version);
+        }
+    }
+
+    pub fn deserialize(des: &mut MiniDes) -> Result<Self, ()> {
+        let author = des.str()?;
+        let description = des.str()?;
+        let category = des.str()?;
+        let mut tags = Vec::new();
+        for _ in 0..des.varint()? {
+            tags.push(des.str()?);
+        }
+        let created = des.varint()? as u64;
+        let modified = des.varint()? as u64;
+        let engine_version = if des.bool()? {
+            Some(des.version()?)
+        } else {
+            None
+        };
+        let mut library_versions = Vec::new();
+        for _ in 0..des.varint()? {
+            library_versions.push((des.str()?, des.version()?));
+        }
+        Ok(Self {
+            author,
+            description,
+            category,
+            tags,
+            created,
+            modified,
+            engine_version,
+            library_versions,
+        })
+    }
+
+    /// Writes the top level keys for a text patch, leaving out anything which is empty.
+    pub fn write_text(&self) -> String {
+        let mut text = String::new();
+        let fields = [
+            ("author", &self.author),
+            ("description", &self.description),
+            ("category", &self.category),
+        ];
+        for (key, value) in fields.iter() {
+            if !value.is_empty() {
+                text.push_str(&format!("{}: {}\n", key, quoted(value)));
+            }
+        }
+        if !self.tags.is_empty() {
+            text.push_str("tags:\n");
+            for tag in &self.tags {
+                text.push_str(&format!("  - {}\n", quoted(tag)));
+            }
+        }
+        if self.created != 0 {
+            text.push_str(&format!("created: {}\n", self.created));
+        }
+        if self.modified != 0 {
+            text.push_str(&format!("modified: {}\n", self.modified));
+        }
+        if let Some(version) = self.engine_version {
+            text.push_str(&format!("engine_version: {}\n", version));
+        }
+        text
+    }
+
+    /// Reads the keys written by `write_text` from the top level of a text patch. Library
+    /// versions are read from its `libraries` entry.
+    pub fn from_text_yaml(yaml: &YamlNode) -> Result<Self, String> {
+        let mut metadata = Self::default();
+        for child in &yaml.children {
+            let text = |node: &YamlNode, value: &str| {
+                if value.len() >= MAX_TEXT_LEN {
+                    Err(format!("ERROR: {} is too long.", node.full_name))
+                } else {
+                    Ok(value.to_owned())
+                }
+            };
+            match &child.name[..] {
+                "author" => metadata.author = text(child, &child.value)?,
+                "description" => metadata.description = text(child, &child.value)?,
+                "category" => metadata.category = text(child, &child.value)?,
+                "tags" => {
+                    // Plain list items are read as nodes named after them.
+                    for tag in &child.children {
+                        metadata.tags.push(text(tag, &tag.name)?);
+                    }
+                }
+                "created" => metadata.created = child.parse()?,
+                "modified" => metadata.modified = child.parse()?,
+                "engine_version" => metadata.engine_version = Some(child.parse()?),
+                "libraries" => {
+                    for lib in &child.children {
+                        metadata
+                            .library_versions
+                            .push((lib.name.clone(), lib.parse()?));
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(metadata)
+    }
+}
+
+/// Returns the name and version of each library `graph` uses modules from. Libraries with modules
+/// which are installed come first and use the installed version. Libraries which are only used by
+/// placeholders come after them and use the version the first of those placeholders is from, text
+/// patches store the version of each placeholder separately.
+pub fn used_libraries(graph: &ep::ModuleGraph, registry: &Registry) -> Vec<(String, Version)> {
+    let mut libs: Vec<(String, Version)> = Vec::new();
+    for placeholders in &[false, true] {
+        for module in graph.borrow_modules() {
+            let module = module.borrow();
+            let template = module.template.borrow();
+            if template.is_placeholder() != *placeholders
+                || libs.iter().any(|(name, _)| name == &template.lib_name)
+            {
+                continue;
+            }
+            let version = if let Some(info) = &template.placeholder {
+                info.lib_version
+            } else if let Some(info) = registry.borrow_library_info(&template.lib_name) {
+                info.version
+            } else {
+                continue;
+            };
+            libs.push((template.lib_name.clone(), version));
+        }
+    }
+    libs
+}
+
+/// Formats seconds since the Unix epoch as a date like 2020-09-13, in UTC.
+pub fn format_date(timestamp: u64) -> String {
+    // Converts days since the epoch to a date in the proleptic Gregorian calendar, which repeats
+    // every 400 years (146097 days.) Years are counted from March so that leap days come last.
+    let days = (timestamp / 86400) as i64 + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{}-{:02}-{:02}", year, month, day)
+}
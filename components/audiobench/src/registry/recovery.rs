@@ -0,0 +1,207 @@
+//! Autosaves of the patch being edited so that unsaved changes survive a crash. Each instance of
+//! Audiobench has a random id which is stored in the plugin state, see `write_state`, and
+//! periodically writes its current patch to `User/recovery/<id>.abrecovery` in the library folder.
+//! The file is deleted when the instance closes normally, so if the file for the id in a restored
+//! plugin state exists, it was left behind by a crash. Until the user restores or discards such a
+//! leftover, the instance autosaves to `<id>.new.abrecovery` instead, which takes the place of the
+//! leftover once it has been dealt with.
+
+use crate::{
+    engine::parts as ep,
+    registry::{save_data::Patch, Registry},
+};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+pub const RECOVERY_EXTENSION: &str = ".abrecovery";
+/// Ids used by instances running in this process. Hosts copy the plugin state when an instance is
+/// duplicated, and both copies must not use the same file.
+static CLAIMED_IDS: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+/// Plugin states start with this, the id in hex and a newline, followed by the current patch.
+/// States saved before recovery ids existed only contain the patch.
+const STATE_PREFIX: &str = "recovery ";
+
+/// Splits a plugin state written by `RecoverySlot::write_state` into the recovery id and the
+/// patch.
+pub fn split_state(state: &[u8]) -> (Option<u64>, &[u8]) {
+    if let Some(rest) = state.strip_prefix(STATE_PREFIX.as_bytes()) {
+        if let Some(newline) = rest.iter().position(|byte| *byte == b'\n') {
+            let id = std::str::from_utf8(&rest[..newline])
+                .ok()
+                .and_then(|id| u64::from_str_radix(id, 16).ok());
+            if let Some(id) = id {
+                return (Some(id), &rest[newline + 1..]);
+            }
+        }
+    }
+    (None, state)
+}
+
+fn remove_file(path: &Path) {
+    if let Err(err) = fs::remove_file(path) {
+        if err.kind() != ErrorKind::NotFound {
+            eprintln!(
+                "WARNING: Failed to delete recovery file {}, caused by:\nERROR: {}",
+                path.to_string_lossy(),
+                err
+            );
+        }
+    }
+}
+
+pub struct RecoverySlot {
+    folder: PathBuf,
+    id: u64,
+    /// True while `<id>.abrecovery` contains a patch from a crash which the user has not restored
+    /// or discarded yet.
+    has_leftover: bool,
+}
+
+impl RecoverySlot {
+    pub fn new(library_path: &Path) -> Self {
+        let mut claimed = CLAIMED_IDS.lock().unwrap();
+        let mut id = rand::random();
+        while claimed.contains(&id) {
+            id = rand::random();
+        }
+        claimed.push(id);
+        Self {
+            folder: library_path.join("User").join("recovery"),
+            id,
+            has_leftover: false,
+        }
+    }
+
+    fn path_with_suffix(&self, suffix: &str) -> PathBuf {
+        self.folder
+            .join(format!("{:016x}{}{}", self.id, suffix, RECOVERY_EXTENSION))
+    }
+
+    /// Where the current patch is autosaved.
+    fn session_path(&self) -> PathBuf {
+        if self.has_leftover {
+            self.path_with_suffix(".new")
+        } else {
+            self.path_with_suffix("")
+        }
+    }
+
+    /// Returns the plugin state for `patch_data`, which records the id of this slot so that it can
+    /// be found again with `restore_id`.
+    pub fn write_state(&self, patch_data: &str) -> String {
+        format!("{}{:016x}\n{}", STATE_PREFIX, self.id, patch_data)
+    }
+
+    /// Switches to the id from a restored plugin state and returns the patch left behind by a
+    /// crash of the instance the state belonged to, if there is one. It stays on disk until
+    /// `release_leftover` is called. Nothing happens if another instance in this process already
+    /// uses the id or a leftover is still waiting to be dealt with.
+    pub fn restore_id(&mut self, id: u64, registry: &Registry) -> Option<Result<Patch, String>> {
+        if id == self.id || self.has_leftover {
+            return None;
+        }
+        let mut claimed = CLAIMED_IDS.lock().unwrap();
+        if claimed.contains(&id) {
+            return None;
+        }
+        self.clear();
+        claimed.retain(|other| *other != self.id);
+        claimed.push(id);
+        drop(claimed);
+        self.id = id;
+
+        let path = self.path_with_suffix("");
+        // If the instance crashed again before an older leftover was dealt with, the newer
+        // autosave is the one worth keeping.
+        let newer = self.path_with_suffix(".new");
+        if newer.exists() {
+            if let Err(err) = fs::rename(&newer, &path) {
+                eprintln!(
+                    "WARNING: Failed to move recovery file {}, caused by:\nERROR: {}",
+                    newer.to_string_lossy(),
+                    err
+                );
+            }
+        }
+        if !path.exists() {
+            return None;
+        }
+        self.has_leftover = true;
+        let result = fs::read(&path)
+            .map_err(|err| format!("ERROR: {}", err))
+            .and_then(|data| Patch::load_readable("Recovered".to_owned(), &data, registry))
+            .map_err(|err| {
+                format!(
+                    "ERROR: Failed to read recovered patch from {}, caused by:\n{}",
+                    path.to_string_lossy(),
+                    err
+                )
+            });
+        Some(result)
+    }
+
+    /// Deletes the patch returned by `restore_id` after the user has restored or discarded it.
+    pub fn release_leftover(&mut self) {
+        if !self.has_leftover {
+            return;
+        }
+        let path = self.path_with_suffix("");
+        remove_file(&path);
+        self.has_leftover = false;
+        // Changes autosaved in the meantime move to the usual file.
+        let newer = self.path_with_suffix(".new");
+        if newer.exists() {
+            if let Err(err) = fs::rename(&newer, &path) {
+                eprintln!(
+                    "WARNING: Failed to move recovery file {}, caused by:\nERROR: {}",
+                    newer.to_string_lossy(),
+                    err
+                );
+            }
+        }
+    }
+
+    /// Writes `graph` to the recovery file along with the name and metadata of `current_patch`.
+    pub fn save(
+        &self,
+        graph: &ep::ModuleGraph,
+        current_patch: &Patch,
+        registry: &Registry,
+    ) -> Result<(), String> {
+        let path = self.session_path();
+        let mut patch = Patch::new(path.clone());
+        patch.set_name(current_patch.borrow_name().to_owned());
+        patch.set_metadata(current_patch.borrow_metadata().clone());
+        patch.save_note_graph(graph, registry);
+        let write_err = |err: std::io::Error| {
+            format!(
+                "ERROR: Failed to write recovery file {}, caused by:\nERROR: {}",
+                path.to_string_lossy(),
+                err
+            )
+        };
+        fs::create_dir_all(&self.folder).map_err(write_err)?;
+        patch.write().map_err(write_err)
+    }
+
+    /// Deletes the autosave of the current patch, used when there are no unsaved changes left to
+    /// lose. A leftover is kept until `release_leftover` is called.
+    pub fn clear(&self) {
+        remove_file(&self.session_path());
+    }
+}
+
+impl Drop for RecoverySlot {
+    fn drop(&mut self) {
+        // A leftover which was not dealt with is offered again next time.
+        self.clear();
+        CLAIMED_IDS
+            .lock()
+            .unwrap()
+            .retain(|other| *other != self.id);
+    }
+}
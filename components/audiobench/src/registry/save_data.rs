@@ -1,16 +1,96 @@
 use crate::{
     engine::{
-        controls::{AutomationSource, Control},
+        controls::{AnyControl, AutomationSource, PlaceholderControl},
+        midi_bindings::{MidiBinding, MidiCurve, PlaceholderMidiBinding},
         parts as ep,
     },
-    registry::Registry,
+    registry::{
+        migration::OldControl,
+        module_template::{ModuleTemplate, PlaceholderInfo},
+        patch_metadata::PatchMetadata,
+        text_patch, Registry,
+    },
 };
 use shared_util::{
     mini_serde::{MiniDes, MiniSer},
     prelude::*,
+    Version,
 };
+use std::collections::HashMap;
 use std::io::{self, Write};
 use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+/// Placeholder modules get an output for every output index a wire uses. Real modules have
+/// nowhere near this many, so larger indexes are treated as corrupt data instead of allocating
+/// outputs for them.
+const MAX_PLACEHOLDER_OUTPUTS: usize = 0x400;
+
+/// Makes sure a placeholder template has an output at `index`. We do not know what outputs the
+/// missing module had, so outputs are added as they are used with the type given by `typ`.
+pub(super) fn add_placeholder_outputs(
+    template: &mut ModuleTemplate,
+    index: usize,
+    typ: ep::JackType,
+    registry: &Registry,
+) -> Result<(), ()> {
+    if index >= MAX_PLACEHOLDER_OUTPUTS {
+        return Err(());
+    }
+    while index >= template.outputs.len() {
+        let icon = registry.lookup_icon(typ.icon_name()).ok_or(())?;
+        let output = ep::IOJack::placeholder(typ, icon, template.outputs.len());
+        template.outputs.push(output);
+    }
+    Ok(())
+}
+
+/// Tags of the sections patch data is split into from format 4 on. Data which older versions
+/// should be able to ignore goes in a section with a new tag, or at the end of an existing section
+/// since whatever a reader does not read from a section is skipped.
+mod part {
+    pub const LIBRARIES: usize = 0;
+    pub const MODULES: usize = 1;
+    pub const CONTROLS: usize = 2;
+    pub const MIDI_BINDINGS: usize = 3;
+    pub const SEED: usize = 4;
+    pub const KNOWN: &[usize] = &[LIBRARIES, MODULES, CONTROLS, MIDI_BINDINGS, SEED];
+}
+
+/// Tags of the sections which can follow the checked section holding the graph, from format 4
+/// on. They are kept out of it so that they can be changed without saving the graph again.
+/// Readers from before a section existed ignore it along with anything else after the graph.
+mod trailer {
+    pub const METADATA: usize = 0;
+}
+
+/// Gives the data for each part of a patch. Before format 4 the parts were stored one after
+/// another, after that each one is in its own tagged section. Missing sections are empty.
+enum PatchReader {
+    Flat(MiniDes),
+    Sections(HashMap<usize, MiniDes>),
+}
+
+impl PatchReader {
+    fn part(&mut self, tag: usize) -> &mut MiniDes {
+        match self {
+            Self::Flat(des) => des,
+            Self::Sections(sections) => sections
+                .entry(tag)
+                .or_insert_with(|| MiniDes::start(Vec::new())),
+        }
+    }
+}
+
+/// Error produced while restoring a patch. Converting from `()` gives the error used when the data
+/// does not make sense, so that `?` can be used on the results from `MiniDes`.
+struct RestoreError(String);
+
+impl From<()> for RestoreError {
+    fn from(_: ()) -> Self {
+        Self("ERROR: Patch data is corrupt.".to_owned())
+    }
+}
 
 #[derive(Debug, Clone)]
 enum PatchSource {
@@ -23,18 +103,37 @@ pub struct Patch {
     source: PatchSource,
     name: String,
     exists_on_disk: bool,
+    // The format version `data` was written in.
+    format_version: u8,
+    // The oldest format version whose readers can understand `data`, only stored from format 4 on.
+    readable_from: u8,
     data: Vec<u8>,
+    // Set if the patch is saved as text, in which case this is used instead of `data`. Contains
+    // everything except the name and metadata, see `text_patch`.
+    text: Option<String>,
+    metadata: PatchMetadata,
+    // Where the patch is shown in the patch browser, see `set_location`.
+    lib_name: String,
+    folders: Vec<String>,
 }
 
 impl Patch {
-    const FORMAT_VERSION: u8 = 2;
+    const FORMAT_VERSION: u8 = 4;
+    /// Should only change if a future format can no longer be read by skipping sections.
+    const READABLE_FROM: u8 = 4;
 
     pub fn new(save_path: PathBuf) -> Self {
         Self {
             name: "Unnamed".to_owned(),
             source: PatchSource::Writable(save_path),
             exists_on_disk: false,
+            format_version: Self::FORMAT_VERSION,
+            readable_from: Self::READABLE_FROM,
             data: Vec::new(),
+            text: None,
+            metadata: Default::default(),
+            lib_name: String::new(),
+            folders: Vec::new(),
         }
     }
 
@@ -43,7 +142,13 @@ impl Patch {
             name: Default::default(),
             source,
             exists_on_disk: true,
+            format_version: Self::FORMAT_VERSION,
+            readable_from: Self::READABLE_FROM,
             data: Vec::new(),
+            text: None,
+            metadata: Default::default(),
+            lib_name: String::new(),
+            folders: Vec::new(),
         };
         patch.deserialize(data)?;
         Ok(patch)
@@ -83,133 +188,515 @@ impl Patch {
         &self.name
     }
 
+    /// Sets the library the patch is from and the folders it is in, relative to the `patches`
+    /// folder of that library if it is in one.
+    pub fn set_location(&mut self, lib_name: String, folders: Vec<String>) {
+        self.lib_name = lib_name;
+        self.folders = folders;
+    }
+
+    pub fn borrow_lib_name(&self) -> &str {
+        &self.lib_name
+    }
+
+    pub fn borrow_folders(&self) -> &[String] {
+        &self.folders
+    }
+
+    pub fn borrow_metadata(&self) -> &PatchMetadata {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: PatchMetadata) {
+        self.metadata = metadata;
+    }
+
+    /// When the patch was last saved, in seconds since the Unix epoch. Patches saved before this
+    /// was recorded use the modification time of their file instead, or 0 if there is none.
+    pub fn get_modified_time(&self) -> u64 {
+        if self.metadata.modified != 0 {
+            return self.metadata.modified;
+        }
+        if let PatchSource::Writable(path) = &self.source {
+            std::fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs())
+                .unwrap_or(0)
+        } else {
+            0
+        }
+    }
+
+    /// Binary patches from before format 4 have nowhere to store metadata, so it is lost when
+    /// they are written unless the graph is saved again first.
+    pub fn can_store_metadata(&self) -> bool {
+        self.text.is_some() || self.format_version >= 4
+    }
+
     pub fn save_note_graph(&mut self, graph: &ep::ModuleGraph, registry: &Registry) {
-        let mut ser = MiniSer::new();
-        let mut ordered_lib_names = Vec::new();
-        let lib_data: Vec<_> = registry.borrow_library_infos().collect();
-        assert!(lib_data.len() < 0x100);
-        ser.note("Num libs: ");
-        ser.u8((lib_data.len() - 1) as _);
-        for (lib_name, lib_info) in lib_data {
-            if lib_name == "User" {
-                continue;
+        let mut libs: Vec<(String, Version)> = registry
+            .borrow_library_infos()
+            .filter(|(lib_name, _)| *lib_name != "User")
+            .map(|(lib_name, lib_info)| (lib_name.clone(), lib_info.version))
+            .collect();
+        // Placeholders have to be saved with the library version they were loaded with, which might
+        // be from a library that is not installed or differ from the installed version. A library
+        // can be listed more than once so that they do not change the version used by other
+        // modules.
+        for module in graph.borrow_modules() {
+            let module = module.borrow();
+            let template = module.template.borrow();
+            if let Some(info) = &template.placeholder {
+                let entry = (template.lib_name.clone(), info.lib_version);
+                if !libs.contains(&entry) {
+                    libs.push(entry);
+                }
             }
-            ordered_lib_names.push(lib_name.clone());
-            ser.str(lib_name);
-            ser.version(lib_info.version);
         }
-        let lib_index = |name: &String| {
-            ordered_lib_names
-                .iter()
-                .position(|other| other == name)
-                .unwrap() as u8
+        let lib_index = |template: &ModuleTemplate| {
+            let lib_name = &template.lib_name;
+            if let Some(info) = &template.placeholder {
+                let entry = (lib_name.clone(), info.lib_version);
+                libs.iter().position(|other| other == &entry).unwrap()
+            } else {
+                libs.iter()
+                    .position(|(other, _)| other == lib_name)
+                    .unwrap()
+            }
         };
-
         let ordered_modules = Vec::from(graph.borrow_modules());
-        assert!(ordered_modules.len() < 0x100);
-        ser.note("Num modules: ");
-        ser.u8(ordered_modules.len() as _);
         let mod_index = |rc: &Rc<_>| {
             ordered_modules
                 .iter()
                 .position(|other| Rc::ptr_eq(rc, other))
-                .unwrap() as u8
+                .unwrap()
         };
-        ser.note("Modules: ");
-        for module in graph.borrow_modules() {
-            let module = module.borrow();
-            let template = module.template.borrow();
-            ser.note("<lib ");
-            ser.u8(lib_index(&template.lib_name));
-            ser.note("save_id ");
-            ser.u8(template.save_id as _);
-            ser.note("x ");
-            ser.i32(module.pos.0 as _);
-            ser.note("y ");
-            ser.i32(module.pos.1 as _);
-            ser.note("> ");
-        }
-        ser.note("Module controls: ");
-        for module in graph.borrow_modules() {
-            let module = module.borrow();
-            for (index, control) in module.controls.iter().enumerate() {
-                ser.note(&format!("c{}: <", index));
-                let control_ptr = control.as_dyn_ptr();
-                let control = control_ptr.borrow();
-                for source in control.get_connected_automation() {
+
+        let mut ser = MiniSer::new();
+        ser.checked_section(|ser| {
+            ser.tagged_section(part::LIBRARIES, |ser| {
+                ser.note("Num libs: ");
+                ser.varint(libs.len());
+                for (lib_name, lib_version) in &libs {
+                    ser.str(lib_name);
+                    ser.version(*lib_version);
+                }
+            });
+            ser.tagged_section(part::MODULES, |ser| {
+                ser.note("Num modules: ");
+                ser.varint(ordered_modules.len());
+                for module in graph.borrow_modules() {
+                    let module = module.borrow();
+                    let template = module.template.borrow();
+                    ser.section(|ser| {
+                        ser.note("lib ");
+                        ser.varint(lib_index(&template));
+                        ser.note("save_id ");
+                        ser.varint(template.save_id);
+                        ser.note("x ");
+                        ser.ivarint(module.pos.0 as _);
+                        ser.note("y ");
+                        ser.ivarint(module.pos.1 as _);
+                    });
+                }
+            });
+            ser.tagged_section(part::CONTROLS, |ser| {
+                for module in graph.borrow_modules() {
+                    let module = module.borrow();
+                    ser.section(|ser| {
+                        ser.varint(module.controls.len());
+                        for (index, control) in module.controls.iter().enumerate() {
+                            ser.note(&format!("c{}: <", index));
+                            let control_ptr = control.as_dyn_ptr();
+                            let control = control_ptr.borrow();
+                            for source in control.get_connected_automation() {
+                                ser.bool(true);
+                                ser.varint(mod_index(&source.module));
+                                ser.varint(source.output_index);
+                            }
+                            ser.bool(false);
+                            // Each control is in its own section so that controls of modules
+                            // which are not installed can be skipped over.
+                            ser.section(|ser| control.serialize(ser));
+                            ser.note("> ");
+                        }
+                    });
+                }
+            });
+            ser.tagged_section(part::MIDI_BINDINGS, |ser| {
+                for binding in graph.borrow_midi_bindings() {
+                    let control = if let Some(control) = binding.get_control() {
+                        control
+                    } else {
+                        continue;
+                    };
+                    let range = control.borrow().range;
+                    ser.note("<");
                     ser.bool(true);
-                    ser.u8(mod_index(&source.module));
-                    ser.u4(source.output_index as _);
+                    ser.u7(binding.controller as _);
+                    ser.varint(mod_index(&binding.module));
+                    ser.varint(binding.control_index);
+                    ser.f32_in_range(binding.range.0, range.0, range.1);
+                    ser.f32_in_range(binding.range.1, range.0, range.1);
+                    ser.u2(binding.curve.ordinal());
+                    ser.note("> ");
+                }
+                for binding in graph.borrow_placeholder_midi_bindings() {
+                    ser.note("<");
+                    ser.bool(true);
+                    ser.u7(binding.controller as _);
+                    ser.varint(mod_index(&binding.module));
+                    ser.varint(binding.control_index);
+                    ser.u16(binding.raw_range.0);
+                    ser.u16(binding.raw_range.1);
+                    ser.u2(binding.curve.ordinal());
+                    ser.note("> ");
                 }
                 ser.bool(false);
-                control.serialize(&mut ser);
-                ser.note("> ");
-            }
-        }
+            });
+            ser.tagged_section(part::SEED, |ser| {
+                ser.note("Seed: ");
+                ser.u32(graph.get_seed());
+            });
+        });
+        self.format_version = Self::FORMAT_VERSION;
+        self.readable_from = Self::READABLE_FROM;
         self.data = ser.finish();
+        self.metadata.update_on_save(graph, registry);
+        // Patches which are already text stay text so that they keep working with version
+        // control.
+        if self.text.is_some() || registry.is_saving_text_patches() {
+            self.text = Some(text_patch::write_text_patch(graph, registry));
+        }
     }
 
+    /// Replaces the contents of `graph` with the contents of this patch. If the patch was saved
+    /// with older versions of the libraries it uses, it is upgraded using the migrations those
+    /// libraries provide and a description of everything that changed is returned. Modules which
+    /// cannot be found are replaced with placeholders, see `ModuleTemplate::new_placeholder`.
     pub fn restore_note_graph(
         &self,
         graph: &mut ep::ModuleGraph,
         registry: &Registry,
-    ) -> Result<(), ()> {
+    ) -> Result<Vec<String>, String> {
+        if let Some(text) = &self.text {
+            return text_patch::restore_text_patch(text, graph, registry);
+        }
+        self.restore_note_graph_impl(graph, registry)
+            .map_err(|err| err.0)
+    }
+
+    fn restore_note_graph_impl(
+        &self,
+        graph: &mut ep::ModuleGraph,
+        registry: &Registry,
+    ) -> Result<Vec<String>, RestoreError> {
+        let mut changes = Vec::new();
         let mut des = MiniDes::start(self.data.clone());
-        let mut lib_names = Vec::new();
-        for _ in 0..des.u8()? {
+        let mut reader = if self.format_version >= 4 {
+            let mut sections = HashMap::new();
+            des.checked_section(|des| {
+                while des.remaining_bits() > 0 {
+                    let (tag, section) = des.tagged_section(|tag, des| Ok((tag, des.rest())))?;
+                    sections.insert(tag, MiniDes::start_raw(section));
+                }
+                Ok::<_, ()>(())
+            })?;
+            PatchReader::Sections(sections)
+        } else {
+            PatchReader::Flat(des)
+        };
+        let newer = if let PatchReader::Sections(sections) = &reader {
+            sections.keys().any(|tag| !part::KNOWN.contains(tag))
+        } else {
+            false
+        };
+        if newer || self.format_version > Self::FORMAT_VERSION {
+            changes.push(
+                concat!(
+                    "Patch was saved by a newer version of Audiobench, anything this version does ",
+                    "not understand was left out"
+                )
+                .to_owned(),
+            );
+        }
+        let sectioned = self.format_version >= 4;
+        // Format 3 replaced fixed size counts and indexes, which limited patches to 255 modules
+        // and 16 outputs per module, with ones that grow as needed. It also records how much data
+        // each control takes up, which is what allows loading modules that are not installed.
+        let varints = self.format_version >= 3;
+        let index = |des: &mut MiniDes| {
+            if varints {
+                des.varint()
+            } else {
+                des.u8().map(usize::from)
+            }
+        };
+        // Formats from 4 on put each module in its own section so that newer versions can add
+        // data to them.
+        let module_section =
+            |des: &mut MiniDes, read: &mut dyn FnMut(&mut MiniDes) -> Result<(), RestoreError>| {
+                if sectioned {
+                    des.section(|des| read(des))
+                } else {
+                    read(des)
+                }
+            };
+
+        let des = reader.part(part::LIBRARIES);
+        let mut libs = Vec::new();
+        for _ in 0..index(des)? {
             let name = des.str()?;
-            lib_names.push(name);
-            // TODO: version checking. unimplemented!()
-            let _ver = des.version()?;
+            let version = des.version()?;
+            if let Some(info) = registry.borrow_library_info(&name) {
+                if version > info.version {
+                    changes.push(format!(
+                        concat!(
+                            "Patch was saved with version {} of library {} but version {} is ",
+                            "installed, some settings might not load correctly"
+                        ),
+                        version, name, info.version
+                    ));
+                }
+            }
+            libs.push((name, version));
         }
+
+        let des = reader.part(part::MODULES);
         let mut modules: Vec<Rcrc<ep::Module>> = Vec::new();
-        let num_modules = des.u8()?;
+        let mut layouts = Vec::new();
+        let mut placeholder_templates: HashMap<(String, usize, Version), Rcrc<ModuleTemplate>> =
+            HashMap::new();
+        let num_modules = index(des)?;
         for _ in 0..num_modules {
-            let lib_i = des.u8()? as usize;
-            if lib_i >= lib_names.len() {
-                return Err(());
-            }
-            let lib_name = &lib_names[lib_i];
-            let save_id = des.u8()? as usize;
-            let template = registry.borrow_template_by_serialized_id(&(lib_name.clone(), save_id));
-            let template = template.ok_or(())?;
-            let mut module = ep::Module::create(Rc::clone(template));
-            module.pos = (des.i32()? as _, des.i32()? as _);
-            // The controls are serialized later so we can deserialize them after we know what all
-            // the outputs of each module will be.
-            modules.push(rcrc(module));
+            module_section(des, &mut |des| {
+                let lib_i = index(des)?;
+                if lib_i >= libs.len() {
+                    return Err(().into());
+                }
+                let (lib_name, lib_version) = &libs[lib_i];
+                let save_id = index(des)?;
+                let id = (lib_name.clone(), save_id);
+                let template = if let Some(template) =
+                    registry.borrow_template_by_old_serialized_id(&id, *lib_version)
+                {
+                    let layout =
+                        registry.old_control_layout(&template.borrow(), *lib_version, &mut changes);
+                    layouts.push(layout.into_iter().map(|(_, control)| control).collect());
+                    Rc::clone(template)
+                } else if varints {
+                    // The layout is filled in once we know how many controls the module had.
+                    layouts.push(Vec::new());
+                    let lib_installed = registry.borrow_library_info(lib_name).is_some();
+                    if lib_installed {
+                        changes.push(format!(
+                            "A module from version {} of {} is missing",
+                            lib_version, lib_name
+                        ));
+                    } else {
+                        changes.push(format!("Library {} is not installed", lib_name));
+                    }
+                    let key = (id.0, id.1, *lib_version);
+                    let template = placeholder_templates.entry(key).or_insert_with(|| {
+                        let info = PlaceholderInfo {
+                            lib_version: *lib_version,
+                            lib_installed,
+                        };
+                        rcrc(ModuleTemplate::new_placeholder(
+                            lib_name.clone(),
+                            save_id,
+                            info,
+                        ))
+                    });
+                    Rc::clone(template)
+                } else {
+                    // Older formats do not record how much data each control takes up, so there
+                    // is no way to skip over the controls of a module we know nothing about.
+                    return Err(RestoreError(format!(
+                        concat!(
+                            "ERROR: This patch uses a module from version {} of {}, which is not ",
+                            "installed."
+                        ),
+                        lib_version, lib_name
+                    )));
+                };
+                let mut module = ep::Module::create(template);
+                module.pos = if varints {
+                    (des.ivarint()? as _, des.ivarint()? as _)
+                } else {
+                    (des.i32()? as _, des.i32()? as _)
+                };
+                // The controls are serialized later so we can deserialize them after we know what
+                // all the outputs of each module will be.
+                modules.push(rcrc(module));
+                Ok(())
+            })?;
         }
-        for i in 0..num_modules as usize {
-            let mut module = modules[i].borrow_mut();
-            for control in &mut module.controls {
-                let control_ptr = control.as_dyn_ptr();
-                let mut control = control_ptr.borrow_mut();
-                // Connect wires
-                while des.bool()? {
-                    let mod_i = des.u8()? as usize;
-                    if i == mod_i || mod_i >= modules.len() {
-                        return Err(());
+        changes.dedup();
+
+        let des = reader.part(part::CONTROLS);
+        for i in 0..num_modules {
+            module_section(des, &mut |des| {
+                if varints {
+                    let num_controls = index(des)?;
+                    // Every control takes up at least one bit, so a larger count can only come
+                    // from corrupt data.
+                    if num_controls > des.remaining_bits() {
+                        return Err(().into());
                     }
-                    let target_module = Rc::clone(&modules[mod_i]);
-                    let num_outs = target_module.borrow().template.borrow().outputs.len();
-                    let output_index = des.u4()? as usize;
-                    if output_index >= num_outs {
-                        return Err(());
+                    let mut module = modules[i].borrow_mut();
+                    if module.template.borrow().is_placeholder() {
+                        for index in 0..num_controls {
+                            let control = PlaceholderControl::default();
+                            module.controls.push(AnyControl::Placeholder(rcrc(control)));
+                            let old_range = None;
+                            layouts[i].push(OldControl::Current { index, old_range });
+                        }
+                    } else if num_controls != layouts[i].len() {
+                        return Err(().into());
                     }
-                    let output_type =
-                        target_module.borrow().template.borrow().outputs[output_index].get_type();
-                    let source = AutomationSource {
-                        module: target_module,
-                        output_index,
-                        output_type,
+                }
+                let module = modules[i].borrow();
+                let is_placeholder = module.template.borrow().is_placeholder();
+                for old_control in &layouts[i] {
+                    // Data for controls which have since been removed is read into a throwaway
+                    // copy.
+                    let removed_copy;
+                    let (control, old_range) = match old_control {
+                        OldControl::Current { index, old_range } => {
+                            (&module.controls[*index], *old_range)
+                        }
+                        OldControl::Removed(control) => {
+                            removed_copy = control.deep_clone();
+                            (&removed_copy, None)
+                        }
                     };
-                    control.connect_automation(source);
+                    // Controls which changed range are read using the range they had when the
+                    // patch was saved and then moved into the new range.
+                    let new_range = old_range.map(|old_range| swap_range(control, old_range));
+                    let control_ptr = control.as_dyn_ptr();
+                    let mut control_ref = control_ptr.borrow_mut();
+                    // Connect wires
+                    while des.bool()? {
+                        let mod_i = index(des)?;
+                        if i == mod_i || mod_i >= modules.len() {
+                            return Err(().into());
+                        }
+                        let target_module = Rc::clone(&modules[mod_i]);
+                        let output_index = if varints {
+                            des.varint()?
+                        } else {
+                            des.u4()? as usize
+                        };
+                        let acceptable = control_ref.acceptable_automation();
+                        let target_template = Rc::clone(&target_module.borrow().template);
+                        let mut target_template = target_template.borrow_mut();
+                        if target_template.is_placeholder() {
+                            let typ = acceptable.first().cloned().unwrap_or(ep::JackType::Audio);
+                            add_placeholder_outputs(
+                                &mut target_template,
+                                output_index,
+                                typ,
+                                registry,
+                            )?;
+                        }
+                        if output_index >= target_template.outputs.len() {
+                            return Err(().into());
+                        }
+                        let output_type = target_template.outputs[output_index].get_type();
+                        if !is_placeholder && !acceptable.contains(&output_type) {
+                            return Err(().into());
+                        }
+                        let source = AutomationSource {
+                            module: target_module,
+                            output_index,
+                            output_type,
+                        };
+                        control_ref.connect_automation(source);
+                    }
+                    if varints {
+                        des.section(|des| control_ref.deserialize(des))?;
+                    } else {
+                        control_ref.deserialize(des)?;
+                    }
+                    drop(control_ref);
+                    if let Some(new_range) = new_range {
+                        swap_range(control, new_range);
+                    }
                 }
-                control.deserialize(&mut des)?;
+                Ok(())
+            })?;
+        }
+        for template in placeholder_templates.values() {
+            let mut template = template.borrow_mut();
+            template.size.1 = template.size.1.max(template.outputs.len() as i32);
+        }
+
+        let des = reader.part(part::MIDI_BINDINGS);
+        let mut midi_bindings = Vec::new();
+        let mut placeholder_midi_bindings = Vec::new();
+        // Patches saved before MIDI bindings existed just end here. Either there are no bits left
+        // or there are only zeros used as padding, both of which mean there are no bindings.
+        while des.bool().unwrap_or(false) {
+            let controller = des.u7()? as usize;
+            let mod_i = index(des)?;
+            let module = Rc::clone(modules.get(mod_i).ok_or(())?);
+            let old_control_index = index(des)?;
+            if module.borrow().template.borrow().is_placeholder() {
+                if old_control_index >= module.borrow().controls.len() {
+                    return Err(().into());
+                }
+                placeholder_midi_bindings.push(PlaceholderMidiBinding {
+                    controller,
+                    module,
+                    control_index: old_control_index,
+                    raw_range: (des.u16()?, des.u16()?),
+                    curve: MidiCurve::from_ordinal(des.u2()?).ok_or(())?,
+                });
+                continue;
+            }
+            let (control_index, saved_range) = match layouts[mod_i].get(old_control_index) {
+                Some(OldControl::Current { index, old_range }) => (Some(*index), *old_range),
+                Some(OldControl::Removed(AnyControl::FloatInRange(control))) => {
+                    (None, Some(control.borrow().range))
+                }
+                _ => return Err(().into()),
+            };
+            let range = if let Some(index) = control_index {
+                match module.borrow().controls.get(index) {
+                    Some(AnyControl::FloatInRange(control)) => control.borrow().range,
+                    _ => return Err(().into()),
+                }
+            } else {
+                saved_range.unwrap()
+            };
+            let saved_range = saved_range.unwrap_or(range);
+            let binding_range = (
+                des.f32_in_range(saved_range.0, saved_range.1)?
+                    .clam(range.0, range.1),
+                des.f32_in_range(saved_range.0, saved_range.1)?
+                    .clam(range.0, range.1),
+            );
+            let curve = MidiCurve::from_ordinal(des.u2()?).ok_or(())?;
+            // Bindings to controls which have since been removed are dropped.
+            if let Some(control_index) = control_index {
+                let mut binding = MidiBinding::new(controller, module, control_index).ok_or(())?;
+                binding.range = binding_range;
+                binding.curve = curve;
+                midi_bindings.push(binding);
             }
         }
+
+        // Older patches do not have a seed. There are always less than 32 bits of padding so this
+        // will fail instead of reading garbage.
+        let seed = reader.part(part::SEED).u32().unwrap_or(0);
         graph.set_modules(modules);
-        Ok(())
+        graph.set_midi_bindings(midi_bindings);
+        graph.set_placeholder_midi_bindings(placeholder_midi_bindings);
+        graph.set_seed(seed);
+        Ok(changes)
     }
 
     pub fn write(&mut self) -> io::Result<()> {
@@ -240,34 +727,237 @@ impl Patch {
     }
 
     pub fn serialize(&self) -> String {
+        if let Some(text) = &self.text {
+            return format!(
+                "{}name: {}\n{}{}",
+                text_patch::HEADER,
+                text_patch::quoted(&self.name),
+                self.metadata.write_text(),
+                text
+            );
+        }
         let mut ser = MiniSer::new();
-        ser.u8(Self::FORMAT_VERSION);
+        // Data is only converted to the current format when it is saved again, so it might still
+        // be in the format it was loaded in.
+        ser.u8(self.format_version);
+        if self.format_version >= 4 {
+            ser.u8(self.readable_from);
+        }
         ser.str(&self.name);
         ser.blob(&self.data[..]);
+        // Anything after the data of older formats would be read as part of the graph, so their
+        // metadata is only kept once the graph is saved in the current format.
+        if self.format_version >= 4 && !self.data.is_empty() {
+            ser.tagged_section(trailer::METADATA, |ser| self.metadata.serialize(ser));
+        }
         let data = ser.finish();
         base64::encode_config(&data, base64::URL_SAFE_NO_PAD)
     }
 
+    /// Accepts both binary and text patches.
     pub fn deserialize(&mut self, data: &[u8]) -> Result<(), String> {
+        if text_patch::is_text_patch(data) {
+            let text = std::str::from_utf8(data)
+                .map_err(|_| "ERROR: Patch data is corrupt (text is not valid UTF-8.)")?;
+            let (name, metadata, body) = text_patch::split_header(text)?;
+            self.name = name;
+            self.metadata = metadata;
+            self.text = Some(body);
+            return Ok(());
+        }
+        self.text = None;
         let data = base64::decode_config(data, base64::URL_SAFE_NO_PAD)
             .map_err(|_| "ERROR: Patch data is corrupt (invalid base64 data.)")?;
+        if data.first() == Some(&1) {
+            return Err(Self::describe_format_1(&data[1..]));
+        }
         let mut des = MiniDes::start(data);
         let format_version = des
             .u8()
             .map_err(|_| "ERROR: Patch data is corrupt (does not contain format version.)")?;
-        if format_version > Self::FORMAT_VERSION {
+        let readable_from = if format_version >= 4 {
+            des.u8()
+                .map_err(|_| "ERROR: Patch data is corrupt (does not contain format version.)")?
+        } else {
+            format_version
+        };
+        // Newer formats can still be read if they only add sections this version can skip.
+        if readable_from > Self::FORMAT_VERSION {
             return Err("ERROR: Patch was created in a newer version of Audiobench".to_owned());
-        } else if format_version == 1 {
-            return Err(concat!(
-                "ERROR: Patch was created in an older version of Audiobench ",
-                "which is no longer supported"
-            )
-            .to_owned());
         }
+        // Older formats differ in how counts and controls are stored, which is dealt with when
+        // restoring the note graph.
+        self.format_version = format_version;
+        self.readable_from = readable_from;
         self.name = des
             .str()
             .map_err(|_| "ERROR: Patch data is corrupt (does not contain patch name.)")?;
-        self.data = des.end();
+        let data = des.end();
+        self.metadata = Default::default();
+        let graph_len = if format_version >= 4 {
+            Self::read_trailer(&data, &mut self.metadata)
+        } else {
+            data.len()
+        };
+        self.data = data[..graph_len].to_owned();
         Ok(())
     }
+
+    /// Format 1 is byte-aligned and big-endian instead of being read bit by bit like every later
+    /// format, and no reader for it has been kept. Only the name is read so that the user knows
+    /// which patch has to be rebuilt.
+    fn describe_format_1(data: &[u8]) -> String {
+        let name = data
+            .get(..2)
+            .map(|len| u16::from_be_bytes([len[0], len[1]]) as usize)
+            .and_then(|len| data.get(2..2 + len))
+            .and_then(|name| std::str::from_utf8(name).ok());
+        let name = name
+            .map(|name| format!(" \"{}\"", name))
+            .unwrap_or_default();
+        format!(
+            concat!(
+                "ERROR: The patch{} was saved by an early version of Audiobench in a format ",
+                "(version 1) which can no longer be read. To keep using it, open it in the ",
+                "version of Audiobench it was made with and rebuild it by hand in this one."
+            ),
+            name
+        )
+    }
+
+    /// Reads the sections following the graph in `data` (see `trailer`) and returns how many bytes
+    /// the graph takes up. Problems with the graph are left for `restore_note_graph` to report.
+    fn read_trailer(data: &[u8], metadata: &mut PatchMetadata) -> usize {
+        let mut des = MiniDes::start(data.to_owned());
+        if des.checked_section(|_| Ok::<_, ()>(())).is_err() {
+            return data.len();
+        }
+        // The graph is always saved as whole bytes, so it is padded up to the next byte.
+        for _ in 0..des.remaining_bits() % 8 {
+            des.bool().unwrap();
+        }
+        let graph_len = data.len() - des.remaining_bits() / 8;
+        // What follows the last section is less than a byte of padding, which is too short to be
+        // a section.
+        while des.remaining_bits() >= 8 {
+            let res = des.tagged_section(|tag, des| {
+                if tag == trailer::METADATA {
+                    *metadata = PatchMetadata::deserialize(des)?;
+                }
+                Ok::<_, ()>(())
+            });
+            if res.is_err() {
+                break;
+            }
+        }
+        graph_len
+    }
+}
+
+/// Changes the range of a FloatInRange or Int control and returns the range it had before. Other
+/// controls do not have a range and are left unchanged.
+pub(super) fn swap_range(control: &AnyControl, range: (f32, f32)) -> (f32, f32) {
+    match control {
+        AnyControl::FloatInRange(control) => {
+            let mut control = control.borrow_mut();
+            let old_range = control.range;
+            control.set_range(range);
+            old_range
+        }
+        AnyControl::Int(control) => {
+            let mut control = control.borrow_mut();
+            let old_range = control.get_range();
+            control.set_range((range.0 as i16, range.1 as i16));
+            (old_range.0 as f32, old_range.1 as f32)
+        }
+        _ => range,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::controls::Control;
+    use crate::registry::library_preload::LibraryInfo;
+
+    /// Lib 2.0.0 is installed but the patch uses a module from Lib 1.0.0 which no longer exists,
+    /// and a module from Other, which is not installed at all.
+    fn graph_with_placeholders() -> (Registry, ep::ModuleGraph) {
+        let info = LibraryInfo {
+            pretty_name: "Lib".to_owned(),
+            description: String::new(),
+            version: Version::new(2, 0, 0),
+            dependencies: Vec::new(),
+            update_url: None,
+        };
+        let registry = Registry::new_for_testing(vec![("Lib".to_owned(), info)]);
+        let placeholder = |lib_name: &str, lib_version, lib_installed| {
+            let info = PlaceholderInfo {
+                lib_version,
+                lib_installed,
+            };
+            let template = ModuleTemplate::new_placeholder(lib_name.to_owned(), 5, info);
+            let mut module = ep::Module::create(rcrc(template));
+            let mut control = PlaceholderControl::default();
+            control.value_from_text("1011001").unwrap();
+            module.controls.push(AnyControl::Placeholder(rcrc(control)));
+            rcrc(module)
+        };
+        let mut graph = ep::ModuleGraph::new();
+        graph.add_module(placeholder("Lib", Version::new(1, 0, 0), true));
+        graph.add_module(placeholder("Other", Version::new(0, 3, 0), false));
+        (registry, graph)
+    }
+
+    fn placeholder_versions(graph: &ep::ModuleGraph) -> Vec<Version> {
+        let modules = graph.borrow_modules().iter();
+        modules
+            .map(|module| {
+                let module = module.borrow();
+                let template = module.template.borrow();
+                template.placeholder.as_ref().unwrap().lib_version
+            })
+            .collect()
+    }
+
+    #[test]
+    fn placeholders_keep_their_library_version() {
+        let (registry, graph) = graph_with_placeholders();
+        let mut patch = Patch::new(PathBuf::new());
+        patch.save_note_graph(&graph, &registry);
+        let saved = patch.data.clone();
+
+        let mut loaded = ep::ModuleGraph::new();
+        patch.restore_note_graph(&mut loaded, &registry).unwrap();
+        assert_eq!(
+            placeholder_versions(&loaded),
+            vec![Version::new(1, 0, 0), Version::new(0, 3, 0)]
+        );
+        patch.save_note_graph(&loaded, &registry);
+        assert_eq!(patch.data, saved);
+    }
+
+    #[test]
+    fn text_placeholders_keep_their_library_version() {
+        let (registry, graph) = graph_with_placeholders();
+        let text = text_patch::write_text_patch(&graph, &registry);
+        let mut loaded = ep::ModuleGraph::new();
+        text_patch::restore_text_patch(&text, &mut loaded, &registry).unwrap();
+        assert_eq!(
+            placeholder_versions(&loaded),
+            vec![Version::new(1, 0, 0), Version::new(0, 3, 0)]
+        );
+        assert_eq!(text_patch::write_text_patch(&loaded, &registry), text);
+    }
+
+    #[test]
+    fn format_1_patches_are_rejected_by_name() {
+        // The default patch as it was shipped in format 1.
+        let data =
+            "AQAHRGVmYXVsdAAAAQAE_wHgAKgFAVAAqAYAeADACgAwADAOIgABAliAAGqqAAA7o9cKAD5MzM0APpmZmgMD";
+        let mut patch = Patch::new(PathBuf::new());
+        let err = patch.deserialize(data.as_bytes()).unwrap_err();
+        assert!(err.contains("\"Default\""), "{}", err);
+        assert!(err.contains("(version 1)"), "{}", err);
+    }
 }
@@ -0,0 +1,383 @@
+//! Creates the files for a new module from a short description of its inputs, controls and
+//! outputs, so that library authors don't have to copy an existing module by hand.
+
+use crate::{
+    engine::parts::JackType,
+    registry::{library_preload, migration, yaml},
+};
+use std::{
+    collections::HashSet,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Describes a module to create. Names should be `snake_case`, they are used as variable names in
+/// the module's Julia code and are turned into labels for the GUI.
+pub struct ModuleSpec {
+    /// The name of the module's files, like `Amplifier`.
+    pub name: String,
+    /// Which category the module appears under in the module browser, like `Utility`.
+    pub category: String,
+    /// Names and jack types (`audio`, `pitch`, `trigger` or `waveform`) of inputs.
+    pub inputs: Vec<(String, String)>,
+    /// Names and types of other controls, the types are named the same as in `.module.yaml` files.
+    pub controls: Vec<(String, String)>,
+    /// Names and jack types of outputs.
+    pub outputs: Vec<(String, String)>,
+}
+
+/// Widgets besides inputs all take up this many grid cells in each direction.
+const WIDGET_SIZE: i32 = 2;
+/// How many widgets are placed next to each other before starting a new row.
+const WIDGETS_PER_ROW: i32 = 4;
+
+fn parse_jack_type(name: &str, type_name: &str) -> Result<JackType, String> {
+    JackType::from_str(type_name).map_err(|_| {
+        format!(
+            concat!(
+                "ERROR: {} is not a valid type for {}, ",
+                "expected audio, pitch, trigger or waveform."
+            ),
+            type_name, name
+        )
+    })
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    let valid = name
+        .chars()
+        .next()
+        .map(|c| c.is_ascii_alphabetic() || c == '_')
+        .unwrap_or(false)
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            concat!(
+                "ERROR: \"{}\" is not a valid name, names can only contain letters, numbers and ",
+                "underscores and cannot start with a number."
+            ),
+            name
+        ))
+    }
+}
+
+/// Turns `attack_time` into `Attack Time`.
+fn label_from_name(name: &str) -> String {
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns the save ids which modules in the library use or used to use, along with the names of
+/// all modules in the library.
+fn scan_library(library_path: &Path) -> Result<(HashSet<usize>, HashSet<String>), String> {
+    let mut library = library_preload::preload_library_from_path(library_path)?;
+    let mut used_ids = HashSet::new();
+    let mut module_names = HashSet::new();
+    for index in 0..library.content.get_num_files() {
+        let file_name = library.content.get_file_name(index);
+        let is_module = file_name.ends_with(".module.yaml");
+        if !is_module && file_name != "migrations.yaml" {
+            continue;
+        }
+        let buffer = library.content.read_file_contents(index)?;
+        let text = String::from_utf8(buffer).map_err(|err| {
+            format!(
+                "ERROR: The file {} is not a valid UTF-8 text document, caused by:\nERROR: {}",
+                file_name, err
+            )
+        })?;
+        let yaml = yaml::parse_yaml(&text, &file_name)?;
+        if is_module {
+            used_ids.insert(yaml.unique_child("save_id")?.parse()?);
+            let file_name = Path::new(&file_name).file_name().unwrap().to_string_lossy();
+            module_names.insert(file_name.trim_end_matches(".module.yaml").to_owned());
+        } else {
+            used_ids.extend(migration::parse_migrations(&yaml)?.old_save_ids());
+        }
+    }
+    Ok((used_ids, module_names))
+}
+
+/// Returns the definition of the control for the `.module.yaml` file along with the widgets used
+/// to display it.
+fn describe_control(name: &str, type_name: &str) -> Result<(String, String), String> {
+    let label = label_from_name(name);
+    let tooltip = format!("TODO: Describe what {} does", label);
+    let simple_widget = |widget_type: &str| {
+        format!(
+            "    {}:\n      control: {}\n      label: {}\n      tooltip: {}\n",
+            widget_type, name, label, tooltip
+        )
+    };
+    Ok(match type_name {
+        "FloatInRange" => (
+            format!(
+                "  {}: FloatInRange\n    min: 0\n    max: 1\n    default: 0.5\n",
+                name
+            ),
+            simple_widget("Knob"),
+        ),
+        "Int" => (
+            format!("  {}: Int\n    min: 1\n    max: 8\n    default: 1\n", name),
+            simple_widget("IntBox"),
+        ),
+        "Frequency" => (
+            format!("  {}: Frequency\n    default: 1.0\n", name),
+            simple_widget("FrequencyBox"),
+        ),
+        "OptionChoice" => (
+            format!(
+                "  {}: OptionChoice\n    options:\n      Off:\n      On:\n",
+                name
+            ),
+            format!(
+                concat!(
+                    "    OptionBox:\n      w: {}\n      h: {}\n      control: {}\n",
+                    "      label: {}\n      tooltip: {}\n"
+                ),
+                WIDGET_SIZE, WIDGET_SIZE, name, label, tooltip
+            ),
+        ),
+        "TimingMode" => (
+            format!("  {}: TimingMode\n", name),
+            format!("    TimingSelector:\n      control: {}\n", name),
+        ),
+        // The timing mode control is filled in once all controls are known.
+        "Duration" => (
+            format!("  {}: Duration\n    default: 1.0\n", name),
+            format!(
+                concat!(
+                    "    DurationBox:\n      duration_control: {}\n",
+                    "      mode_control: $TIMING_MODE\n      label: {}\n      tooltip: {}\n"
+                ),
+                name, label, tooltip
+            ),
+        ),
+        _ => {
+            return Err(format!(
+                concat!(
+                    "ERROR: {} is not a control type that can be generated, expected one of ",
+                    "FloatInRange, Int, Frequency, OptionChoice, TimingMode or Duration."
+                ),
+                type_name
+            ))
+        }
+    })
+}
+
+/// Returns the value an output is set to in the skeleton code for each sample.
+fn default_value_code(typ: JackType) -> (&'static str, &'static str) {
+    // First is the type of the buffer, second is the value of each sample.
+    match typ {
+        JackType::Audio => ("MonoAudio", "0f0"),
+        JackType::Pitch => ("ControlSignal", "440f0"),
+        JackType::Trigger => ("Trigger", "false"),
+        JackType::Waveform => unreachable!("Waveforms are not buffers."),
+    }
+}
+
+/// Returns all controls the module will have besides inputs, along with the name of the timing
+/// mode control. A timing mode control is added if there is a duration control but no timing mode
+/// control, because duration boxes need one to know what units they are in.
+fn controls_with_timing_mode(spec: &ModuleSpec) -> (Vec<(String, String)>, String) {
+    let mut controls = spec.controls.clone();
+    let timing_mode = controls
+        .iter()
+        .find(|(_, type_name)| type_name == "TimingMode")
+        .map(|(name, _)| name.clone());
+    let uses_duration = controls
+        .iter()
+        .any(|(_, type_name)| type_name == "Duration");
+    let timing_mode = match timing_mode {
+        Some(name) => name,
+        None if uses_duration => {
+            controls.push(("timing_mode".to_owned(), "TimingMode".to_owned()));
+            "timing_mode".to_owned()
+        }
+        None => String::new(),
+    };
+    (controls, timing_mode)
+}
+
+fn generate_yaml(spec: &ModuleSpec, save_id: usize) -> Result<String, String> {
+    let (controls, timing_mode) = controls_with_timing_mode(spec);
+    let mut control_code = String::new();
+    let mut widget_code = String::new();
+    for (index, (name, type_name)) in spec.inputs.iter().enumerate() {
+        parse_jack_type(name, type_name)?;
+        let label = label_from_name(name);
+        writeln!(control_code, "  {}: Input\n    type: {}", name, type_name).unwrap();
+        writeln!(
+            widget_code,
+            concat!(
+                "    Input:\n      y: {}\n      control: {}\n      label: {}\n",
+                "      tooltip: TODO: Describe what {} is used for"
+            ),
+            index, name, label, label
+        )
+        .unwrap();
+    }
+    for (index, (name, type_name)) in controls.iter().enumerate() {
+        let (control, widget) = describe_control(name, type_name)?;
+        control_code.push_str(&control);
+        let index = index as i32;
+        let x = index % WIDGETS_PER_ROW * WIDGET_SIZE;
+        let y = index / WIDGETS_PER_ROW * WIDGET_SIZE;
+        // The position goes right after the widget type.
+        let position = format!("      x: {}\n      y: {}\n", x, y);
+        let split = widget.find('\n').unwrap() + 1;
+        widget_code.push_str(&widget[..split]);
+        widget_code.push_str(&position);
+        widget_code.push_str(&widget[split..].replace("$TIMING_MODE", &timing_mode));
+    }
+    let mut output_code = String::new();
+    for (name, type_name) in &spec.outputs {
+        parse_jack_type(name, type_name)?;
+        writeln!(
+            output_code,
+            "  {}:\n    type: {}\n    label: {}\n    tooltip: TODO: Describe this output",
+            name,
+            type_name,
+            label_from_name(name)
+        )
+        .unwrap();
+    }
+
+    let num_controls = controls.len() as i32;
+    let num_columns = num_controls.min(WIDGETS_PER_ROW).max(1);
+    let num_rows = (num_controls + WIDGETS_PER_ROW - 1) / WIDGETS_PER_ROW;
+    let width = num_columns * WIDGET_SIZE;
+    let height = (num_rows * WIDGET_SIZE)
+        .max(spec.inputs.len() as i32)
+        .max(spec.outputs.len() as i32)
+        .max(WIDGET_SIZE);
+
+    let mut yaml = format!("save_id: {}\n", save_id);
+    yaml.push_str(&format!("outputs:\n{}", output_code));
+    if !control_code.is_empty() {
+        yaml.push_str(&format!("controls:\n{}", control_code));
+    }
+    yaml.push_str(&format!(
+        concat!(
+            "gui:\n  label: {}\n  category: {}\n  tooltip: TODO: Describe what this module does\n",
+            "  width: {}\n  height: {}\n  widgets:\n{}"
+        ),
+        label_from_name(&spec.name),
+        spec.category,
+        width,
+        height,
+        widget_code
+    ));
+    Ok(yaml)
+}
+
+fn generate_julia(spec: &ModuleSpec) -> Result<String, String> {
+    let mut code = String::from("function exec()\n");
+    let (controls, _) = controls_with_timing_mode(spec);
+    let parameters: Vec<_> = spec
+        .inputs
+        .iter()
+        .chain(controls.iter())
+        .map(|(name, _)| &name[..])
+        .collect();
+    if !parameters.is_empty() {
+        writeln!(code, "    # Available controls: {}", parameters.join(", ")).unwrap();
+    }
+    let mut buffer_outputs = Vec::new();
+    for (name, type_name) in &spec.outputs {
+        let typ = parse_jack_type(name, type_name)?;
+        if typ == JackType::Waveform {
+            writeln!(code, "    {} = flat_waveform", name).unwrap();
+        } else {
+            let (buffer_type, _) = default_value_code(typ);
+            writeln!(code, "    {} = similar({})", name, buffer_type).unwrap();
+            buffer_outputs.push((name, typ));
+        }
+    }
+    if !buffer_outputs.is_empty() {
+        code.push_str("    for s in sample_indices(MonoAudio)\n");
+        code.push_str("        # TODO: Compute the outputs for sample s.\n");
+        for (name, typ) in buffer_outputs {
+            let (_, value) = default_value_code(typ);
+            writeln!(code, "        {}[1, s] = {}", name, value).unwrap();
+        }
+        code.push_str("    end\n");
+    }
+    code.push_str("end\n");
+    Ok(code)
+}
+
+/// Creates `modules/<category>/<name>.module.yaml` and `.module.jl` in the directory library at
+/// `library_path`. The module gets the lowest unused save id, a widget for every control laid out
+/// on a grid and an `exec()` function which sets every output to a default value. Returns the
+/// paths of the files that were created.
+pub fn create_module(library_path: &Path, spec: &ModuleSpec) -> Result<Vec<PathBuf>, String> {
+    if !library_path.is_dir() {
+        return Err(format!(
+            "ERROR: {} is not a folder, modules can only be added to libraries stored as folders.",
+            library_path.to_string_lossy()
+        ));
+    }
+    check_name(&spec.name)?;
+    let mut names = HashSet::new();
+    for (name, _) in spec
+        .inputs
+        .iter()
+        .chain(spec.controls.iter())
+        .chain(spec.outputs.iter())
+    {
+        check_name(name)?;
+        if !names.insert(name) {
+            return Err(format!(
+                "ERROR: There is more than one control or output named {}.",
+                name
+            ));
+        }
+    }
+
+    let (used_ids, module_names) = scan_library(library_path)?;
+    if module_names.contains(&spec.name) {
+        return Err(format!(
+            "ERROR: The library already has a module named {}.",
+            spec.name
+        ));
+    }
+    let mut save_id = 0;
+    while used_ids.contains(&save_id) {
+        save_id += 1;
+    }
+    let yaml = generate_yaml(spec, save_id)?;
+    let julia = generate_julia(spec)?;
+
+    let folder = library_path
+        .join("modules")
+        .join(spec.category.to_lowercase().replace(' ', "_"));
+    let yaml_path = folder.join(format!("{}.module.yaml", spec.name));
+    let julia_path = folder.join(format!("{}.module.jl", spec.name));
+    for path in &[&yaml_path, &julia_path] {
+        if path.exists() {
+            return Err(format!("ERROR: {} already exists.", path.to_string_lossy()));
+        }
+    }
+    let write_err = |path: &Path, err: std::io::Error| {
+        format!(
+            "ERROR: Failed to write {}, caused by:\nERROR: {}",
+            path.to_string_lossy(),
+            err
+        )
+    };
+    fs::create_dir_all(&folder).map_err(|err| write_err(&folder, err))?;
+    fs::write(&yaml_path, yaml).map_err(|err| write_err(&yaml_path, err))?;
+    fs::write(&julia_path, julia).map_err(|err| write_err(&julia_path, err))?;
+    Ok(vec![yaml_path, julia_path])
+}
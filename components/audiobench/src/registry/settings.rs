@@ -0,0 +1,110 @@
+//! Preferences which are kept between runs of Audiobench. They are stored in `settings.yaml` in
+//! the platform's config folder rather than the library folder, since everything in the library
+//! folder is loaded as a library.
+
+use crate::registry::yaml;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub struct Settings {
+    /// Whether Audiobench and libraries with an `update_url` are checked for new versions on
+    /// startup.
+    pub check_for_updates: bool,
+    /// Checked for new versions of Audiobench instead of the official URL. Can be a `file://` or
+    /// plain HTTP URL, which is useful for testing or for an internal mirror.
+    pub engine_update_url: Option<String>,
+    /// Name of the theme used by the GUI, None for the default theme.
+    pub theme: Option<String>,
+    /// Whether patches are saved in the text format, which is easier to keep in version control.
+    /// Patches which are already text are always saved as text.
+    pub text_patches: bool,
+    /// None if the settings should not be saved.
+    path: Option<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            check_for_updates: true,
+            engine_update_url: None,
+            theme: None,
+            text_patches: false,
+            path: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Loads the settings from the config folder, using defaults for anything which is missing.
+    /// Problems are printed instead of returned so that a broken settings file can't prevent
+    /// Audiobench from starting.
+    pub fn load() -> Self {
+        let path = directories::ProjectDirs::from("", "", "Audiobench")
+            .map(|dirs| dirs.config_dir().join("settings.yaml"));
+        let mut settings = Self {
+            path: path.clone(),
+            ..Default::default()
+        };
+        if let Some(path) = path.filter(|path| path.exists()) {
+            if let Err(err) = settings.load_from(&path) {
+                eprintln!(
+                    "WARNING: Failed to load settings from {}, caused by:\n{}",
+                    path.to_string_lossy(),
+                    err
+                );
+            }
+        }
+        settings
+    }
+
+    fn load_from(&mut self, path: &Path) -> Result<(), String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("ERROR: {}", err))?;
+        let yaml = yaml::parse_yaml(&text, &path.to_string_lossy())?;
+        for child in &yaml.children {
+            match &child.name[..] {
+                "check_for_updates" => {
+                    self.check_for_updates = child.parse_enumerated(&["false", "true"])? == 1;
+                }
+                "engine_update_url" => self.engine_update_url = Some(child.value.clone()),
+                "theme" => self.theme = Some(child.value.clone()),
+                "text_patches" => {
+                    self.text_patches = child.parse_enumerated(&["false", "true"])? == 1;
+                }
+                // Probably from a newer version of Audiobench.
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = if let Some(path) = &self.path {
+            path
+        } else {
+            return Ok(());
+        };
+        let mut text = format!("check_for_updates: {}\n", self.check_for_updates);
+        if let Some(url) = &self.engine_update_url {
+            text.push_str(&format!("engine_update_url: {}\n", url));
+        }
+        if let Some(theme) = &self.theme {
+            text.push_str(&format!("theme: {}\n", theme));
+        }
+        if self.text_patches {
+            text.push_str("text_patches: true\n");
+        }
+        let write_err = |err: std::io::Error| {
+            format!(
+                "ERROR: Failed to save settings to {}, caused by:\nERROR: {}",
+                path.to_string_lossy(),
+                err
+            )
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(write_err)?;
+        }
+        fs::write(path, text).map_err(write_err)
+    }
+}
@@ -0,0 +1,601 @@
+//! Patches can also be saved as YAML, which works much better with version control than the
+//! binary format. Modules, outputs and controls are referred to by name rather than by save id and
+//! index, and each control is on its own line:
+//!
+//! ```yaml
+//! # Audiobench patch
+//! name: Bass
+//! author: Someone
+//! seed: 1234
+//! libraries:
+//!   Factory: 0.3.0
+//! modules:
+//!   envelope_1:
+//!     library: Factory
+//!     module: envelope
+//!     x: 120
+//!     y: -40
+//!     controls:
+//!       attack_time: 0.01
+//!   oscillator_1:
+//!     library: Factory
+//!     module: oscillator
+//!     x: 0
+//!     y: 0
+//!     controls:
+//!       amplitude:
+//!         value: 0..1
+//!         wires:
+//!           - envelope_1.signal
+//! midi_bindings:
+//!   - controller: 7
+//!     control: oscillator_1.amplitude
+//!     range: 0..1
+//!     curve: linear
+//! ```
+//!
+//! Each control's value is written by `Control::value_to_text`, which includes everything the
+//! binary format stores, so patches can be converted between the formats without losing anything.
+//! Modules which were missing when the patch was saved are written with a `save_id` instead of a
+//! `module` and the `library_version` they are from, and their controls are named `control0`,
+//! `control1` and so on.
+
+use crate::{
+    engine::{
+        controls::{AnyControl, AutomationSource, PlaceholderControl},
+        midi_bindings::{MidiBinding, MidiCurve, PlaceholderMidiBinding, NUM_MIDI_CONTROLLERS},
+        parts as ep,
+    },
+    registry::{
+        migration::OldControl,
+        module_template::{ModuleTemplate, PlaceholderInfo},
+        patch_metadata::{self, PatchMetadata},
+        save_data::{add_placeholder_outputs, swap_range},
+        yaml::{self, YamlNode},
+        Registry,
+    },
+};
+use shared_util::{prelude::*, Version};
+use std::collections::HashMap;
+
+/// The first line of every text patch, used to tell them apart from binary patches.
+pub const HEADER: &str = "# Audiobench patch\n";
+
+/// Returns true if `data` looks like a text patch. Binary patches are base64, which never
+/// contains a colon.
+pub fn is_text_patch(data: &[u8]) -> bool {
+    data.contains(&b':')
+}
+
+/// Quotes `value` unless YAML would read it back unchanged without quotes.
+pub fn quoted(value: &str) -> String {
+    let plain = value.starts_with(|c: char| c.is_alphanumeric() || c == '-' || c == '.')
+        && !value.ends_with(' ')
+        && value
+            .chars()
+            .all(|c| c.is_alphanumeric() || " ._-/,".contains(c))
+        && !["null", "Null", "NULL"].contains(&value);
+    if plain {
+        return value.to_owned();
+    }
+    let mut result = "\"".to_owned();
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            _ => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn control_name(template: &ModuleTemplate, index: usize) -> String {
+    if template.is_placeholder() {
+        format!("control{}", index)
+    } else {
+        template.default_controls[index].0.clone()
+    }
+}
+
+/// Writes everything in `graph`. The name and metadata of the patch are added by
+/// `Patch::serialize`.
+pub fn write_text_patch(graph: &ep::ModuleGraph, registry: &Registry) -> String {
+    let mut text = format!("seed: {}\n", graph.get_seed());
+
+    let libs = patch_metadata::used_libraries(graph, registry);
+    if !libs.is_empty() {
+        text.push_str("libraries:\n");
+        for (name, version) in &libs {
+            text.push_str(&format!("  {}: {}\n", quoted(name), version));
+        }
+    }
+
+    // Modules are named after their template and how many modules of the same kind came before
+    // them, like `oscillator_2`.
+    let mut ids = Vec::new();
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for module in graph.borrow_modules() {
+        let module = module.borrow();
+        let template = module.template.borrow();
+        let kind = if template.is_placeholder() {
+            "missing".to_owned()
+        } else {
+            template.module_name.clone()
+        };
+        let count = counts.entry(kind.clone()).or_insert(0);
+        *count += 1;
+        ids.push(format!("{}_{}", kind, count));
+    }
+    let id_of = |target: &Rcrc<ep::Module>| {
+        let index = graph.index_of_module(target).unwrap();
+        &ids[index]
+    };
+
+    if !ids.is_empty() {
+        text.push_str("modules:\n");
+    }
+    for (module, id) in graph.borrow_modules().iter().zip(ids.iter()) {
+        let module = module.borrow();
+        let template = module.template.borrow();
+        text.push_str(&format!("  {}:\n", quoted(id)));
+        text.push_str(&format!("    library: {}\n", quoted(&template.lib_name)));
+        if let Some(info) = &template.placeholder {
+            text.push_str(&format!("    save_id: {}\n", template.save_id));
+            text.push_str(&format!("    library_version: {}\n", info.lib_version));
+        } else {
+            text.push_str(&format!("    module: {}\n", quoted(&template.module_name)));
+        }
+        text.push_str(&format!(
+            "    x: {}\n    y: {}\n",
+            module.pos.0, module.pos.1
+        ));
+        if module.controls.is_empty() {
+            continue;
+        }
+        text.push_str("    controls:\n");
+        for (index, control) in module.controls.iter().enumerate() {
+            let name = quoted(&control_name(&template, index));
+            let control_ptr = control.as_dyn_ptr();
+            let control = control_ptr.borrow();
+            let value = quoted(&control.value_to_text());
+            let wires = control.get_connected_automation();
+            if wires.is_empty() {
+                text.push_str(&format!("      {}: {}\n", name, value));
+                continue;
+            }
+            text.push_str(&format!("      {}:\n        value: {}\n", name, value));
+            text.push_str("        wires:\n");
+            for source in wires {
+                let source_module = source.module.borrow();
+                let source_template = source_module.template.borrow();
+                let output = source_template.outputs[source.output_index].borrow_code_name();
+                let wire = format!("{}.{}", id_of(&source.module), output);
+                text.push_str(&format!("          - {}\n", quoted(&wire)));
+            }
+        }
+    }
+
+    let mut bindings = Vec::new();
+    for binding in graph.borrow_midi_bindings() {
+        if binding.get_control().is_none() {
+            continue;
+        }
+        let control = {
+            let module = binding.module.borrow();
+            let template = module.template.borrow();
+            control_name(&template, binding.control_index)
+        };
+        bindings.push(format!(
+            "    controller: {}\n    control: {}\n    range: {}..{}\n    curve: {}\n",
+            binding.controller,
+            quoted(&format!("{}.{}", id_of(&binding.module), control)),
+            binding.range.0,
+            binding.range.1,
+            binding.curve.name().to_lowercase(),
+        ));
+    }
+    for binding in graph.borrow_placeholder_midi_bindings() {
+        bindings.push(format!(
+            "    controller: {}\n    control: {}.control{}\n    raw_range: {}..{}\n    curve: {}\n",
+            binding.controller,
+            quoted(id_of(&binding.module)),
+            binding.control_index,
+            binding.raw_range.0,
+            binding.raw_range.1,
+            binding.curve.name().to_lowercase(),
+        ));
+    }
+    if !bindings.is_empty() {
+        text.push_str("midi_bindings:\n");
+        for binding in bindings {
+            // The first line of each item starts with a dash instead of being indented.
+            text.push_str("  -");
+            text.push_str(&binding[3..]);
+        }
+    }
+    text
+}
+
+/// Returns the name and metadata of the patch stored in `text` and the text with them removed,
+/// which is what `restore_text_patch` expects.
+pub fn split_header(text: &str) -> Result<(String, PatchMetadata, String), String> {
+    let yaml = yaml::parse_yaml(text, "patch")?;
+    let name = yaml.unique_child("name")?.value.clone();
+    let metadata = PatchMetadata::from_text_yaml(&yaml)?;
+    let mut body = String::new();
+    let mut skipping = false;
+    for line in text.lines() {
+        if line.trim_end() == HEADER.trim_end() {
+            continue;
+        }
+        // Top level entries start at the beginning of the line and continue until the next one
+        // does. Items of a top level list may also start at the beginning of the line.
+        let continues = line.starts_with(' ') || line.starts_with("- ") || line.is_empty();
+        if !continues {
+            let key = line.split(':').next().unwrap_or("").trim();
+            skipping = key == "name" || patch_metadata::TEXT_KEYS.contains(&key);
+        }
+        if !skipping {
+            body.push_str(line);
+            body.push('\n');
+        }
+    }
+    Ok((name, metadata, body))
+}
+
+fn split_range(text: &str) -> Option<(&str, &str)> {
+    let split = text.find("..")?;
+    Some((&text[..split], &text[split + 2..]))
+}
+
+/// Splits something like `oscillator_1.amplitude` into a module and the name after the dot.
+fn split_reference<'a>(
+    node: &YamlNode,
+    reference: &'a str,
+    modules: &HashMap<&str, Rcrc<ep::Module>>,
+) -> Result<(Rcrc<ep::Module>, &'a str), String> {
+    let dot = reference.rfind('.').ok_or_else(|| {
+        format!(
+            "ERROR: {} should look like module_1.name, found \"{}\".",
+            node.full_name, reference
+        )
+    })?;
+    let module = modules.get(&reference[..dot]).ok_or_else(|| {
+        format!(
+            "ERROR: {} refers to a module named {}, which does not exist.",
+            node.full_name,
+            &reference[..dot]
+        )
+    })?;
+    Ok((Rc::clone(module), &reference[dot + 1..]))
+}
+
+/// Finds the control written as `name` in a module saved with the controls in `layout`, see
+/// `Registry::old_control_layout`.
+fn find_control<'a>(layout: &'a [(String, OldControl)], name: &str) -> Option<&'a OldControl> {
+    layout
+        .iter()
+        .find(|(other, _)| other == name)
+        .map(|(_, control)| control)
+}
+
+/// Replaces the contents of `graph` with the patch written in `text`, which should not contain
+/// the name or metadata of the patch (see `split_header`.) Returns a description of anything that could not be
+/// restored the way it was saved.
+pub fn restore_text_patch(
+    text: &str,
+    graph: &mut ep::ModuleGraph,
+    registry: &Registry,
+) -> Result<Vec<String>, String> {
+    let yaml = yaml::parse_yaml(text, "patch")?;
+    let mut changes = Vec::new();
+
+    let mut lib_versions = HashMap::new();
+    if let Ok(libs) = yaml.unique_child("libraries") {
+        for lib in &libs.children {
+            let version: Version = lib.parse()?;
+            if let Some(info) = registry.borrow_library_info(&lib.name) {
+                if version > info.version {
+                    changes.push(format!(
+                        concat!(
+                            "Patch was saved with version {} of library {} but version {} is ",
+                            "installed, some settings might not load correctly"
+                        ),
+                        version, lib.name, info.version
+                    ));
+                }
+            }
+            lib_versions.insert(lib.name.clone(), version);
+        }
+    }
+
+    let mut modules = Vec::new();
+    // The controls each module had in the version of its library the patch was saved with.
+    let mut layouts = Vec::new();
+    let mut modules_by_id: HashMap<&str, Rcrc<ep::Module>> = HashMap::new();
+    let mut placeholder_templates: HashMap<(String, usize, Version), Rcrc<ModuleTemplate>> =
+        HashMap::new();
+    let no_modules = YamlNode::default();
+    let module_nodes = yaml.unique_child("modules").unwrap_or(&no_modules);
+    for node in &module_nodes.children {
+        let lib_name = &node.unique_child("library")?.value;
+        let template = if let Ok(save_id) = node.unique_child("save_id") {
+            let save_id: usize = save_id.parse()?;
+            let lib_installed = registry.borrow_library_info(lib_name).is_some();
+            // Other modules from the same library might be from a different version.
+            let lib_version = if let Ok(version) = node.unique_child("library_version") {
+                version.parse()?
+            } else {
+                lib_versions
+                    .get(lib_name)
+                    .cloned()
+                    .unwrap_or_else(|| Version::new(0, 0, 0))
+            };
+            if lib_installed {
+                changes.push(format!(
+                    "A module from version {} of {} is missing",
+                    lib_version, lib_name
+                ));
+            } else {
+                changes.push(format!("Library {} is not installed", lib_name));
+            }
+            let key = (lib_name.clone(), save_id, lib_version);
+            let template = placeholder_templates.entry(key).or_insert_with(|| {
+                let info = PlaceholderInfo {
+                    lib_version,
+                    lib_installed,
+                };
+                rcrc(ModuleTemplate::new_placeholder(
+                    lib_name.clone(),
+                    save_id,
+                    info,
+                ))
+            });
+            // The layout is filled in once we know how many controls the module had.
+            layouts.push(Vec::new());
+            Rc::clone(template)
+        } else {
+            let module_name = &node.unique_child("module")?.value;
+            let template = registry.borrow_templates().iter().find(|template| {
+                let template = template.borrow();
+                &template.lib_name == lib_name && &template.module_name == module_name
+            });
+            let template = template.ok_or_else(|| {
+                format!(
+                    "ERROR: {} uses the module {} from {}, which could not be found.",
+                    node.full_name, module_name, lib_name
+                )
+            })?;
+            // Patches which do not list the library are assumed to be from the installed version.
+            let lib_version = lib_versions
+                .get(lib_name)
+                .cloned()
+                .or_else(|| {
+                    registry
+                        .borrow_library_info(lib_name)
+                        .map(|info| info.version)
+                })
+                .unwrap_or_else(|| Version::new(0, 0, 0));
+            let layout = registry.old_control_layout(&template.borrow(), lib_version, &mut changes);
+            layouts.push(layout);
+            Rc::clone(template)
+        };
+        let mut module = ep::Module::create(template);
+        module.pos = (
+            node.unique_child("x")?.parse()?,
+            node.unique_child("y")?.parse()?,
+        );
+        let module = rcrc(module);
+        modules_by_id.insert(&node.name, Rc::clone(&module));
+        modules.push(module);
+    }
+    changes.dedup();
+
+    // Controls are restored after every module exists so that wires can refer to any of them.
+    for (module_index, (module_ptr, node)) in
+        modules.iter().zip(module_nodes.children.iter()).enumerate()
+    {
+        let controls = if let Ok(controls) = node.unique_child("controls") {
+            controls
+        } else {
+            continue;
+        };
+        let is_placeholder = module_ptr.borrow().template.borrow().is_placeholder();
+        if is_placeholder {
+            let mut module = module_ptr.borrow_mut();
+            for index in 0..controls.children.len() {
+                let control = PlaceholderControl::default();
+                module.controls.push(AnyControl::Placeholder(rcrc(control)));
+                let name = format!("control{}", index);
+                if controls.children[index].name != name {
+                    return Err(format!(
+                        "ERROR: {} should be named {}.",
+                        controls.children[index].full_name, name
+                    ));
+                }
+                let old_range = None;
+                layouts[module_index].push((name, OldControl::Current { index, old_range }));
+            }
+        }
+        for control_node in &controls.children {
+            let module = module_ptr.borrow();
+            let (index, old_range) = match find_control(&layouts[module_index], &control_node.name)
+            {
+                Some(OldControl::Current { index, old_range }) => (*index, *old_range),
+                // The changes from the migration already mention that the control was removed.
+                Some(OldControl::Removed(_)) => continue,
+                None => {
+                    changes.push(format!(
+                        "{} no longer has a control named {}, its value was left out",
+                        node.name, control_node.name
+                    ));
+                    continue;
+                }
+            };
+            // Controls which changed range are read using the range they had when the patch was
+            // saved and then moved into the new range.
+            let new_range =
+                old_range.map(|old_range| swap_range(&module.controls[index], old_range));
+            let control_ptr = module.controls[index].as_dyn_ptr();
+            let mut control = control_ptr.borrow_mut();
+            let value = if control_node.children.is_empty() {
+                control_node
+            } else {
+                control_node.unique_child("value")?
+            };
+            if let Ok(wires) = control_node.unique_child("wires") {
+                for wire in &wires.children {
+                    let (source_module, output) =
+                        split_reference(wire, &wire.name, &modules_by_id)?;
+                    if Rc::ptr_eq(&source_module, module_ptr) {
+                        return Err(format!(
+                            "ERROR: {} connects a module to itself.",
+                            wire.full_name
+                        ));
+                    }
+                    let acceptable = control.acceptable_automation();
+                    let source_template = Rc::clone(&source_module.borrow().template);
+                    let mut source_template = source_template.borrow_mut();
+                    if source_template.is_placeholder() {
+                        let index = output
+                            .strip_prefix("output")
+                            .and_then(|index| index.parse().ok());
+                        if let Some(index) = index {
+                            let typ = acceptable.first().cloned().unwrap_or(ep::JackType::Audio);
+                            add_placeholder_outputs(&mut source_template, index, typ, registry)
+                                .map_err(|_| {
+                                    format!(
+                                        "ERROR: {} refers to an output which is not valid.",
+                                        wire.full_name
+                                    )
+                                })?;
+                        }
+                    }
+                    let output_index = source_template
+                        .outputs
+                        .iter()
+                        .position(|other| other.borrow_code_name() == output)
+                        .ok_or_else(|| {
+                            format!(
+                                "ERROR: {} refers to an output named {}, which does not exist.",
+                                wire.full_name, output
+                            )
+                        })?;
+                    let output_type = source_template.outputs[output_index].get_type();
+                    if !is_placeholder && !acceptable.contains(&output_type) {
+                        return Err(format!(
+                            "ERROR: {} connects an output to a control which does not accept it.",
+                            wire.full_name
+                        ));
+                    }
+                    control.connect_automation(AutomationSource {
+                        module: Rc::clone(&source_module),
+                        output_index,
+                        output_type,
+                    });
+                }
+            }
+            control.value_from_text(&value.value).map_err(|_| {
+                format!(
+                    "ERROR: \"{}\" is not a valid value for {}.",
+                    value.value, value.full_name
+                )
+            })?;
+            drop(control);
+            if let Some(new_range) = new_range {
+                swap_range(&module.controls[index], new_range);
+            }
+        }
+    }
+    for template in placeholder_templates.values() {
+        let mut template = template.borrow_mut();
+        template.size.1 = template.size.1.max(template.outputs.len() as i32);
+    }
+
+    let mut midi_bindings = Vec::new();
+    let mut placeholder_midi_bindings = Vec::new();
+    let no_bindings = YamlNode::default();
+    let binding_nodes = yaml.unique_child("midi_bindings").unwrap_or(&no_bindings);
+    for node in &binding_nodes.children {
+        let controller = node
+            .unique_child("controller")?
+            .parse_ranged(Some(0), Some(NUM_MIDI_CONTROLLERS - 1))?;
+        let curve_names: Vec<_> = MidiCurve::ALL
+            .iter()
+            .map(|curve| curve.name().to_lowercase())
+            .collect();
+        let curve_names: Vec<_> = curve_names.iter().map(|name| &name[..]).collect();
+        let curve = MidiCurve::ALL[node.unique_child("curve")?.parse_enumerated(&curve_names)?];
+        let control_node = node.unique_child("control")?;
+        let (module, control) = split_reference(control_node, &control_node.value, &modules_by_id)?;
+        let module_index = modules
+            .iter()
+            .position(|other| Rc::ptr_eq(other, &module))
+            .unwrap();
+        let control_index = match find_control(&layouts[module_index], control) {
+            Some(OldControl::Current { index, .. }) => *index,
+            // Bindings to controls which have since been removed are dropped.
+            Some(OldControl::Removed(_)) => continue,
+            None => {
+                return Err(format!(
+                    "ERROR: {} refers to a control which does not exist.",
+                    control_node.full_name
+                ))
+            }
+        };
+        if module.borrow().template.borrow().is_placeholder() {
+            let raw_range = node.unique_child("raw_range")?.parse_custom(|value| {
+                let (min, max) = split_range(value).ok_or("ERROR: Expected a range like 0..1.")?;
+                let parse = |value: &str| {
+                    value
+                        .trim()
+                        .parse()
+                        .map_err(|_| "ERROR: Expected a range like 0..1.".to_owned())
+                };
+                Ok((parse(min)?, parse(max)?))
+            })?;
+            placeholder_midi_bindings.push(PlaceholderMidiBinding {
+                controller,
+                module,
+                control_index,
+                raw_range,
+                curve,
+            });
+            continue;
+        }
+        let mut binding = MidiBinding::new(controller, module, control_index).ok_or_else(|| {
+            format!(
+                "ERROR: {} refers to a control which cannot be bound to MIDI.",
+                control_node.full_name
+            )
+        })?;
+        let (min, max) = binding.range;
+        binding.range = node.unique_child("range")?.parse_custom(|value| {
+            let (start, end) = split_range(value).ok_or("ERROR: Expected a range like 0..1.")?;
+            let parse = |value: &str| {
+                value
+                    .trim()
+                    .parse::<f32>()
+                    .map(|value| value.clam(min, max))
+                    .map_err(|_| "ERROR: Expected a range like 0..1.".to_owned())
+            };
+            Ok((parse(start)?, parse(end)?))
+        })?;
+        binding.curve = curve;
+        midi_bindings.push(binding);
+    }
+
+    let seed = if let Ok(seed) = yaml.unique_child("seed") {
+        seed.parse()?
+    } else {
+        0
+    };
+    graph.set_modules(modules);
+    graph.set_midi_bindings(midi_bindings);
+    graph.set_placeholder_midi_bindings(placeholder_midi_bindings);
+    graph.set_seed(seed);
+    Ok(changes)
+}
@@ -0,0 +1,216 @@
+//! Themes change the colors, corner sizes and font sizes used by the GUI. Libraries can define
+//! them in a `theme.yaml` file, or in files ending with `.theme.yaml` if they define more than one.
+//! Every entry is optional, anything left out is the same as in the default theme:
+//!
+//! ```yaml
+//! name: Light
+//! colors:
+//!   bg0: E8E8E8
+//!   fg1: "#000000"
+//! jack_colors:
+//!   audio: 2060C0
+//! corner_size: 2
+//! font_size: 12
+//! big_font_size: 14
+//! ```
+
+use crate::{engine::parts::JackType, registry::yaml::YamlNode};
+
+pub type Rgb = (u8, u8, u8);
+
+/// A color which is looked up in the active theme when it is drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThemeColor {
+    Bg0,
+    Bg1,
+    Bg2,
+    Fg0,
+    Fg1,
+    Editable,
+    Automation,
+    Error,
+    Success,
+    Warning,
+    /// Used for jacks and wires carrying the given type of data.
+    Jack(JackType),
+    /// The same in every theme.
+    Fixed(Rgb),
+}
+
+const fn hex_color(hex: u32) -> Rgb {
+    (
+        ((hex >> 16) & 0xFF) as u8,
+        ((hex >> 8) & 0xFF) as u8,
+        (hex & 0xFF) as u8,
+    )
+}
+
+#[derive(Clone, Debug)]
+pub struct Theme {
+    pub name: String,
+    pub lib_name: String,
+    bg0: Rgb,
+    bg1: Rgb,
+    bg2: Rgb,
+    fg0: Rgb,
+    fg1: Rgb,
+    editable: Rgb,
+    automation: Rgb,
+    error: Rgb,
+    success: Rgb,
+    warning: Rgb,
+    audio_jack: Rgb,
+    pitch_jack: Rgb,
+    trigger_jack: Rgb,
+    waveform_jack: Rgb,
+    pub corner_size: f32,
+    pub font_size: f32,
+    pub big_font_size: f32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let fg1 = (0xFF, 0xFF, 0xFF);
+        Self {
+            name: "Default".to_owned(),
+            lib_name: "Factory".to_owned(),
+            bg0: hex_color(0x121520),
+            bg1: hex_color(0x2F434F),
+            bg2: hex_color(0x48525F),
+            fg0: (0x77, 0x77, 0x77),
+            fg1,
+            editable: hex_color(0xFF0022),
+            automation: hex_color(0xC7D5E8),
+            error: hex_color(0xFF0022),
+            success: hex_color(0x038c23),
+            warning: hex_color(0xCC7722),
+            audio_jack: fg1,
+            pitch_jack: fg1,
+            trigger_jack: fg1,
+            waveform_jack: fg1,
+            corner_size: 4.0,
+            font_size: 12.0,
+            big_font_size: 14.0,
+        }
+    }
+}
+
+impl Theme {
+    pub fn resolve(&self, color: ThemeColor) -> Rgb {
+        match color {
+            ThemeColor::Bg0 => self.bg0,
+            ThemeColor::Bg1 => self.bg1,
+            ThemeColor::Bg2 => self.bg2,
+            ThemeColor::Fg0 => self.fg0,
+            ThemeColor::Fg1 => self.fg1,
+            ThemeColor::Editable => self.editable,
+            ThemeColor::Automation => self.automation,
+            ThemeColor::Error => self.error,
+            ThemeColor::Success => self.success,
+            ThemeColor::Warning => self.warning,
+            ThemeColor::Jack(JackType::Audio) => self.audio_jack,
+            ThemeColor::Jack(JackType::Pitch) => self.pitch_jack,
+            ThemeColor::Jack(JackType::Trigger) => self.trigger_jack,
+            ThemeColor::Jack(JackType::Waveform) => self.waveform_jack,
+            ThemeColor::Fixed(rgb) => rgb,
+        }
+    }
+
+    fn palette_entry(&mut self, name: &str) -> Option<&mut Rgb> {
+        Some(match name {
+            "bg0" => &mut self.bg0,
+            "bg1" => &mut self.bg1,
+            "bg2" => &mut self.bg2,
+            "fg0" => &mut self.fg0,
+            "fg1" => &mut self.fg1,
+            "editable" => &mut self.editable,
+            "automation" => &mut self.automation,
+            "error" => &mut self.error,
+            "success" => &mut self.success,
+            "warning" => &mut self.warning,
+            _ => return None,
+        })
+    }
+
+    fn jack_entry(&mut self, name: &str) -> Option<&mut Rgb> {
+        Some(match JackType::from_str(name).ok()? {
+            JackType::Audio => &mut self.audio_jack,
+            JackType::Pitch => &mut self.pitch_jack,
+            JackType::Trigger => &mut self.trigger_jack,
+            JackType::Waveform => &mut self.waveform_jack,
+        })
+    }
+
+    /// `default_name` is used if the theme does not specify a name.
+    pub fn from_yaml(yaml: &YamlNode, lib_name: &str, default_name: &str) -> Result<Self, String> {
+        let mut theme = Self {
+            name: default_name.to_owned(),
+            lib_name: lib_name.to_owned(),
+            ..Default::default()
+        };
+        for child in &yaml.children {
+            match &child.name[..] {
+                "name" => theme.name = child.value.clone(),
+                "colors" => {
+                    for color in &child.children {
+                        let entry = theme.palette_entry(&color.name).ok_or_else(|| {
+                            format!(
+                                concat!(
+                                    "ERROR: {} is not a color, expected one of bg0, bg1, bg2, ",
+                                    "fg0, fg1, editable, automation, error, success or warning."
+                                ),
+                                color.full_name
+                            )
+                        })?;
+                        *entry = color.parse_custom(parse_hex_color)?;
+                    }
+                }
+                "jack_colors" => {
+                    for color in &child.children {
+                        let entry = theme.jack_entry(&color.name).ok_or_else(|| {
+                            format!(
+                                concat!(
+                                    "ERROR: {} is not a jack type, expected one of audio, ",
+                                    "pitch, trigger or waveform."
+                                ),
+                                color.full_name
+                            )
+                        })?;
+                        *entry = color.parse_custom(parse_hex_color)?;
+                    }
+                }
+                // Larger corners would not fit on jacks, which only round one side.
+                "corner_size" => theme.corner_size = child.parse_ranged(Some(0.0), Some(6.0))?,
+                "font_size" => theme.font_size = child.parse_ranged(Some(6.0), Some(18.0))?,
+                "big_font_size" => {
+                    theme.big_font_size = child.parse_ranged(Some(6.0), Some(20.0))?
+                }
+                _ => {
+                    return Err(format!(
+                        "ERROR: {} is not a valid theme setting.",
+                        child.full_name
+                    ))
+                }
+            }
+        }
+        Ok(theme)
+    }
+}
+
+/// Parses colors like `FF0022` or `"#FF0022"`.
+fn parse_hex_color(value: &str) -> Result<Rgb, String> {
+    let hex = value.trim_matches(|c| c == '"' || c == '\'');
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 {
+        return Err(format!(
+            "ERROR: \"{}\" is not a color, expected six hex digits like FF0022.",
+            value
+        ));
+    }
+    u32::from_str_radix(hex, 16).map(hex_color).map_err(|_| {
+        format!(
+            "ERROR: \"{}\" is not a color, expected six hex digits like FF0022.",
+            value
+        )
+    })
+}
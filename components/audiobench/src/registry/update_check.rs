@@ -1,8 +1,10 @@
+use crate::config::ENGINE_VERSION;
 use reqwest::blocking::{Client, ClientBuilder};
 use serde::Deserialize;
 use shared_util::Version;
-use std::sync::mpsc::Sender;
+use std::{fs, sync::mpsc::Sender};
 
+/// The contents of the JSON file at an update URL.
 #[derive(Deserialize)]
 pub struct UpdateInfo {
     pub version: Version,
@@ -10,41 +12,57 @@ pub struct UpdateInfo {
     pub download_url: String,
 }
 
-fn retrieve_info(client: &mut Client, url: &str) -> Option<UpdateInfo> {
-    let request = client
+/// Reads the contents of a URL. Besides HTTP and HTTPS, `file://` URLs are supported so that a
+/// local folder can stand in for an update server.
+fn fetch(client: &mut Client, url: &str) -> Result<Vec<u8>, String> {
+    if let Some(path) = url.strip_prefix("file://") {
+        // file:///C:/... on Windows, the path should not start with a slash.
+        let path = if cfg!(windows) && path.starts_with('/') && path.get(2..3) == Some(":") {
+            &path[1..]
+        } else {
+            path
+        };
+        return fs::read(path)
+            .map_err(|err| format!("ERROR: Failed to read {}, caused by:\nERROR: {}", path, err));
+    }
+    let response = client
         .get(url)
-        // Some websites do not like the default user agent.
-        .header("User-Agent", "Chrome/86.0 (KHTML, unlike Lizard)")
-        .header("Referer", "https://Audiobench");
-    let response = match request.send() {
-        Ok(v) => v,
-        Err(err) => {
-            eprintln!(
-                "ERROR: Failed to check for updates at {}, cause by:\nERROR: {}",
+        .header("User-Agent", format!("Audiobench/{}", ENGINE_VERSION))
+        .send()
+        .map_err(|err| {
+            format!(
+                "ERROR: Failed to connect to {}, caused by:\nERROR: {}",
                 url, err
-            );
-            return None;
-        }
-    };
-    let response_ok = response.status().is_success();
-    let response_text = match response.text() {
+            )
+        })?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!(
+            "ERROR: The server at {} responded with {}.",
+            url, status
+        ));
+    }
+    let bytes = response.bytes().map_err(|err| {
+        format!(
+            "ERROR: Failed to download {}, caused by:\nERROR: {}",
+            url, err
+        )
+    })?;
+    Ok(bytes.to_vec())
+}
+
+fn retrieve_info(client: &mut Client, url: &str) -> Option<UpdateInfo> {
+    let response = match fetch(client, url) {
         Ok(v) => v,
         Err(err) => {
             eprintln!(
-                "ERROR: Failed to decode response at {}, cause by:\nERROR: {}",
-                url, err,
+                "ERROR: Failed to check for updates at {}, cause by:\n{}",
+                url, err
             );
             return None;
         }
     };
-    if !response_ok {
-        eprintln!(
-            "ERROR: Failed to check for updates at {}, cause by:\nERROR: {}",
-            url, response_text,
-        );
-        return None;
-    }
-    match serde_json::from_str(&response_text) {
+    match serde_json::from_slice(&response) {
         Ok(info) => Some(info),
         Err(err) => {
             eprintln!(
@@ -56,15 +74,19 @@ fn retrieve_info(client: &mut Client, url: &str) -> Option<UpdateInfo> {
     }
 }
 
+fn create_client() -> Client {
+    // TODO: rust-native-tls has a bug which rejects domain names with underscores. PITA when
+    // I'm hosting an update checker on code_cube.gitlab.io! Relevant bug report:
+    // https://github.com/sfackler/rust-native-tls/issues/177
+    ClientBuilder::new().use_rustls_tls().build().unwrap()
+}
+
 pub fn spawn_update_checker(
     urls_to_check: Vec<String>,
     response_channel: Sender<(String, Option<UpdateInfo>)>,
 ) {
     std::thread::spawn(move || {
-        // TODO: rust-native-tls has a bug which rejects domain names with underscores. PITA when
-        // I'm hosting an update checker on code_cube.gitlab.io! Relevant bug report:
-        // https://github.com/sfackler/rust-native-tls/issues/177
-        let mut client = ClientBuilder::new().use_rustls_tls().build().unwrap();
+        let mut client = create_client();
         for url in urls_to_check.into_iter() {
             let info = retrieve_info(&mut client, &url);
             if let Err(err) = response_channel.send((url, info)) {
@@ -76,3 +98,21 @@ pub fn spawn_update_checker(
         }
     });
 }
+
+/// Downloads the file at `url` in the background and sends its contents along with `name` once it
+/// is done.
+pub fn spawn_download(
+    name: String,
+    url: String,
+    response_channel: Sender<(String, Result<Vec<u8>, String>)>,
+) {
+    std::thread::spawn(move || {
+        let result = fetch(&mut create_client(), &url);
+        if let Err(err) = response_channel.send((name, result)) {
+            eprintln!(
+                "WARNING: Failed to send download result, caused by:\n{}",
+                err
+            );
+        }
+    });
+}
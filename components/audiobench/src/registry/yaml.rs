@@ -1,8 +1,17 @@
 use std::borrow::Borrow;
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::str::FromStr;
+use yaml_rust::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::{Marker, TScalarStyle},
+};
 
-// An inefficient and limited but easy to use YAML representation.
+// An inefficient but easy to use YAML representation. Mappings become children named after their
+// keys. Items of sequences become children too: plain values become children named after the
+// value, mappings with a single key become that key's child and anything else is named after its
+// index. This means a list of widgets can be written as a sequence of `- Knob: ...` items instead
+// of repeating the `Knob:` key.
 #[derive(Default, Debug)]
 pub struct YamlNode {
     pub name: String,
@@ -108,7 +117,226 @@ impl YamlNode {
     }
 }
 
+/// Parses a YAML document into a tree of nodes, see `YamlNode`. The top level of the document has
+/// to be a mapping. Files which are not valid YAML but were accepted by older versions of
+/// Audiobench, which allowed a key to have a value and children at the same time, are still
+/// loaded the way they used to be.
 pub fn parse_yaml(input: &str, filename: &str) -> Result<YamlNode, String> {
+    match parse_standard_yaml(input, filename) {
+        // Only errors caused by things the old format allowed fall back to it, so that other
+        // mistakes are not silently read as plain text.
+        Err(err) if err.contains(NESTED_VALUE_ERROR) || err.contains(DUPLICATE_KEY_ERROR) => {
+            parse_legacy_yaml(input, filename).map_err(|_| err)
+        }
+        result => result,
+    }
+}
+
+/// What yaml-rust reports when a key has both a value and children.
+const NESTED_VALUE_ERROR: &str = "mapping values are not allowed in this context";
+const DUPLICATE_KEY_ERROR: &str = "Duplicate key";
+
+#[derive(Clone)]
+enum Value {
+    Scalar(String),
+    Sequence(Vec<Value>),
+    Mapping(Vec<(String, Value)>),
+}
+
+#[derive(Default)]
+struct EventCollector {
+    events: Vec<(Event, Marker)>,
+}
+
+impl MarkedEventReceiver for EventCollector {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        self.events.push((event, mark));
+    }
+}
+
+struct TreeBuilder<'a> {
+    events: std::vec::IntoIter<(Event, Marker)>,
+    last_mark: Option<Marker>,
+    anchors: HashMap<usize, Value>,
+    filename: &'a str,
+}
+
+impl<'a> TreeBuilder<'a> {
+    fn error(&self, message: &str, mark: Option<Marker>) -> String {
+        if let Some(mark) = mark {
+            format!(
+                "ERROR: {} at line {} column {} (in {})",
+                message,
+                mark.line(),
+                mark.col() + 1,
+                self.filename
+            )
+        } else {
+            format!("ERROR: {} (in {})", message, self.filename)
+        }
+    }
+
+    fn next_event(&mut self) -> Result<(Event, Marker), String> {
+        let (event, mark) = self
+            .events
+            .next()
+            .ok_or_else(|| self.error("Unexpected end of file", self.last_mark))?;
+        self.last_mark = Some(mark);
+        Ok((event, mark))
+    }
+
+    fn remember(&mut self, anchor: usize, value: &Value) {
+        if anchor > 0 {
+            self.anchors.insert(anchor, value.clone());
+        }
+    }
+
+    fn build_value(&mut self, event: Event, mark: Marker) -> Result<Value, String> {
+        match event {
+            Event::Scalar(text, style, anchor, _) => {
+                let is_null = ["~", "null", "Null", "NULL"].contains(&&text[..]);
+                let value = if style == TScalarStyle::Plain && is_null {
+                    Value::Scalar(String::new())
+                } else {
+                    Value::Scalar(text)
+                };
+                self.remember(anchor, &value);
+                Ok(value)
+            }
+            Event::Alias(anchor) => self
+                .anchors
+                .get(&anchor)
+                .cloned()
+                .ok_or_else(|| self.error("Alias refers to an unknown anchor", Some(mark))),
+            Event::SequenceStart(anchor) => {
+                let mut items = Vec::new();
+                loop {
+                    let (event, mark) = self.next_event()?;
+                    if let Event::SequenceEnd = event {
+                        break;
+                    }
+                    items.push(self.build_value(event, mark)?);
+                }
+                let value = Value::Sequence(items);
+                self.remember(anchor, &value);
+                Ok(value)
+            }
+            Event::MappingStart(anchor) => {
+                let mut entries: Vec<(String, Value)> = Vec::new();
+                loop {
+                    let (event, mark) = self.next_event()?;
+                    let key = match event {
+                        Event::MappingEnd => break,
+                        Event::Scalar(key, ..) => key,
+                        _ => return Err(self.error("Keys must be plain text", Some(mark))),
+                    };
+                    if entries.iter().any(|(other, _)| other == &key) {
+                        return Err(
+                            self.error(&format!("{} {}", DUPLICATE_KEY_ERROR, key), Some(mark))
+                        );
+                    }
+                    let (event, mark) = self.next_event()?;
+                    entries.push((key, self.build_value(event, mark)?));
+                }
+                let value = Value::Mapping(entries);
+                self.remember(anchor, &value);
+                Ok(value)
+            }
+            _ => Err(self.error("Unexpected YAML event", Some(mark))),
+        }
+    }
+
+    /// Returns the value of the only document in the file, None if the file is empty.
+    fn build_document(&mut self) -> Result<Option<Value>, String> {
+        let mut document = None;
+        loop {
+            let (event, mark) = self.next_event()?;
+            match event {
+                Event::StreamStart | Event::DocumentEnd => (),
+                Event::StreamEnd => return Ok(document),
+                Event::DocumentStart if document.is_some() => {
+                    return Err(self.error("Only one document is allowed per file", Some(mark)))
+                }
+                Event::DocumentStart => {
+                    let (event, mark) = self.next_event()?;
+                    document = Some(self.build_value(event, mark)?);
+                }
+                _ => return Err(self.error("Unexpected YAML event", Some(mark))),
+            }
+        }
+    }
+}
+
+fn create_node(name: String, parent_full_name: &str, value: Value) -> YamlNode {
+    let full_name = format!("{}.{}", parent_full_name, name);
+    let mut node = YamlNode {
+        name,
+        full_name,
+        value: String::new(),
+        children: Vec::new(),
+    };
+    match value {
+        Value::Scalar(text) => node.value = text,
+        Value::Mapping(entries) => {
+            for (key, value) in entries {
+                let child = create_node(key, &node.full_name, value);
+                node.children.push(Box::new(child));
+            }
+        }
+        Value::Sequence(items) => {
+            for (index, item) in items.into_iter().enumerate() {
+                let child = match item {
+                    Value::Scalar(text) => {
+                        create_node(text, &node.full_name, Value::Scalar(String::new()))
+                    }
+                    Value::Mapping(mut entries) if entries.len() == 1 => {
+                        let (key, value) = entries.pop().unwrap();
+                        create_node(key, &node.full_name, value)
+                    }
+                    item => create_node(format!("{}", index), &node.full_name, item),
+                };
+                node.children.push(Box::new(child));
+            }
+        }
+    }
+    node
+}
+
+fn parse_standard_yaml(input: &str, filename: &str) -> Result<YamlNode, String> {
+    let mut collector = EventCollector::default();
+    Parser::new(input.chars())
+        .load(&mut collector, true)
+        .map_err(|err| format!("ERROR: {} (in {})", err, filename))?;
+    let mut builder = TreeBuilder {
+        events: collector.events.into_iter(),
+        last_mark: None,
+        anchors: HashMap::new(),
+        filename,
+    };
+    let mut root = YamlNode {
+        name: filename.to_owned(),
+        full_name: filename.to_owned(),
+        value: "".to_owned(),
+        children: Vec::new(),
+    };
+    match builder.build_document()? {
+        Some(Value::Mapping(entries)) => {
+            for (key, value) in entries {
+                root.children
+                    .push(Box::new(create_node(key, filename, value)));
+            }
+        }
+        Some(Value::Scalar(text)) if text.is_empty() => (),
+        None => (),
+        Some(_) => {
+            return Err(builder.error("The top level of the file must be a mapping", None));
+        }
+    }
+    Ok(root)
+}
+
+/// The indentation-based format used before Audiobench understood standard YAML.
+fn parse_legacy_yaml(input: &str, filename: &str) -> Result<YamlNode, String> {
     let mut stack = vec![YamlNode {
         name: filename.to_owned(),
         full_name: filename.to_owned(),
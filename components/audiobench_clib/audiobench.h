@@ -37,6 +37,7 @@ extern "C" {
     void ABAudioSongTime(ABInstanceRef, float);
     void ABAudioSongBeats(ABInstanceRef, float);
     void ABAudioControl(ABInstanceRef, int, float);
+    void ABAudioMorph(ABInstanceRef, float);
     float *ABAudioRenderAudio(ABInstanceRef);
 
     void ABUiSetGraphicsFunctions(ABInstanceRef, ABGraphicsFunctions);
@@ -63,7 +63,7 @@ pub unsafe extern "C" fn ABUiSerializePatch(
         let data = instance
             .ui_engine
             .borrow()
-            .serialize_current_patch()
+            .serialize_plugin_state()
             .into_bytes()
             .into_boxed_slice();
         *size_out = data.len() as u32;
@@ -128,6 +128,13 @@ pub unsafe extern "C" fn ABAudioControl(cr: *mut CreateResult, index: i32, value
     });
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn ABAudioMorph(cr: *mut CreateResult, amount: f32) {
+    with_ok(cr, |instance| {
+        instance.audio_engine.borrow_mut().set_morph_amount(amount)
+    });
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn ABAudioBpm(cr: *mut CreateResult, bpm: f32) {
     with_ok(cr, |instance| {
@@ -234,26 +241,20 @@ pub unsafe extern "C" fn ABUiMouseMove(
 #[no_mangle]
 pub unsafe extern "C" fn ABUiMouseUp(cr: *mut CreateResult) {
     with_ok(cr, |instance| {
-        instance.ui_with_gui_mut(|gui| {
-            gui.on_mouse_up();
-        })
+        instance.ui_mouse_up();
     });
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn ABUiScroll(cr: *mut CreateResult, delta: f32) {
     with_ok(cr, |instance| {
-        instance.ui_with_gui_mut(|gui| {
-            gui.on_scroll(delta);
-        })
+        instance.ui_scroll(delta);
     });
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn ABUiKeyPress(cr: *mut CreateResult, key: u8) {
     with_ok(cr, |instance| {
-        instance.ui_with_gui_mut(|gui| {
-            gui.on_key_press(key as char);
-        })
+        instance.ui_key_press(key as char);
     });
 }
@@ -1,89 +1,353 @@
 use audiobench::*;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
 
-const TYPICAL_BUFFER_LENGTH: usize = 512;
-const TYPICAL_SAMPLE_RATE: usize = 44100;
-const TYPICAL_TEST_DURATION: usize = 100;
-const TYPICAL_NUM_NOTES: usize = 10;
-/// The 'default' patch from the Factory library.
-const PATCH_DEFAULT: &[u8] =
-    "AQAHRGVmYXVsdAAAAQAE_wHgAKgFAVAAqAYAeADACgAwADAOIgABAliAAGqqAAA7o9cKAD5MzM0APpmZmgMD"
-        .as_bytes();
-/// The 'pluckypluckypluck' patch from the Factory library.
-const PATCH_PLUCK: &[u8] = "AQARUGx1Y2t5cGx1Y2t5cGx1Y2sAAAEACv__DwK4AKgFAKj_6A4AqABgEgEIAWgOAfgAqBEBOADYBgE4ASAGADABaAz_cABgDv-gAPAHEomiQgAEAgUGCQcJIgKUmQIJAADo9QAAAAAAAAAAO6PXCgA9d85jAD55hhgAADvqv2gBAAA7o9cKAD5MzIYAPpmZmgAAAAcAAAAAO6PXCgA9lewBAD6ZmZogMwAI8zMFHgHuFAMDCBR6frg".as_bytes();
-
-struct TestParameters {
+const DEFAULT_BUFFER_LENGTH: usize = 512;
+const DEFAULT_SAMPLE_RATE: usize = 44100;
+const DEFAULT_DURATION: f32 = 10.0;
+const DEFAULT_NUM_VOICES: usize = 10;
+/// How much audio to render before measuring anything, so that Julia has a chance to compile
+/// everything.
+const DEFAULT_WARMUP: f32 = 1.0;
+/// How much worse (in percent) a result can be than the baseline before it counts as a regression.
+const DEFAULT_THRESHOLD: f64 = 10.0;
+/// Notes are spread out over this many semitones, starting at LOWEST_NOTE.
+const NOTE_SPREAD: usize = 100;
+const LOWEST_NOTE: usize = 10;
+
+const USAGE: &str = "\
+Usage: benchmark [OPTIONS]
+
+Renders audio as fast as possible and reports how long it took.
+
+Options:
+  --patch <FILE>          Patch to benchmark (.abpatch), defaults to Factory:Default
+  --voices <N>            Number of notes to hold down [default: 10]
+  --buffer-length <N>     Samples per buffer [default: 512]
+  --sample-rate <N>       Samples per second [default: 44100]
+  --duration <SECONDS>    Amount of audio to render [default: 10]
+  --warmup <SECONDS>      Amount of audio to render before measuring [default: 1]
+  --json                  Print results as JSON, suitable for use with --compare
+  --compare <FILE>        Compare results to a baseline created with --json and exit with an
+                          error if performance regressed
+  --threshold <PERCENT>   How much worse than the baseline is tolerated [default: 10]
+  --help                  Print this message
+";
+
+struct Options {
+    patch: Option<String>,
+    num_voices: usize,
     buffer_length: usize,
     sample_rate: usize,
-    num_seconds: usize,
-    num_notes: usize,
-    patch_name: &'static str,
-    patch_data: &'static [u8],
+    duration: f32,
+    warmup: f32,
+    json: bool,
+    compare: Option<String>,
+    threshold: f64,
 }
 
-fn do_benchmark(test_id: usize, params: &TestParameters) {
-    let TestParameters {
-        buffer_length,
-        sample_rate,
-        num_seconds,
-        num_notes,
-        patch_name,
-        patch_data,
-    } = params;
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self {
+            patch: None,
+            num_voices: DEFAULT_NUM_VOICES,
+            buffer_length: DEFAULT_BUFFER_LENGTH,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            duration: DEFAULT_DURATION,
+            warmup: DEFAULT_WARMUP,
+            json: false,
+            compare: None,
+            threshold: DEFAULT_THRESHOLD,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("ERROR: {} requires a value.", name))
+            };
+            match &arg[..] {
+                "--patch" => options.patch = Some(value(&arg)?),
+                "--voices" => options.num_voices = parse_value(&arg, value(&arg)?)?,
+                "--buffer-length" => options.buffer_length = parse_value(&arg, value(&arg)?)?,
+                "--sample-rate" => options.sample_rate = parse_value(&arg, value(&arg)?)?,
+                "--duration" => options.duration = parse_value(&arg, value(&arg)?)?,
+                "--warmup" => options.warmup = parse_value(&arg, value(&arg)?)?,
+                "--json" => options.json = true,
+                "--compare" => options.compare = Some(value(&arg)?),
+                "--threshold" => options.threshold = parse_value(&arg, value(&arg)?)?,
+                "--help" | "-h" => {
+                    print!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                _ => {
+                    return Err(format!(
+                        "ERROR: Unrecognized argument {}.\n\n{}",
+                        arg, USAGE
+                    ))
+                }
+            }
+        }
+        if options.num_voices < 1 || options.num_voices > NOTE_SPREAD {
+            return Err(format!(
+                "ERROR: The number of voices must be between 1 and {}.",
+                NOTE_SPREAD
+            ));
+        }
+        if options.buffer_length == 0 || options.sample_rate == 0 {
+            return Err("ERROR: The buffer length and sample rate must be nonzero.".to_owned());
+        }
+        if options.duration <= 0.0 || options.warmup < 0.0 || options.duration.is_nan() {
+            return Err(
+                "ERROR: The duration must be positive and the warmup must not be negative."
+                    .to_owned(),
+            );
+        }
+        Ok(options)
+    }
 
-    println!("================================================================================");
-    println!("EXECUTING TEST {}", test_id);
-    println!("Buffer lenth:     {} samples", buffer_length);
-    println!("Sample rate:      {} hertz", sample_rate);
-    println!("Render time:      {} seconds", num_seconds);
-    println!("Number of notes:  {}", num_notes);
-    println!("Patch name:       {}", patch_name);
-
-    let mut instance = OldInstance::new();
-    instance.set_global_params(*buffer_length, *sample_rate);
-    instance.deserialize_patch(patch_data);
-    // We need to do this at least once so that the audio thread can adjust to the new parameters.
-    // Without this, the audio thread will shut down the notes when the actual benchmark starts.
-    instance.render_audio();
-    let note_distance = 100 / *num_notes;
-    for offset in 0..*num_notes {
-        instance.start_note(10 + offset * note_distance, 0.8);
+    fn patch_name(&self) -> &str {
+        self.patch
+            .as_ref()
+            .map(|p| &p[..])
+            .unwrap_or("Factory:Default")
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "patch": self.patch_name(),
+            "voices": self.num_voices,
+            "buffer_length": self.buffer_length,
+            "sample_rate": self.sample_rate,
+            "duration": self.duration,
+        })
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("ERROR: {} is not a valid value for {}.", value, name))
+}
+
+struct Results {
+    /// How many seconds of audio were rendered per second of real time.
+    realtime_factor: f64,
+    /// Time taken to render a single buffer, sorted from fastest to slowest.
+    buffer_times: Vec<Duration>,
+    /// How many buffers took longer to render than it would take to play them.
+    num_overruns: usize,
+    perf_report: String,
+}
+
+impl Results {
+    fn percentile(&self, percentile: f64) -> Duration {
+        let index = (self.buffer_times.len() - 1) as f64 * percentile / 100.0;
+        self.buffer_times[index.round() as usize]
+    }
+
+    fn to_json(&self) -> Value {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        json!({
+            "realtime_factor": self.realtime_factor,
+            "overruns": self.num_overruns,
+            "latency_ms": {
+                "p50": ms(self.percentile(50.0)),
+                "p90": ms(self.percentile(90.0)),
+                "p99": ms(self.percentile(99.0)),
+                "max": ms(self.percentile(100.0)),
+            },
+        })
+    }
+}
+
+/// Waits until the Julia thread has finished whatever it was doing. Audio rendered while the Julia
+/// thread is busy is just silence, so it would make the results look better than they are.
+fn wait_until_ready(instance: &mut Instance) {
+    while instance.ui_engine.borrow().is_julia_thread_busy() {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn run_benchmark(options: &Options) -> Result<Results, String> {
+    let mut instance = Instance::new()?;
+    instance
+        .audio_engine
+        .borrow_mut()
+        .set_global_params(options.buffer_length, options.sample_rate);
+    if let Some(path) = &options.patch {
+        let data = std::fs::read(path)
+            .map_err(|err| format!("ERROR: Failed to read {}, caused by:\nERROR: {}", path, err))?;
+        instance.ui_deserialize_patch(&data[..])?;
+    } else {
+        // The default patch gets a new seed every time Audiobench starts.
+        instance.ui_engine.borrow_mut().set_patch_seed(0);
     }
+    // Keep the results comparable between runs of patches that use randomness.
+    instance.ui_engine.borrow_mut().set_deterministic_mode(true);
+    wait_until_ready(&mut instance);
+
+    let buffer_duration = options.buffer_length as f64 / options.sample_rate as f64;
+    let num_warmup_buffers = (options.warmup as f64 / buffer_duration).ceil() as usize;
+    let num_buffers = ((options.duration as f64 / buffer_duration).ceil() as usize).max(1);
+
+    for offset in 0..options.num_voices {
+        let note = LOWEST_NOTE + offset * NOTE_SPREAD / options.num_voices;
+        instance.audio_engine.borrow_mut().start_note(note, 0.8);
+    }
+    for _ in 0..num_warmup_buffers {
+        wait_until_ready(&mut instance);
+        instance.audio_render_audio();
+    }
+    instance.ui_engine.borrow_mut().reset_perf_counter();
+
+    let mut buffer_times = Vec::with_capacity(num_buffers);
     let mut anti_optimization_accumulator = 0.0;
-    let num_render_cycles = sample_rate * num_seconds / buffer_length;
-    for _ in 0..num_render_cycles {
-        let audio = instance.render_audio();
+    let start = Instant::now();
+    for _ in 0..num_buffers {
+        let buffer_start = Instant::now();
+        let audio = instance.audio_render_audio();
+        buffer_times.push(buffer_start.elapsed());
         for sample in audio {
             anti_optimization_accumulator += sample;
         }
     }
+    let total_time = start.elapsed().as_secs_f64();
+    // Make sure the compiler can't throw away the audio.
+    if anti_optimization_accumulator == f32::INFINITY {
+        eprintln!("WARNING: The rendered audio contains infinite values.");
+    }
+
+    buffer_times.sort();
+    let max_time = Duration::from_secs_f64(buffer_duration);
+    let num_overruns = buffer_times.iter().filter(|time| **time > max_time).count();
+    // Feedback data is only copied to the UI side when this is called, module timings included.
+    instance.ui_engine.borrow_mut().display_new_feedback_data();
+    let perf_report = instance.ui_engine.borrow().perf_report();
+    Ok(Results {
+        realtime_factor: num_buffers as f64 * buffer_duration / total_time,
+        buffer_times,
+        num_overruns,
+        perf_report,
+    })
+}
 
-    println!("");
+fn print_results(options: &Options, results: &Results) {
+    let ms = |duration: Duration| format!("{:.3}ms", duration.as_secs_f64() * 1000.0);
+    println!("================================================================================");
+    println!("Patch:            {}", options.patch_name());
+    println!("Voices:           {}", options.num_voices);
+    println!("Buffer length:    {} samples", options.buffer_length);
+    println!("Sample rate:      {} hertz", options.sample_rate);
+    println!("Render time:      {} seconds", options.duration);
+    println!();
     println!("RESULTS:");
-    println!("{}", instance.perf_report());
-    println!("");
+    println!("Realtime factor:  {:.2}x", results.realtime_factor);
+    println!("Overruns:         {}", results.num_overruns);
+    println!(
+        "Buffer latency:   p50 {}, p90 {}, p99 {}, max {}",
+        ms(results.percentile(50.0)),
+        ms(results.percentile(90.0)),
+        ms(results.percentile(99.0)),
+        ms(results.percentile(100.0)),
+    );
+    println!();
+    println!("{}", results.perf_report);
+    println!();
 }
 
-const TESTS: [TestParameters; 2] = [
-    TestParameters {
-        buffer_length: TYPICAL_BUFFER_LENGTH,
-        sample_rate: TYPICAL_SAMPLE_RATE,
-        num_seconds: TYPICAL_TEST_DURATION,
-        num_notes: TYPICAL_NUM_NOTES,
-        patch_name: "Default",
-        patch_data: PATCH_DEFAULT,
-    },
-    TestParameters {
-        buffer_length: TYPICAL_BUFFER_LENGTH,
-        sample_rate: TYPICAL_SAMPLE_RATE,
-        num_seconds: TYPICAL_TEST_DURATION,
-        num_notes: TYPICAL_NUM_NOTES,
-        patch_name: "Pluck",
-        patch_data: PATCH_PLUCK,
-    },
-];
+/// Returns a description of every way the results are worse than the baseline.
+fn find_regressions(baseline: &Value, current: &Value, threshold: f64) -> Vec<String> {
+    let mut regressions = Vec::new();
+    let factor = 1.0 + threshold / 100.0;
+    let get = |value: &Value, path: &[&str]| {
+        let mut value = value;
+        for key in path {
+            value = &value[key];
+        }
+        value.as_f64()
+    };
+    // Lower is worse for the realtime factor, higher is worse for everything else.
+    let checks: [(&[&str], bool); 5] = [
+        (&["results", "realtime_factor"], false),
+        (&["results", "latency_ms", "p50"], true),
+        (&["results", "latency_ms", "p90"], true),
+        (&["results", "latency_ms", "p99"], true),
+        (&["results", "overruns"], true),
+    ];
+    for (path, higher_is_worse) in checks.iter() {
+        let (old, new) = match (get(baseline, path), get(current, path)) {
+            (Some(old), Some(new)) => (old, new),
+            _ => continue,
+        };
+        let regressed = if *higher_is_worse {
+            new > old * factor
+        } else {
+            new * factor < old
+        };
+        if regressed {
+            regressions.push(format!("{}: {:.3} -> {:.3}", path.join("."), old, new));
+        }
+    }
+    regressions
+}
+
+fn compare(options: &Options, baseline_path: &str, current: &Value) -> Result<bool, String> {
+    let baseline = std::fs::read_to_string(baseline_path).map_err(|err| {
+        format!(
+            "ERROR: Failed to read {}, caused by:\nERROR: {}",
+            baseline_path, err
+        )
+    })?;
+    let baseline: Value = serde_json::from_str(&baseline).map_err(|err| {
+        format!(
+            "ERROR: {} is not a valid baseline, caused by:\nERROR: {}",
+            baseline_path, err
+        )
+    })?;
+    if baseline["parameters"] != current["parameters"] {
+        eprintln!("WARNING: The baseline was recorded with different parameters.");
+    }
+    let regressions = find_regressions(&baseline, current, options.threshold);
+    if regressions.is_empty() {
+        eprintln!("No regressions compared to {}.", baseline_path);
+        Ok(true)
+    } else {
+        eprintln!(
+            "Performance regressed by more than {}% compared to {}:",
+            options.threshold, baseline_path
+        );
+        for regression in regressions {
+            eprintln!("  {}", regression);
+        }
+        Ok(false)
+    }
+}
+
+fn run() -> Result<bool, String> {
+    let options = Options::parse(std::env::args().skip(1))?;
+    let results = run_benchmark(&options)?;
+    let json = json!({
+        "parameters": options.to_json(),
+        "results": results.to_json(),
+    });
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&json).unwrap());
+    } else {
+        print_results(&options, &results);
+    }
+    if let Some(baseline_path) = &options.compare {
+        compare(&options, baseline_path, &json)
+    } else {
+        Ok(true)
+    }
+}
 
 fn main() {
-    for (index, test) in TESTS.iter().enumerate() {
-        do_benchmark(index, test);
+    match run() {
+        Ok(true) => (),
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
     }
 }
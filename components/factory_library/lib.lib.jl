@@ -98,6 +98,8 @@ struct NoteInput
     velocity::Float32
     elapsed_time::Float32
     elapsed_beats::Float32
+    seed::UInt32
+    random::Float32
     start_trigger::Bool
     release_trigger::Bool
 end
@@ -116,6 +118,30 @@ struct NoteContext
     note_out::NoteOutput
 end
 
+# A small random number generator (xorshift32) which should be seeded with the seed of the current
+# note. Use this instead of rand() so that renders are reproducible when the engine is in
+# deterministic mode.
+mutable struct NoteRng
+    state::UInt32
+    # Xorshift gets stuck if the state is ever zero.
+    NoteRng(seed::UInt32) = new(if seed == 0 0x9E3779B9 else seed end)
+end
+
+# Modules which use more than one generator can provide a different salt for each of them so that
+# they do not produce the same sequence.
+NoteRng(context::NoteContext, salt::UInt32 = 0x00000000) = NoteRng(context.note_in.seed ⊻ salt)
+
+# Returns a random value between 0 and 1.
+function next_random!(rng::NoteRng)::Float32
+    x = rng.state
+    x ⊻= x << 13
+    x ⊻= x >> 17
+    x ⊻= x << 5
+    rng.state = x
+    # Only use the top 24 bits because that is all a Float32 can represent exactly.
+    Float32(x >> 8) / 16777216f0
+end
+
 function timing_mode_source_is_global(mode::Integer)::Bool
     mode & 0b1 == 0b1
 end
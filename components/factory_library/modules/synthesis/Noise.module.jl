@@ -2,10 +2,11 @@ mutable struct StaticData
     old_value::Float32
     new_value::Float32
     old_value_time::Float32
+    rng::NoteRng
 end
 
 function static_init()
-    StaticData(0f0, 0f0, 0f0)
+    StaticData(0f0, 0f0, 0f0, NoteRng(0x00000000))
 end
 
 function exec()
@@ -13,19 +14,22 @@ function exec()
     value_now = 0f0
     factor = 0f0
     timing = get_timing(context, 0) # 0 = note time in seconds.
+    if context.note_in.start_trigger
+        static.rng = NoteRng(context)
+    end
 
     for s in sample_indices(MonoAudio)
         delay_now = max_delay[%, 1, s] * delay_mul[%, 1, s]
         time_now = timing[%, 1, s]
         if delay_now <= 1f0 / sample_rate
             static.old_value = static.new_value
-            static.new_value = rand()
+            static.new_value = next_random!(static.rng)
             value_now = static.old_value
             static.old_value_time = time_now
         else
             if static.old_value_time + delay_now <= time_now
                 static.old_value = static.new_value
-                static.new_value = rand()
+                static.new_value = next_random!(static.rng)
                 static.old_value_time += delay_now
             end
             factor = (time_now - static.old_value_time) / delay_now
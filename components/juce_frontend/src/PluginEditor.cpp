@@ -328,7 +328,19 @@ void AudiobenchAudioProcessorEditor::mouseWheelMove(const MouseEvent &event, con
 
 bool AudiobenchAudioProcessorEditor::keyPressed(const KeyPress &key, Component *originatingComponent)
 {
-    ABUiKeyPress(processor.ab, (char)key.getTextCharacter());
+    // Undo and redo are sent as the ASCII control characters Ctrl+Z and Ctrl+Y produce.
+    if (key == KeyPress('z', ModifierKeys::commandModifier, 0))
+    {
+        ABUiKeyPress(processor.ab, '\x1A');
+    }
+    else if (key == KeyPress('y', ModifierKeys::commandModifier, 0) || key == KeyPress('z', ModifierKeys::commandModifier | ModifierKeys::shiftModifier, 0))
+    {
+        ABUiKeyPress(processor.ab, '\x19');
+    }
+    else
+    {
+        ABUiKeyPress(processor.ab, (char)key.getTextCharacter());
+    }
     return true;
 }
 
@@ -29,6 +29,8 @@ AudiobenchAudioProcessor::AudiobenchAudioProcessor()
 #endif
 {
     ab = ABCreateInstance();
+    addParameter(morph =
+                     new AudioParameterFloat("morph", "Morph", 0.0f, 1.0f, 0.0f));
 }
 
 AudiobenchAudioProcessor::~AudiobenchAudioProcessor() { ABDestroyInstance(ab); }
@@ -181,6 +183,14 @@ void AudiobenchAudioProcessor::processBlock(AudioBuffer<float>& buffer,
     for (auto i = totalNumInputChannels; i < totalNumOutputChannels; ++i)
         buffer.clear(i, 0, buffer.getNumSamples());
 
+    // The morph amount can also be changed from the GUI or by a MIDI
+    // controller, so it is only sent when the host changes it.
+    float morphAmount = *morph;
+    if (morphAmount != lastMorphAmount) {
+        ABAudioMorph(ab, morphAmount);
+        lastMorphAmount = morphAmount;
+    }
+
     float* audioBuffer = ABAudioRenderAudio(ab);
 
     // This is the place where you'd normally do the guts of your plugin's
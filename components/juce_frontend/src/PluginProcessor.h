@@ -59,6 +59,9 @@ public:
     ABInstanceRef ab;
 
 private:
+    AudioParameterFloat* morph;
+    float lastMorphAmount = 0.0f;
+
     //==============================================================================
     JUCE_DECLARE_NON_COPYABLE_WITH_LEAK_DETECTOR (AudiobenchAudioProcessor)
 };
@@ -85,6 +85,10 @@ impl FileClip {
     pub fn contains(&self, pattern: &str) -> bool {
         self.data.as_str().contains(pattern)
     }
+
+    pub fn as_str(&self) -> &str {
+        self.data.as_str()
+    }
 }
 
 /// Represents a segment of Julia code assemblied from zero or more smaller pieces. Keeps track of
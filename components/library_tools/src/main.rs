@@ -0,0 +1,172 @@
+use audiobench::*;
+use std::path::PathBuf;
+
+const USAGE: &str = "\
+Usage: library_tools <COMMAND> [OPTIONS]
+
+Tools for people making Audiobench libraries.
+
+Commands:
+  lint <PATH>...          Load each library (a folder or .ablib file) the same way Audiobench
+                          would and report every problem found. Libraries it depends on are
+                          looked for next to it.
+  new-module <LIBRARY> <NAME>
+                          Create the files for a new module in a library stored as a folder. The
+                          module gets an unused save id, a widget for every control and a
+                          skeleton exec() function.
+
+Options:
+  --deny-warnings         lint: Exit with an error if there are any warnings, not just errors
+  --category <NAME>       new-module: Category shown in the module browser [default: Utility]
+  --input <NAME:TYPE>     new-module: Add an input, TYPE is audio, pitch, trigger or waveform
+  --control <NAME:TYPE>   new-module: Add a control, TYPE is FloatInRange, Int, Frequency,
+                          OptionChoice, TimingMode or Duration
+  --output <NAME:TYPE>    new-module: Add an output, TYPE is audio, pitch, trigger or waveform
+  --help                  Print this message
+";
+
+enum Command {
+    Lint(Vec<PathBuf>),
+    NewModule(PathBuf, ModuleSpec),
+}
+
+struct Options {
+    command: Command,
+    deny_warnings: bool,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let command_name = args.next();
+        let mut positional = Vec::new();
+        let mut deny_warnings = false;
+        let mut category = "Utility".to_owned();
+        let mut inputs = Vec::new();
+        let mut controls = Vec::new();
+        let mut outputs = Vec::new();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("ERROR: {} requires a value.", name))
+            };
+            match &arg[..] {
+                "--deny-warnings" => deny_warnings = true,
+                "--category" => category = value(&arg)?,
+                "--input" => inputs.push(parse_typed_name(&arg, value(&arg)?)?),
+                "--control" => controls.push(parse_typed_name(&arg, value(&arg)?)?),
+                "--output" => outputs.push(parse_typed_name(&arg, value(&arg)?)?),
+                "--help" | "-h" => {
+                    print!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                _ if arg.starts_with('-') => {
+                    return Err(format!(
+                        "ERROR: Unrecognized argument {}.\n\n{}",
+                        arg, USAGE
+                    ))
+                }
+                _ => positional.push(PathBuf::from(arg)),
+            }
+        }
+        let command = match command_name.as_deref() {
+            Some("lint") => {
+                if positional.is_empty() {
+                    return Err("ERROR: lint requires at least one library to check.".to_owned());
+                }
+                Command::Lint(positional)
+            }
+            Some("new-module") => {
+                if positional.len() != 2 {
+                    return Err(
+                        "ERROR: new-module requires a library folder and a module name.".to_owned(),
+                    );
+                }
+                let name = positional.pop().unwrap().to_string_lossy().into();
+                let spec = ModuleSpec {
+                    name,
+                    category,
+                    inputs,
+                    controls,
+                    outputs,
+                };
+                Command::NewModule(positional.pop().unwrap(), spec)
+            }
+            Some("--help") | Some("-h") => {
+                print!("{}", USAGE);
+                std::process::exit(0);
+            }
+            Some(other) => {
+                return Err(format!(
+                    "ERROR: Unrecognized command {}.\n\n{}",
+                    other, USAGE
+                ))
+            }
+            None => return Err(format!("ERROR: No command specified.\n\n{}", USAGE)),
+        };
+        Ok(Self {
+            command,
+            deny_warnings,
+        })
+    }
+}
+
+/// Splits something like `gain:FloatInRange` into a name and a type.
+fn parse_typed_name(name: &str, value: String) -> Result<(String, String), String> {
+    let colon = value.find(':').ok_or_else(|| {
+        format!(
+            "ERROR: {} is not a valid value for {}, expected NAME:TYPE.",
+            value, name
+        )
+    })?;
+    Ok((value[..colon].to_owned(), value[colon + 1..].to_owned()))
+}
+
+/// Returns false if any of the libraries had problems.
+fn lint(paths: &[PathBuf], deny_warnings: bool) -> bool {
+    let mut success = true;
+    for path in paths {
+        let problems = lint_library(path);
+        if problems.is_empty() {
+            eprintln!("{}: no problems found.", path.to_string_lossy());
+            continue;
+        }
+        eprintln!(
+            "{}: found {} problem(s):",
+            path.to_string_lossy(),
+            problems.len()
+        );
+        for problem in &problems {
+            eprintln!("\n{}", problem);
+            if deny_warnings || !problem.starts_with("WARNING") {
+                success = false;
+            }
+        }
+        eprintln!();
+    }
+    success
+}
+
+fn run() -> Result<bool, String> {
+    let options = Options::parse(std::env::args().skip(1))?;
+    match &options.command {
+        Command::Lint(paths) => Ok(lint(paths, options.deny_warnings)),
+        Command::NewModule(library_path, spec) => {
+            for path in create_module(library_path, spec)? {
+                eprintln!("Created {}", path.to_string_lossy());
+            }
+            // Make sure the library still loads with the new module in it.
+            Ok(lint(&[library_path.clone()], false))
+        }
+    }
+}
+
+fn main() {
+    match run() {
+        Ok(true) => (),
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    }
+}
@@ -18,6 +18,7 @@ pub struct TextField {
     pub text: String,
     pub(crate) focused: bool,
     pub(crate) on_defocus: Box<dyn Fn(&str)>,
+    pub(crate) on_change: Box<dyn Fn(&str)>,
 }
 
 impl TextField {
@@ -26,12 +27,18 @@ impl TextField {
             text: initial_contents.into(),
             focused: false,
             on_defocus,
+            on_change: Box::new(|_| ()),
         }
     }
 
     pub fn is_focused(&self) -> bool {
         self.focused
     }
+
+    /// `on_change` is called after every key press which changes the text.
+    pub fn set_on_change(&mut self, on_change: Box<dyn Fn(&str)>) {
+        self.on_change = on_change;
+    }
 }
 
 /// Acts like a more transparent version of Option<>. It automatically derefs to the templated type,
@@ -175,8 +175,9 @@ impl<State, DT, RW> Gui<State, DT, RW> {
         // For some reason JUCE gives \r instead of \n.
         let key = if key == '\r' { '\n' } else { key };
         let mut internal = self.interface.internal_state.borrow_mut();
-        if let Some(field) = &mut internal.focused_text_field {
-            let mut field = field.borrow_mut();
+        if let Some(field) = &internal.focused_text_field {
+            let field_rc = Rc::clone(field);
+            let mut field = field_rc.borrow_mut();
             match key {
                 '\x08' | '\x7F' => {
                     // ASCII delete or backspace.
@@ -189,11 +190,16 @@ impl<State, DT, RW> Gui<State, DT, RW> {
                     // Escape or enter
                     drop(field);
                     internal.defocus_text_field();
+                    return;
                 }
                 _ => {
                     field.text.push(key);
                 }
             }
+            drop(field);
+            drop(internal);
+            let field = field_rc.borrow();
+            (field.on_change)(&field.text);
         }
     }
 
@@ -15,10 +15,13 @@ pub mod mini_serde;
 pub mod perf_counter;
 pub mod prelude;
 pub use nvec::*;
-pub use perf_counter::{NoopPerfCounter, PerfCounter, PerfSectionGuard, SimplePerfCounter};
+pub use perf_counter::{
+    AtomicPerfCounter, NoopPerfCounter, PerfCounter, PerfSectionGuard, SimplePerfCounter,
+};
 pub use search::*;
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+// Fields are ordered from most to least significant so that the derived ordering is correct.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct Version {
     pub maj: u8,
     pub min: u8,
@@ -1,6 +1,35 @@
 use crate::{prelude::FloatUtil, Version};
 use bitvec::prelude::*;
 
+/// Serialized data that was read without being interpreted, so that it can be written back
+/// unchanged.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RawSection {
+    bits: BitVec<Lsb0, u8>,
+}
+
+impl RawSection {
+    /// Writes the data as a string of 0s and 1s, which `from_bit_string` reads back.
+    pub fn to_bit_string(&self) -> String {
+        self.bits
+            .iter()
+            .map(|bit| if *bit { '1' } else { '0' })
+            .collect()
+    }
+
+    pub fn from_bit_string(text: &str) -> Result<Self, ()> {
+        let mut bits = BitVec::new();
+        for c in text.chars() {
+            match c {
+                '0' => bits.push(false),
+                '1' => bits.push(true),
+                _ => return Err(()),
+            }
+        }
+        Ok(Self { bits })
+    }
+}
+
 #[scones::make_constructor]
 pub struct MiniSer {
     #[value(BitVec::new())]
@@ -34,6 +63,52 @@ impl MiniSer {
         }
     }
 
+    /// Writes whatever `write` serializes prefixed with its length in bits. When reading it back
+    /// with `MiniDes::section`, data the reader does not understand can be skipped or kept as a
+    /// `RawSection`.
+    pub fn section(&mut self, write: impl FnOnce(&mut MiniSer)) {
+        let mut inner = MiniSer::new();
+        write(&mut inner);
+        self.note("{ ");
+        self.pause_debug_content = true;
+        self.varint(inner.bits.len());
+        self.pause_debug_content = false;
+        self.bits.extend_from_bitslice(&inner.bits);
+        self.note(&inner.debug_content);
+        self.note("} ");
+    }
+
+    /// Like `section` but also writes `tag`, which tells the reader what the section contains. A
+    /// reader which does not recognize the tag can skip the section, so data a newer version adds
+    /// in its own tagged section does not prevent older versions from reading the rest.
+    pub fn tagged_section(&mut self, tag: usize, write: impl FnOnce(&mut MiniSer)) {
+        self.note(&format!("#{} ", tag));
+        self.pause_debug_content = true;
+        self.varint(tag);
+        self.pause_debug_content = false;
+        self.section(write);
+    }
+
+    /// Like `section` but followed by a checksum of its contents, which `MiniDes::checked_section`
+    /// uses to detect data that was damaged or cut short.
+    pub fn checked_section(&mut self, write: impl FnOnce(&mut MiniSer)) {
+        let mut inner = MiniSer::new();
+        write(&mut inner);
+        let sum = checksum(&inner.bits);
+        self.section(|ser| {
+            ser.bits = inner.bits;
+            ser.debug_content = inner.debug_content;
+        });
+        self.note("checksum ");
+        self.u16(sum);
+    }
+
+    /// Writes data previously read with `MiniDes::rest`.
+    pub fn raw(&mut self, data: &RawSection) {
+        self.bits.extend_from_bitslice(&data.bits);
+        self.note(&format!("({} raw bits) ", data.bits.len()));
+    }
+
     pub fn version(&mut self, v: Version) {
         self.note("(");
         self.u4(v.maj);
@@ -53,6 +128,49 @@ impl MiniSer {
         self.note(&format!("{} ", value));
     }
 
+    /// Writes a number of any size using as few groups of four bits as possible. Each group is
+    /// followed by a bit saying whether another group comes after it, so numbers below 16 take up
+    /// five bits.
+    pub fn varint(&mut self, value: usize) {
+        self.pause_debug_content = true;
+        let mut rest = value;
+        loop {
+            self.u4((rest & 0b1111) as u8);
+            rest >>= 4;
+            self.bool(rest > 0);
+            if rest == 0 {
+                break;
+            }
+        }
+        self.pause_debug_content = false;
+        self.note(&format!("{} ", value));
+    }
+
+    /// Like `varint` but for numbers which can be negative. Numbers close to zero take up the
+    /// least space.
+    pub fn ivarint(&mut self, value: isize) {
+        self.pause_debug_content = true;
+        // Zigzag encoding, 0 -> 0, -1 -> 1, 1 -> 2, -2 -> 3...
+        let bits = std::mem::size_of::<isize>() * 8 - 1;
+        self.varint(((value << 1) ^ (value >> bits)) as usize);
+        self.pause_debug_content = false;
+        self.note(&format!("{} ", value));
+    }
+
+    /// Writes a u8 if the value is less than 255, otherwise 255 followed by the value as a varint.
+    /// This lets data which used to be written with `u8` grow past 255 while staying readable.
+    pub fn u8_or_varint(&mut self, value: usize) {
+        if value < 0xFF {
+            self.u8(value as u8);
+        } else {
+            self.pause_debug_content = true;
+            self.u8(0xFF);
+            self.varint(value);
+            self.pause_debug_content = false;
+            self.note(&format!("{} ", value));
+        }
+    }
+
     pub fn u1(&mut self, value: u8) {
         self.uint(value as usize, 1);
     }
@@ -128,6 +246,20 @@ impl MiniSer {
     }
 }
 
+/// Fletcher-16 checksum of `bits`, padded with zeros to a whole number of bytes.
+fn checksum(bits: &BitSlice<Lsb0, u8>) -> u16 {
+    let (mut sum1, mut sum2) = (0u16, 0u16);
+    for chunk in bits.chunks(8) {
+        let byte = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |byte, (index, bit)| byte | ((*bit as u16) << index));
+        sum1 = (sum1 + byte) % 255;
+        sum2 = (sum2 + sum1) % 255;
+    }
+    (sum2 << 8) | sum1
+}
+
 pub struct MiniDes {
     bits: BitVec<Lsb0, u8>,
     read_ptr: usize,
@@ -141,6 +273,14 @@ impl MiniDes {
         }
     }
 
+    /// Reads data previously read with `rest`.
+    pub fn start_raw(data: RawSection) -> Self {
+        Self {
+            bits: data.bits,
+            read_ptr: 0,
+        }
+    }
+
     pub fn end(mut self) -> Vec<u8> {
         let mut remainder = Vec::new();
         // Keep reading u8s as long as we can read at least 8 more bits.
@@ -164,6 +304,69 @@ impl MiniDes {
         res
     }
 
+    /// Reads a section written by `MiniSer::section`. `read` is given a deserializer containing
+    /// only the data in the section, anything it does not read is skipped.
+    pub fn section<T, E: From<()>>(
+        &mut self,
+        read: impl FnOnce(&mut MiniDes) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let len = self.varint()?;
+        // Compared this way around since a corrupt length could overflow `read_ptr + len`.
+        if len > self.remaining_bits() {
+            return Err(().into());
+        }
+        let mut inner = MiniDes {
+            bits: self.bits[self.read_ptr..self.read_ptr + len].to_bitvec(),
+            read_ptr: 0,
+        };
+        self.read_ptr += len;
+        read(&mut inner)
+    }
+
+    /// Reads a section written by `MiniSer::tagged_section`. `read` is given the tag and anything
+    /// it does not read, which is all of it if it does not recognize the tag, is skipped.
+    pub fn tagged_section<T, E: From<()>>(
+        &mut self,
+        read: impl FnOnce(usize, &mut MiniDes) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let tag = self.varint()?;
+        self.section(|des| read(tag, des))
+    }
+
+    /// Reads a section written by `MiniSer::checked_section`, returning an error without calling
+    /// `read` if the checksum does not match.
+    pub fn checked_section<T, E: From<()>>(
+        &mut self,
+        read: impl FnOnce(&mut MiniDes) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let len = self.varint()?;
+        // Compared this way around since a corrupt length could overflow `read_ptr + len`.
+        if len > self.remaining_bits() {
+            return Err(().into());
+        }
+        let mut inner = MiniDes {
+            bits: self.bits[self.read_ptr..self.read_ptr + len].to_bitvec(),
+            read_ptr: 0,
+        };
+        self.read_ptr += len;
+        if self.u16()? != checksum(&inner.bits) {
+            return Err(().into());
+        }
+        read(&mut inner)
+    }
+
+    pub fn remaining_bits(&self) -> usize {
+        self.bits.len() - self.read_ptr
+    }
+
+    /// Reads everything that has not been read yet without interpreting it. This is mostly useful
+    /// inside `section`.
+    pub fn rest(&mut self) -> RawSection {
+        let bits = self.bits[self.read_ptr..].to_bitvec();
+        self.read_ptr = self.bits.len();
+        RawSection { bits }
+    }
+
     pub fn version(&mut self) -> Result<Version, ()> {
         Ok(Version {
             maj: self.u4()?,
@@ -185,6 +388,38 @@ impl MiniDes {
         Ok(value)
     }
 
+    /// Reads a number written by `MiniSer::varint`.
+    pub fn varint(&mut self) -> Result<usize, ()> {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let group = self.u4()? as usize;
+            // Data that does not fit in a usize can only be corrupt.
+            if shift >= std::mem::size_of::<usize>() * 8 || (group << shift) >> shift != group {
+                return Err(());
+            }
+            value |= group << shift;
+            shift += 4;
+            if !self.bool()? {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Reads a number written by `MiniSer::ivarint`.
+    pub fn ivarint(&mut self) -> Result<isize, ()> {
+        let value = self.varint()?;
+        Ok((value >> 1) as isize ^ -((value & 1) as isize))
+    }
+
+    /// Reads a number written by `MiniSer::u8_or_varint`.
+    pub fn u8_or_varint(&mut self) -> Result<usize, ()> {
+        match self.u8()? {
+            0xFF => self.varint(),
+            value => Ok(value as usize),
+        }
+    }
+
     pub fn u1(&mut self) -> Result<u8, ()> {
         Ok(self.uint(1)? as u8)
     }
@@ -243,3 +478,153 @@ impl MiniDes {
         String::from_utf8(bytes).map_err(|_| ())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(write: impl FnOnce(&mut MiniSer)) -> MiniDes {
+        let mut ser = MiniSer::new();
+        write(&mut ser);
+        MiniDes::start(ser.finish())
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        let values = [0, 1, 15, 16, 255, 256, 0xFFFF, std::usize::MAX];
+        let mut des = round_trip(|ser| {
+            for value in &values {
+                ser.varint(*value);
+            }
+        });
+        for value in &values {
+            assert_eq!(des.varint(), Ok(*value));
+        }
+    }
+
+    #[test]
+    fn small_varint_is_small() {
+        let mut ser = MiniSer::new();
+        ser.varint(15);
+        assert_eq!(ser.bits.len(), 5);
+    }
+
+    #[test]
+    fn ivarint_round_trip() {
+        let values = [0, -1, 1, -300, 300, std::isize::MIN, std::isize::MAX];
+        let mut des = round_trip(|ser| {
+            for value in &values {
+                ser.ivarint(*value);
+            }
+        });
+        for value in &values {
+            assert_eq!(des.ivarint(), Ok(*value));
+        }
+    }
+
+    #[test]
+    fn overlong_varint_is_an_error() {
+        let mut des = round_trip(|ser| {
+            for _ in 0..20 {
+                ser.u4(0b1111);
+                ser.bool(true);
+            }
+        });
+        assert_eq!(des.varint(), Err(()));
+    }
+
+    #[test]
+    fn u8_or_varint_round_trip() {
+        let values = [0, 254, 255, 256, 100_000];
+        let mut des = round_trip(|ser| {
+            ser.u8(17);
+            for value in &values {
+                ser.u8_or_varint(*value);
+            }
+        });
+        assert_eq!(des.u8_or_varint(), Ok(17));
+        for value in &values {
+            assert_eq!(des.u8_or_varint(), Ok(*value));
+        }
+    }
+
+    #[test]
+    fn unknown_tagged_section_is_skipped() {
+        let mut des = round_trip(|ser| {
+            ser.tagged_section(7, |ser| ser.u32(0xDEADBEEF));
+            ser.tagged_section(1, |ser| ser.u8(3));
+        });
+        assert_eq!(des.tagged_section(|tag, _| Ok::<_, ()>(tag)), Ok(7));
+        let value = des.tagged_section(|tag, des| {
+            assert_eq!(tag, 1);
+            des.u8()
+        });
+        assert_eq!(value, Ok(3));
+    }
+
+    #[test]
+    fn checked_section_round_trip() {
+        let mut ser = MiniSer::new();
+        ser.u1(1);
+        ser.checked_section(|ser| ser.str("Hello"));
+        ser.u8(9);
+        let data = ser.finish();
+
+        let mut des = MiniDes::start(data.clone());
+        assert_eq!(des.u1(), Ok(1));
+        assert_eq!(des.checked_section(|des| des.str()), Ok("Hello".to_owned()));
+        assert_eq!(des.u8(), Ok(9));
+
+        let mut damaged = data;
+        damaged[2] ^= 0b100;
+        let mut des = MiniDes::start(damaged);
+        assert_eq!(des.u1(), Ok(1));
+        assert_eq!(des.checked_section(|des| des.str()), Err(()));
+    }
+
+    #[test]
+    fn oversized_section_length_is_an_error() {
+        for len in &[100, std::usize::MAX] {
+            let data = round_trip(|ser| {
+                ser.varint(*len);
+                ser.u16(0);
+            })
+            .end();
+            let mut des = MiniDes::start(data.clone());
+            assert_eq!(des.section(|_| Ok::<_, ()>(())), Err(()));
+            let mut des = MiniDes::start(data);
+            assert_eq!(des.checked_section(|_| Ok::<_, ()>(())), Err(()));
+        }
+    }
+
+    #[test]
+    fn bit_string_round_trip() {
+        let mut des = round_trip(|ser| ser.u5(0b10110));
+        let raw = des.rest();
+        let text = raw.to_bit_string();
+        assert_eq!(&text[..5], "01101");
+        assert_eq!(RawSection::from_bit_string(&text), Ok(raw));
+        assert_eq!(RawSection::from_bit_string("0120"), Err(()));
+    }
+
+    #[test]
+    fn large_section_round_trip() {
+        let mut des = round_trip(|ser| {
+            ser.section(|ser| {
+                for _ in 0..5000 {
+                    ser.u16(0xABCD);
+                }
+            });
+            ser.u8(42);
+        });
+        let count = des.section(|des| {
+            let mut count = 0;
+            while des.u16() == Ok(0xABCD) {
+                count += 1;
+            }
+            Ok::<_, ()>(count)
+        });
+        assert_eq!(count, Ok(5000));
+        assert_eq!(des.u8(), Ok(42));
+    }
+}
@@ -1,5 +1,8 @@
 use crate::prelude::*;
-use std::time::{Duration, Instant};
+use std::{
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
 
 pub struct PerfCountSection {
     index: usize,
@@ -9,79 +12,34 @@ pub struct PerfCountSection {
 pub mod sections {
     use super::PerfCountSection;
 
-    pub const GLOBAL_SETUP: PerfCountSection = PerfCountSection {
+    pub const GENERATE_CODE: PerfCountSection = PerfCountSection {
         index: 0,
-        name: "Global Setup",
+        name: "Generate Code",
     };
-    pub const NOTE_SETUP: PerfCountSection = PerfCountSection {
+    pub const COMPILE_CODE: PerfCountSection = PerfCountSection {
         index: 1,
-        name: "Note Setup",
+        name: "Compile Code",
     };
-    pub const NODESPEAK_EXEC: PerfCountSection = PerfCountSection {
+    pub const COLLECT_DYN_DATA: PerfCountSection = PerfCountSection {
         index: 2,
-        name: "Nodespeak Exec",
+        name: "Collect Dyn Data",
     };
-    pub const NOTE_FINALIZE: PerfCountSection = PerfCountSection {
+    pub const NOTE_SETUP: PerfCountSection = PerfCountSection {
         index: 3,
-        name: "Note Finalize",
+        name: "Note Setup",
     };
-    pub const GLOBAL_FINALIZE: PerfCountSection = PerfCountSection {
+    pub const JULIA_EXEC: PerfCountSection = PerfCountSection {
         index: 4,
-        name: "Global Finalize",
-    };
-    pub const GENERATE_CODE: PerfCountSection = PerfCountSection {
-        index: 8,
-        name: "Generate Code",
-    };
-    pub const COMPILE_CODE: PerfCountSection = PerfCountSection {
-        index: 5,
-        name: "Compile Code",
-    };
-    pub const COLLECT_AUTOCON_DATA: PerfCountSection = PerfCountSection {
-        index: 6,
-        name: "Collect FloatInRangeControl Data",
-    };
-    pub const COLLECT_CONTROL_DATA: PerfCountSection = PerfCountSection {
-        index: 7,
-        name: "Collect Control Data",
-    };
-    pub const COMPILER_AST_PHASE: PerfCountSection = PerfCountSection {
-        index: 9,
-        name: "Compiler AST Phase",
-    };
-    pub const COMPILER_VAGUE_PHASE: PerfCountSection = PerfCountSection {
-        index: 10,
-        name: "Compiler Vague Phase",
-    };
-    pub const COMPILER_RESOLVED_PHASE: PerfCountSection = PerfCountSection {
-        index: 11,
-        name: "Compiler Resolved Phase",
-    };
-    pub const COMPILER_TRIVIAL_PHASE: PerfCountSection = PerfCountSection {
-        index: 12,
-        name: "Compiler Trivial Phase",
-    };
-    pub const COMPILER_LLVMIR_PHASE: PerfCountSection = PerfCountSection {
-        index: 13,
-        name: "Compiler LLVMIR Phase",
+        name: "Julia Exec",
     };
 
-    pub const NUM_SECTIONS: usize = 14;
+    pub const NUM_SECTIONS: usize = 5;
     pub const ALL_SECTIONS: [&'static PerfCountSection; NUM_SECTIONS] = [
         &GENERATE_CODE,
         &COMPILE_CODE,
-        &COMPILER_AST_PHASE,
-        &COMPILER_VAGUE_PHASE,
-        &COMPILER_RESOLVED_PHASE,
-        &COMPILER_TRIVIAL_PHASE,
-        &COMPILER_LLVMIR_PHASE,
-        &COLLECT_AUTOCON_DATA,
-        &COLLECT_CONTROL_DATA,
-        &GLOBAL_SETUP,
+        &COLLECT_DYN_DATA,
         &NOTE_SETUP,
-        &NODESPEAK_EXEC,
-        &NOTE_FINALIZE,
-        &GLOBAL_FINALIZE,
+        &JULIA_EXEC,
     ];
 }
 
@@ -174,30 +132,98 @@ impl PerfCounter for SimplePerfCounter {
     }
 
     fn report(&self) -> String {
-        let mut report = String::new();
-        report +=
-            &format!("SECTION NAME                   | TOTAL TIME | SAMPLES | TIME PER SAMPLE \n");
-        let mut everything_time = 0.0;
-        for section in &sections::ALL_SECTIONS {
-            let invocations = self.num_invocations[section.index];
-            if invocations == 0 {
-                continue;
-            }
-            let total_time = self.cumulative_time[section.index].as_secs_f64();
-            everything_time += total_time;
-            let average_time = total_time / (invocations as f64);
-            report += &format!(
-                "{:<30} | {:>10} | {:>7} | {:>15} \n",
-                section.name,
-                total_time.format_metric(6, "s"),
-                invocations,
-                average_time.format_metric(6, "s")
-            );
+        format_report(|index| (self.num_invocations[index], self.cumulative_time[index]))
+    }
+}
+
+/// Same statistics as SimplePerfCounter, but it can be shared between threads and updated without
+/// locking. This makes it safe to use on the audio thread.
+pub struct AtomicPerfCounter {
+    num_invocations: [AtomicU32; NUM_SECTIONS],
+    cumulative_nanos: [AtomicU64; NUM_SECTIONS],
+}
+
+impl AtomicPerfCounter {
+    pub fn new() -> Self {
+        Self {
+            num_invocations: Default::default(),
+            cumulative_nanos: Default::default(),
+        }
+    }
+
+    pub fn begin_section(&self, section: &PerfCountSection) -> PerfSectionGuard {
+        PerfSectionGuard {
+            section_index: section.index,
+            start_time: Instant::now(),
+            handled: false,
+        }
+    }
+
+    pub fn end_section(&self, mut section: PerfSectionGuard) {
+        let time = section.start_time.elapsed();
+        self.add_time(section.section_index, time);
+        section.handled = true;
+    }
+
+    pub fn add_externally_timed_section(&self, section: &PerfCountSection, duration: Duration) {
+        self.add_time(section.index, duration);
+    }
+
+    fn add_time(&self, index: usize, duration: Duration) {
+        self.cumulative_nanos[index].fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+        self.num_invocations[index].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        for index in 0..NUM_SECTIONS {
+            self.num_invocations[index].store(0, Ordering::Relaxed);
+            self.cumulative_nanos[index].store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn get_index(&self, index: usize) -> (u32, Duration) {
+        (
+            self.num_invocations[index].load(Ordering::Relaxed),
+            Duration::from_nanos(self.cumulative_nanos[index].load(Ordering::Relaxed)),
+        )
+    }
+
+    /// Returns how many times the section was timed and how much time was spent in it in total.
+    pub fn get_section(&self, section: &PerfCountSection) -> (u32, Duration) {
+        self.get_index(section.index)
+    }
+
+    pub fn report(&self) -> String {
+        format_report(|index| self.get_index(index))
+    }
+}
+
+/// Builds a table of every section which was invoked at least once. `get_section` takes the index
+/// of a section and returns how many times it was invoked and the total time spent in it.
+fn format_report(get_section: impl Fn(usize) -> (u32, Duration)) -> String {
+    let mut report = String::new();
+    report +=
+        &format!("SECTION NAME                   | TOTAL TIME | SAMPLES | TIME PER SAMPLE \n");
+    let mut everything_time = 0.0;
+    for section in &sections::ALL_SECTIONS {
+        let (invocations, total_time) = get_section(section.index);
+        if invocations == 0 {
+            continue;
         }
+        let total_time = total_time.as_secs_f64();
+        everything_time += total_time;
+        let average_time = total_time / (invocations as f64);
         report += &format!(
-            "                                 {:>10}",
-            everything_time.format_metric(6, "s")
+            "{:<30} | {:>10} | {:>7} | {:>15} \n",
+            section.name,
+            total_time.format_metric(6, "s"),
+            invocations,
+            average_time.format_metric(6, "s")
         );
-        report
     }
+    report += &format!(
+        "                                 {:>10}",
+        everything_time.format_metric(6, "s")
+    );
+    report
 }